@@ -0,0 +1,62 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::hint::black_box;
+
+use clipster::audio_processing::{normalize_audio, resample_to_16khz};
+
+const SAMPLE_RATES: [u32; 2] = [44100, 48000];
+const DURATIONS_SECS: [u32; 3] = [3, 10, 30];
+
+/// Speech-like test signal: a few mixed tones with a slow amplitude envelope
+fn synthetic_recording(sample_rate: u32, secs: u32) -> Vec<f32> {
+    let len = (sample_rate * secs) as usize;
+    (0..len)
+        .map(|i| {
+            let t = i as f32 / sample_rate as f32;
+            let envelope = 0.5 + 0.5 * (t * 2.0 * std::f32::consts::PI * 0.5).sin();
+            let tone = (t * 2.0 * std::f32::consts::PI * 220.0).sin()
+                + 0.5 * (t * 2.0 * std::f32::consts::PI * 440.0).sin()
+                + 0.25 * (t * 2.0 * std::f32::consts::PI * 1320.0).sin();
+            0.3 * envelope * tone
+        })
+        .collect()
+}
+
+fn bench_resample(c: &mut Criterion) {
+    let mut group = c.benchmark_group("resample_to_16khz");
+    group.sample_size(10);
+
+    for sample_rate in SAMPLE_RATES {
+        for secs in DURATIONS_SECS {
+            let samples = synthetic_recording(sample_rate, secs);
+            group.throughput(Throughput::Elements(samples.len() as u64));
+            group.bench_with_input(
+                BenchmarkId::new(format!("{}hz", sample_rate), format!("{}s", secs)),
+                &samples,
+                |b, samples| b.iter(|| resample_to_16khz(black_box(samples), sample_rate).unwrap()),
+            );
+        }
+    }
+
+    group.finish();
+}
+
+fn bench_normalize(c: &mut Criterion) {
+    let mut group = c.benchmark_group("normalize_audio");
+
+    for sample_rate in SAMPLE_RATES {
+        for secs in DURATIONS_SECS {
+            let samples = synthetic_recording(sample_rate, secs);
+            group.throughput(Throughput::Elements(samples.len() as u64));
+            group.bench_with_input(
+                BenchmarkId::new(format!("{}hz", sample_rate), format!("{}s", secs)),
+                &samples,
+                |b, samples| b.iter(|| normalize_audio(black_box(samples))),
+            );
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_resample, bench_normalize);
+criterion_main!(benches);
@@ -0,0 +1,14 @@
+use std::process::Command;
+
+fn main() {
+    // Short commit hash for `--version`, skipped when building outside a git checkout
+    if let Ok(output) = Command::new("git").args(["rev-parse", "--short", "HEAD"]).output() {
+        if output.status.success() {
+            let commit = String::from_utf8_lossy(&output.stdout);
+            println!("cargo:rustc-env=CLIPSTER_GIT_COMMIT={}", commit.trim());
+        }
+    }
+
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs/heads");
+}
@@ -0,0 +1,183 @@
+use async_trait::async_trait;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+
+use crate::ai::remote::traits::{Message, TextStream, Usage, AI};
+use crate::error::ClipsterError;
+
+/// Tries providers in order until one answers, so an outage or rate limit at the first
+/// doesn't lose the request. Fails with the last provider's error when none succeed.
+#[derive(Debug, Clone)]
+pub struct FallbackAI {
+    providers: Vec<Arc<Box<dyn AI>>>,
+    /// Name of the provider that answered the last request
+    responder: Arc<Mutex<Option<String>>>,
+}
+
+impl FallbackAI {
+    pub fn new(providers: Vec<Arc<Box<dyn AI>>>) -> Self {
+        Self { providers, responder: Arc::default() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.providers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.providers.is_empty()
+    }
+
+    /// Calls `request` on each provider in turn, returning the first success and remembering who gave it
+    async fn first_success<'a, T, F>(&'a self, mut request: F) -> Result<T, Box<dyn Error>>
+    where
+        F: FnMut(&'a Arc<Box<dyn AI>>) -> futures_util::future::BoxFuture<'a, Result<T, Box<dyn Error>>>,
+    {
+        let (last, rest) = self
+            .providers
+            .split_last()
+            .ok_or_else(|| ClipsterError::InvalidConfig("The fallback chain has no providers".to_string()))?;
+
+        for (index, ai) in rest.iter().enumerate() {
+            // Errors aren't `Send`, so each one is dealt with before the next request is awaited
+            let error = match request(ai).await {
+                Ok(result) => {
+                    self.answered(ai);
+                    return Ok(result);
+                }
+                Err(e) => e.to_string(),
+            };
+            eprintln!("{} failed: {}, trying {}", ai.name(), error, self.providers[index + 1].name());
+        }
+
+        let result = request(last).await?;
+        self.answered(last);
+        Ok(result)
+    }
+
+    fn answered(&self, ai: &Arc<Box<dyn AI>>) {
+        *self.responder.lock().unwrap() = Some(ai.responder());
+    }
+}
+
+#[async_trait]
+impl AI for FallbackAI {
+    /// `fallback(anthropic:… -> openai:…)`
+    fn name(&self) -> String {
+        let names: Vec<String> = self.providers.iter().map(|ai| ai.name()).collect();
+        format!("fallback({})", names.join(" -> "))
+    }
+
+    /// The provider that answered the last request, the chain's own name before the first one
+    fn responder(&self) -> String {
+        self.responder.lock().unwrap().clone().unwrap_or_else(|| self.name())
+    }
+
+    /// Only when every provider does, the answer may come from any of them
+    fn returns_json(&self) -> bool {
+        !self.providers.is_empty() && self.providers.iter().all(|ai| ai.returns_json())
+    }
+
+    async fn chat(&self, messages: Vec<Message>) -> Result<String, Box<dyn Error>> {
+        self.first_success(|ai| ai.chat(messages.clone())).await
+    }
+
+    async fn chat_with_usage(&self, messages: Vec<Message>) -> Result<(String, Option<Usage>), Box<dyn Error>> {
+        self.first_success(|ai| ai.chat_with_usage(messages.clone())).await
+    }
+
+    /// Falls back only while opening the stream, a stream that breaks off midway isn't restarted
+    async fn chat_stream(&self, messages: Vec<Message>) -> Result<TextStream, Box<dyn Error>> {
+        self.first_success(|ai| ai.chat_stream(messages.clone())).await
+    }
+
+    /// Warms every provider, one failing doesn't keep the rest cold
+    async fn warm_up(&self) -> Result<(), Box<dyn Error>> {
+        let mut failures = Vec::new();
+        for ai in &self.providers {
+            if let Err(e) = ai.warm_up().await {
+                failures.push(format!("{}: {}", ai.name(), e));
+            }
+        }
+
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(failures.join(", ").into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::MockAI;
+
+    fn shared(ai: MockAI) -> Arc<Box<dyn AI>> {
+        Arc::new(Box::new(ai))
+    }
+
+    #[tokio::test]
+    async fn test_fallback_uses_next_provider_after_error() {
+        let chain = FallbackAI::new(vec![
+            shared(MockAI::canned("first").with_error(429, "rate limited")),
+            shared(MockAI::canned("second")),
+            shared(MockAI::canned("third")),
+        ]);
+
+        assert_eq!(chain.len(), 3);
+        assert_eq!(chain.generate("ls").await.unwrap(), "second");
+    }
+
+    #[tokio::test]
+    async fn test_fallback_responder_is_the_provider_that_answered() {
+        let chain = FallbackAI::new(vec![
+            shared(MockAI::echo().with_error(429, "rate limited")),
+            shared(MockAI::canned("second")),
+        ]);
+
+        assert_eq!(chain.responder(), "fallback(mock:echo -> mock:canned)");
+        chain.generate("ls").await.unwrap();
+        assert_eq!(chain.responder(), "mock:canned");
+    }
+
+    #[tokio::test]
+    async fn test_fallback_warms_every_provider() {
+        let chain = FallbackAI::new(vec![
+            shared(MockAI::echo().with_error(503, "down")),
+            shared(MockAI::canned("ls").with_error(401, "bad key")),
+        ]);
+
+        let err = chain.warm_up().await.unwrap_err().to_string();
+        assert!(err.starts_with("mock:echo: mock API error 503"), "{}", err);
+        assert!(err.contains("mock:canned: mock API error 401"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_fallback_returns_last_error() {
+        let chain = FallbackAI::new(vec![
+            shared(MockAI::canned("first").with_error(500, "down")),
+            shared(MockAI::canned("second").with_error(503, "overloaded")),
+        ]);
+
+        let err = chain.generate("ls").await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ClipsterError>(),
+            Some(ClipsterError::ApiError { status: 503, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_fallback_without_providers() {
+        let chain = FallbackAI::new(Vec::new());
+
+        assert!(chain.is_empty());
+        assert_eq!(chain.generate("ls").await.unwrap_err().to_string(), "The fallback chain has no providers");
+    }
+
+    #[test]
+    fn test_fallback_name_lists_chain() {
+        let chain = FallbackAI::new(vec![shared(MockAI::echo()), shared(MockAI::canned("ls"))]);
+
+        assert_eq!(chain.name(), "fallback(mock:echo -> mock:canned)");
+    }
+}
@@ -1 +1,5 @@
-pub mod whisper;
\ No newline at end of file
+pub mod command;
+pub mod dictation;
+#[cfg(feature = "local-llm")]
+pub mod llama;
+pub mod whisper;
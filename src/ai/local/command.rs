@@ -0,0 +1,166 @@
+use async_trait::async_trait;
+use std::error::Error;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use crate::ai::remote::traits::{Message, AI, USER};
+
+/// Runs an external program as a provider: the prompt goes to its stdin
+/// and whatever it prints to stdout is the response
+#[derive(Debug, Clone)]
+pub struct ExternalCommand {
+    program: String,
+    args: Vec<String>,
+}
+
+impl ExternalCommand {
+    pub fn new(program: &str, args: &[String]) -> Self {
+        Self {
+            program: program.to_string(),
+            args: args.to_vec(),
+        }
+    }
+
+    /// Runs the command to completion, a non-zero exit becomes an error carrying its stderr
+    pub fn run(&self, input: &str) -> Result<String, String> {
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Failed to start `{}`: {}", self.program, e))?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(input.as_bytes())
+                .map_err(|e| format!("Failed to write to `{}`: {}", self.program, e))?;
+        }
+
+        let output = child
+            .wait_with_output()
+            .map_err(|e| format!("Failed to run `{}`: {}", self.program, e))?;
+
+        if !output.status.success() {
+            return Err(format!(
+                "`{}` exited with {}: {}",
+                self.program,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    /// Writes `input` to the command's stdin and waits for it to exit, ignoring its output.
+    /// Unlike [`run`](Self::run) this returns even when the command leaves a child behind that
+    /// holds on to stdout, the way `wl-copy` and `xclip` stay around to serve the selection.
+    pub fn feed(&self, input: &str) -> Result<(), String> {
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("Failed to start `{}`: {}", self.program, e))?;
+
+        // Dropped at the end of the block, closing stdin so the command sees the end of its input
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(input.as_bytes())
+                .map_err(|e| format!("Failed to write to `{}`: {}", self.program, e))?;
+        }
+
+        let status = child
+            .wait()
+            .map_err(|e| format!("Failed to run `{}`: {}", self.program, e))?;
+
+        if !status.success() {
+            return Err(format!("`{}` exited with {}", self.program, status));
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AI for ExternalCommand {
+    fn name(&self) -> String {
+        format!("command:{}", self.program)
+    }
+
+    /// Only the last user message is piped in, the system prompt is meant for language models
+    async fn chat(&self, messages: Vec<Message>) -> Result<String, Box<dyn Error>> {
+        let input = messages
+            .into_iter()
+            .rev()
+            .find(|message| message.role == USER)
+            .map(|message| message.content)
+            .unwrap_or_default();
+
+        let command = self.clone();
+        let output = tokio::task::spawn_blocking(move || command.run(&input)).await??;
+
+        Ok(output)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_pipes_stdin_to_stdout() {
+        let command = ExternalCommand::new("tr", &["a-z".to_string(), "A-Z".to_string()]);
+        assert_eq!(command.run("list files").unwrap(), "LIST FILES");
+    }
+
+    #[test]
+    fn test_run_reports_stderr_on_failure() {
+        let command = ExternalCommand::new("sh", &["-c".to_string(), "echo broken >&2; exit 3".to_string()]);
+        let err = command.run("").unwrap_err();
+
+        assert!(err.contains("`sh` exited with"));
+        assert!(err.contains("3"));
+        assert!(err.ends_with("broken"));
+    }
+
+    #[test]
+    fn test_run_reports_missing_program() {
+        let command = ExternalCommand::new("clipster-no-such-program", &[]);
+        assert!(command.run("").unwrap_err().starts_with("Failed to start `clipster-no-such-program`"));
+    }
+
+    #[test]
+    fn test_feed_returns_without_waiting_for_forked_children() {
+        let path = std::env::temp_dir().join(format!("clipster-feed-{}.txt", std::process::id()));
+        let script = format!("cat > {}; sleep 5 &", path.display());
+        let command = ExternalCommand::new("sh", &["-c".to_string(), script]);
+
+        let started = std::time::Instant::now();
+        command.feed("copied").unwrap();
+        let written = std::fs::read_to_string(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(started.elapsed() < std::time::Duration::from_secs(4));
+        assert_eq!(written.unwrap(), "copied");
+    }
+
+    #[test]
+    fn test_feed_reports_failure() {
+        let command = ExternalCommand::new("sh", &["-c".to_string(), "exit 2".to_string()]);
+        assert!(command.feed("x").unwrap_err().starts_with("`sh` exited with"));
+    }
+
+    #[tokio::test]
+    async fn test_chat_sends_only_user_message() {
+        let command = ExternalCommand::new("cat", &[]);
+        let messages = vec![
+            Message { role: "system".to_string(), content: "You are a code assistant".to_string() },
+            Message { role: "user".to_string(), content: "echo hi".to_string() },
+        ];
+
+        assert_eq!(command.chat(messages).await.unwrap(), "echo hi");
+    }
+}
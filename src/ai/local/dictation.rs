@@ -0,0 +1,59 @@
+use async_trait::async_trait;
+use std::error::Error;
+
+use crate::ai::remote::traits::{Message, AI, USER};
+use crate::postprocess::restore_punctuation;
+
+/// Transcription-only "provider" that hands back what was said, optionally tidied up
+#[derive(Debug, Clone, Default)]
+pub struct Dictation {
+    restore_punctuation: bool,
+}
+
+impl Dictation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Capitalize sentences and add missing terminal punctuation, see [`restore_punctuation`]
+    pub fn with_restore_punctuation(mut self, restore_punctuation: bool) -> Self {
+        self.restore_punctuation = restore_punctuation;
+        self
+    }
+}
+
+#[async_trait]
+impl AI for Dictation {
+    fn name(&self) -> String {
+        "dictation".to_string()
+    }
+
+    async fn chat(&self, messages: Vec<Message>) -> Result<String, Box<dyn Error>> {
+        let transcript = messages
+            .into_iter()
+            .rev()
+            .find(|message| message.role == USER)
+            .map(|message| message.content)
+            .unwrap_or_default();
+
+        if self.restore_punctuation {
+            Ok(restore_punctuation(&transcript))
+        } else {
+            Ok(transcript)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_dictation_returns_transcript() {
+        let dictation = Dictation::new();
+        assert_eq!(dictation.generate("hello world").await.unwrap(), "hello world");
+
+        let dictation = dictation.with_restore_punctuation(true);
+        assert_eq!(dictation.generate("hello world").await.unwrap(), "Hello world.");
+    }
+}
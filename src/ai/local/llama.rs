@@ -0,0 +1,203 @@
+use async_trait::async_trait;
+use llama_cpp_2::context::params::LlamaContextParams;
+use llama_cpp_2::llama_backend::LlamaBackend;
+use llama_cpp_2::llama_batch::LlamaBatch;
+use llama_cpp_2::model::params::LlamaModelParams;
+use llama_cpp_2::model::{AddBos, LlamaChatMessage, LlamaModel, Special};
+use llama_cpp_2::sampling::LlamaSampler;
+use std::error::Error;
+use std::num::NonZeroU32;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::ai::remote::traits::{Message, AI};
+
+/// A GGUF model run in-process through llama.cpp, no server or network involved
+#[derive(Clone)]
+pub struct LocalLlama {
+    inner: Arc<LlamaInner>,
+    model_name: String,
+    max_tokens: u32,
+    context_size: u32,
+    temperature: f32,
+}
+
+struct LlamaInner {
+    backend: LlamaBackend,
+    model: LlamaModel,
+}
+
+impl std::fmt::Debug for LocalLlama {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LocalLlama")
+            .field("model_name", &self.model_name)
+            .field("max_tokens", &self.max_tokens)
+            .field("context_size", &self.context_size)
+            .field("temperature", &self.temperature)
+            .finish()
+    }
+}
+
+impl LocalLlama {
+    pub fn new(model_path: &str) -> Result<Self, Box<dyn Error>> {
+        println!("Loading local model from: {}", model_path);
+
+        let backend = LlamaBackend::init().map_err(|e| format!("Failed to initialize llama.cpp: {}", e))?;
+        let model = LlamaModel::load_from_file(&backend, model_path, &LlamaModelParams::default())
+            .map_err(|e| format!("Failed to load model: {}", e))?;
+
+        println!("Local model loaded");
+
+        Ok(Self {
+            inner: Arc::new(LlamaInner { backend, model }),
+            model_name: model_name(model_path),
+            max_tokens: 500,
+            context_size: 2048,
+            temperature: 0.7,
+        })
+    }
+
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = max_tokens;
+        self
+    }
+
+    /// Tokens of prompt plus response the model can attend to
+    pub fn with_context_size(mut self, context_size: u32) -> Self {
+        self.context_size = context_size.max(256);
+        self
+    }
+
+    pub fn with_temperature(mut self, temperature: f32) -> Self {
+        self.temperature = temperature.clamp(0.0, 2.0);
+        self
+    }
+
+    /// Formats the messages with the model's own chat template
+    fn build_prompt(&self, messages: &[Message]) -> Result<String, Box<dyn Error>> {
+        let model = &self.inner.model;
+        let template = model.chat_template(None)?;
+        let chat = messages
+            .iter()
+            .map(|message| LlamaChatMessage::new(message.role.clone(), message.content.clone()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(model.apply_chat_template(&template, &chat, true)?)
+    }
+
+    /// Blocking generation, run on a worker thread by `chat`
+    fn generate_blocking(&self, prompt: &str) -> Result<String, Box<dyn Error>> {
+        let LlamaInner { backend, model } = self.inner.as_ref();
+
+        let context_params = LlamaContextParams::default().with_n_ctx(NonZeroU32::new(self.context_size));
+        let mut context = model
+            .new_context(backend, context_params)
+            .map_err(|e| format!("Failed to create context: {}", e))?;
+
+        let tokens = model.str_to_token(prompt, AddBos::Always)?;
+        let limit = token_limit(tokens.len(), self.max_tokens, self.context_size)?;
+
+        let mut batch = LlamaBatch::new(tokens.len().max(1), 1);
+        let last = tokens.len() as i32 - 1;
+        for (position, token) in (0_i32..).zip(tokens) {
+            batch.add(token, position, &[0], position == last)?;
+        }
+        context.decode(&mut batch)?;
+
+        let mut sampler = if self.temperature == 0.0 {
+            LlamaSampler::greedy()
+        } else {
+            LlamaSampler::chain_simple([LlamaSampler::temp(self.temperature), LlamaSampler::dist(rand_seed())])
+        };
+
+        // Tokens can split multi-byte characters, so decode once at the end
+        let mut output = Vec::new();
+        let mut position = batch.n_tokens();
+
+        while position < limit {
+            let token = sampler.sample(&context, batch.n_tokens() - 1);
+            sampler.accept(token);
+
+            if model.is_eog_token(token) {
+                break;
+            }
+
+            output.extend(model.token_to_bytes(token, Special::Tokenize)?);
+
+            batch.clear();
+            batch.add(token, position, &[0], true)?;
+            position += 1;
+            context.decode(&mut batch)?;
+        }
+
+        Ok(String::from_utf8_lossy(&output).trim().to_string())
+    }
+}
+
+/// File name without the extension, e.g. `qwen2.5-coder-1.5b-q4_k_m`
+fn model_name(model_path: &str) -> String {
+    Path::new(model_path)
+        .file_stem()
+        .map_or_else(|| model_path.to_string(), |stem| stem.to_string_lossy().into_owned())
+}
+
+/// Position generation stops at, the prompt plus `max_tokens`, when that fits the context
+fn token_limit(prompt_tokens: usize, max_tokens: u32, context_size: u32) -> Result<i32, String> {
+    let limit = prompt_tokens as i32 + max_tokens as i32;
+    if limit > context_size as i32 {
+        return Err(format!(
+            "Prompt of {} tokens doesn't fit a {} token context with {} tokens for the response",
+            prompt_tokens, context_size, max_tokens
+        ));
+    }
+    Ok(limit)
+}
+
+fn rand_seed() -> u32 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.subsec_nanos())
+}
+
+#[async_trait]
+impl AI for LocalLlama {
+    fn name(&self) -> String {
+        format!("llama:{}", self.model_name)
+    }
+
+    async fn chat(&self, messages: Vec<Message>) -> Result<String, Box<dyn Error>> {
+        let prompt = self.build_prompt(&messages)?;
+        let llama = self.clone();
+
+        let output = tokio::task::spawn_blocking(move || llama.generate_blocking(&prompt).map_err(|e| e.to_string()))
+            .await??;
+
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_model_name_is_file_stem() {
+        assert_eq!(model_name("models/qwen2.5-coder-1.5b-q4_k_m.gguf"), "qwen2.5-coder-1.5b-q4_k_m");
+        assert_eq!(model_name("/opt/llama"), "llama");
+        assert_eq!(model_name(""), "");
+    }
+
+    #[test]
+    fn test_token_limit_within_context() {
+        assert_eq!(token_limit(100, 500, 2048), Ok(600));
+        assert_eq!(token_limit(1548, 500, 2048), Ok(2048));
+    }
+
+    #[test]
+    fn test_token_limit_rejects_prompt_too_long_for_context() {
+        assert_eq!(
+            token_limit(1600, 500, 2048),
+            Err("Prompt of 1600 tokens doesn't fit a 2048 token context with 500 tokens for the response".to_string())
+        );
+    }
+}
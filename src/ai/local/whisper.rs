@@ -1,58 +1,837 @@
+use std::cell::RefCell;
 use std::error::Error;
+use std::ffi::c_void;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use whisper_rs::{WhisperContext, WhisperContextParameters, FullParams, SamplingStrategy};
 
+use crate::error::ClipsterError;
+
+const SAMPLE_RATE: usize = 16000;
+
+/// Longest run of words compared when de-duplicating overlapping windows
+const MAX_OVERLAP_WORDS: usize = 32;
+
+/// Called with the text of each segment as soon as Whisper finishes it
+pub type SegmentCallback = Box<dyn FnMut(&str)>;
+
+/// A stretch of the transcript and where it was spoken, in milliseconds from the start of the samples
+#[derive(Debug, Clone, PartialEq)]
+pub struct TranscriptSegment {
+    pub start_ms: i64,
+    pub end_ms: i64,
+    pub text: String,
+}
+
+/// Decoding settings applied to every transcription
+#[derive(Debug, Clone, PartialEq)]
+pub struct WhisperSettings {
+    pub language: Option<String>,
+    /// Threads used for decoding, one per logical CPU by default, see [`default_threads`]
+    pub n_threads: i32,
+    /// Output English whatever the spoken language. Needs a multilingual model, and does nothing
+    /// useful when the language is set to `en`.
+    pub translate: bool,
+    pub best_of: i32,
+    /// Force a single segment: quicker for short commands and avoids spurious extra segments,
+    /// but long recordings lose accuracy since they're decoded as one piece of text
+    pub single_segment: bool,
+    /// Skip blank output at the start of decoding, e.g. `[BLANK_AUDIO]`
+    pub suppress_blank: bool,
+    /// Skip non-speech tokens such as music notes and sound descriptions
+    pub suppress_non_speech_tokens: bool,
+    /// Text the decoder treats as already said, which steers it towards the spelling of
+    /// names and jargon it contains
+    pub initial_prompt: Option<String>,
+}
+
+impl Default for WhisperSettings {
+    fn default() -> Self {
+        Self {
+            language: Some("en".to_string()),
+            n_threads: default_threads(),
+            translate: false,
+            best_of: 1,
+            single_segment: false,
+            suppress_blank: true,
+            suppress_non_speech_tokens: true,
+            initial_prompt: None,
+        }
+    }
+}
+
+impl WhisperSettings {
+    pub fn sampling_strategy(&self) -> SamplingStrategy {
+        SamplingStrategy::Greedy { best_of: self.best_of }
+    }
+
+    pub fn build_params(&self) -> FullParams<'_, '_> {
+        let mut params = FullParams::new(self.sampling_strategy());
+
+        params.set_print_progress(false);
+        params.set_print_special(false);
+        params.set_print_realtime(false);
+        params.set_print_timestamps(false);
+        self.apply(&mut params);
+
+        params
+    }
+
+    /// Hands the settings to the decoder's setters
+    fn apply<'a>(&'a self, params: &mut impl DecoderParams<'a>) {
+        params.set_language(self.language.as_deref());
+        params.set_n_threads(self.n_threads);
+        params.set_translate(self.translate);
+        params.set_single_segment(self.single_segment);
+        params.set_suppress_blank(self.suppress_blank);
+        params.set_suppress_nst(self.suppress_non_speech_tokens);
+        if let Some(prompt) = &self.initial_prompt {
+            params.set_initial_prompt(prompt);
+        }
+    }
+}
+
+/// The [`FullParams`] setters that depend on [`WhisperSettings`]. `FullParams` can't be read
+/// back, so tests check what [`WhisperSettings::build_params`] sets through a recorder instead.
+trait DecoderParams<'a> {
+    fn set_language(&mut self, language: Option<&'a str>);
+    fn set_n_threads(&mut self, n_threads: i32);
+    fn set_translate(&mut self, translate: bool);
+    fn set_single_segment(&mut self, single_segment: bool);
+    fn set_suppress_blank(&mut self, suppress_blank: bool);
+    fn set_suppress_nst(&mut self, suppress_nst: bool);
+    fn set_initial_prompt(&mut self, initial_prompt: &str);
+}
+
+impl<'a> DecoderParams<'a> for FullParams<'a, '_> {
+    fn set_language(&mut self, language: Option<&'a str>) {
+        FullParams::set_language(self, language);
+    }
+
+    fn set_n_threads(&mut self, n_threads: i32) {
+        FullParams::set_n_threads(self, n_threads);
+    }
+
+    fn set_translate(&mut self, translate: bool) {
+        FullParams::set_translate(self, translate);
+    }
+
+    fn set_single_segment(&mut self, single_segment: bool) {
+        FullParams::set_single_segment(self, single_segment);
+    }
+
+    fn set_suppress_blank(&mut self, suppress_blank: bool) {
+        FullParams::set_suppress_blank(self, suppress_blank);
+    }
+
+    fn set_suppress_nst(&mut self, suppress_nst: bool) {
+        FullParams::set_suppress_nst(self, suppress_nst);
+    }
+
+    fn set_initial_prompt(&mut self, initial_prompt: &str) {
+        FullParams::set_initial_prompt(self, initial_prompt);
+    }
+}
+
 pub struct WhisperModel {
     ctx: WhisperContext,
+    settings: WhisperSettings,
+    chunking: Option<(f32, f32)>,
+    /// Probabilities of the text tokens in the latest transcript
+    token_probabilities: Vec<f32>,
+    /// What Whisper heard in the latest transcript when the language was left to auto-detection
+    detected_language: Option<String>,
 }
 
 impl WhisperModel {
-    pub fn new(model_path: &str) -> Result<Self, Box<dyn Error>> {
+    pub fn new(model_path: &str) -> Result<Self, ClipsterError> {
         println!("Loading Whisper model from: {}", model_path);
 
         let ctx = WhisperContext::new_with_params(
             model_path,
             WhisperContextParameters::default()
-        ).map_err(|e| format!("Failed to load model: {}", e))?;
+        ).map_err(|e| ClipsterError::Transcription(format!("Failed to load model: {}", e)))?;
 
         println!("Whisper model loaded");
-        Ok(Self { ctx })
+        Ok(Self {
+            ctx,
+            settings: WhisperSettings::default(),
+            chunking: None,
+            token_probabilities: Vec::new(),
+            detected_language: None,
+        })
+    }
+
+    /// Transcribe long recordings in overlapping windows instead of a single pass.
+    /// Each window's text is printed as it completes and stitched into the final transcript.
+    pub fn with_chunking(mut self, window_secs: f32, overlap_secs: f32) -> Self {
+        let window_secs = window_secs.max(1.0);
+        self.chunking = Some((window_secs, overlap_secs.clamp(0.0, window_secs / 2.0)));
+        self
+    }
+
+    /// Transcription language like `de`, `None` lets Whisper auto-detect. See [`language_setting`].
+    pub fn with_language(mut self, language: Option<&str>) -> Self {
+        self.set_language(language);
+        self
+    }
+
+    /// Decoding threads, values below 1 use a single thread
+    pub fn with_threads(mut self, n_threads: i32) -> Self {
+        self.settings.n_threads = n_threads.max(1);
+        self
+    }
+
+    /// See [`WhisperSettings::initial_prompt`], a blank prompt clears it
+    pub fn with_initial_prompt(mut self, prompt: &str) -> Self {
+        self.settings.initial_prompt = initial_prompt(prompt);
+        self
+    }
+
+    /// See [`WhisperSettings::translate`]
+    pub fn with_translate(mut self, translate: bool) -> Self {
+        self.settings.translate = translate;
+        self
+    }
+
+    /// See [`WhisperSettings::single_segment`]
+    pub fn with_single_segment(mut self, single_segment: bool) -> Self {
+        self.settings.single_segment = single_segment;
+        self
+    }
+
+    /// See [`WhisperSettings::suppress_blank`] and [`WhisperSettings::suppress_non_speech_tokens`]
+    pub fn with_token_suppression(mut self, suppress_blank: bool, suppress_non_speech_tokens: bool) -> Self {
+        self.settings.suppress_blank = suppress_blank;
+        self.settings.suppress_non_speech_tokens = suppress_non_speech_tokens;
+        self
+    }
+
+    pub fn settings(&self) -> &WhisperSettings {
+        &self.settings
+    }
+
+    pub fn build_params(&self) -> FullParams<'_, '_> {
+        self.settings.build_params()
+    }
+
+    /// English-only models (`*.en.bin`) ignore the language setting
+    pub fn is_multilingual(&self) -> bool {
+        self.ctx.is_multilingual()
+    }
+
+    /// Changes the transcription language, `None` lets Whisper auto-detect
+    pub fn set_language(&mut self, language: Option<&str>) {
+        self.settings.language = language.map(str::to_string);
     }
 
-    pub fn transcribe(&mut self, samples: &[f32]) -> Result<String, Box<dyn Error>> {
+    /// Mean probability of the text tokens in the latest transcript, from 0 to 1.
+    /// `None` before the first transcription or when nothing was said.
+    pub fn last_confidence(&self) -> Option<f32> {
+        mean_confidence(&self.token_probabilities)
+    }
+
+    /// Language code Whisper detected in the latest transcript, `None` unless it was auto-detecting
+    pub fn detected_language(&self) -> Option<&str> {
+        self.detected_language.as_deref()
+    }
+
+    pub fn transcribe(&mut self, samples: &[f32]) -> Result<String, ClipsterError> {
+        self.transcribe_with_segments(samples, None, None)
+    }
+
+    /// Like [`transcribe`](Self::transcribe), also reporting each segment as it completes
+    /// so long recordings can show progress before the full transcript is ready.
+    /// Setting `cancel` from another thread stops decoding early with a "Transcription cancelled" error.
+    pub fn transcribe_with_segments(
+        &mut self,
+        samples: &[f32],
+        on_segment: Option<SegmentCallback>,
+        cancel: Option<Arc<AtomicBool>>,
+    ) -> Result<String, ClipsterError> {
+        self.token_probabilities.clear();
+        self.detected_language = None;
+
         if samples.is_empty() {
             return Ok(String::new());
         }
 
-        let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+        let on_segment = on_segment.map(|callback| Rc::new(RefCell::new(callback)));
 
-        params.set_print_progress(false);
-        params.set_print_special(false);
-        params.set_print_realtime(false);
-        params.set_print_timestamps(false);
-        params.set_language(Some("en"));
-        params.set_n_threads(4);
-        params.set_translate(false);
+        let result = match self.chunking {
+            Some((window_secs, overlap_secs)) => {
+                let window = (window_secs * SAMPLE_RATE as f32) as usize;
+                let overlap = (overlap_secs * SAMPLE_RATE as f32) as usize;
+                let ranges = chunk_ranges(samples.len(), window, overlap);
+
+                let mut transcript = String::new();
+                for (i, range) in ranges.iter().enumerate() {
+                    let segments = self.transcribe_window(&samples[range.clone()], on_segment.clone(), cancel.as_ref())?;
+                    let text = join_segments(&segments);
+                    if ranges.len() > 1 && on_segment.is_none() {
+                        println!("  [{}/{}] {}", i + 1, ranges.len(), text);
+                    }
+                    transcript = merge_overlap(&transcript, &text);
+                }
+                transcript
+            }
+            None => join_segments(&self.transcribe_window(samples, on_segment, cancel.as_ref())?),
+        };
+
+        if result.is_empty() {
+            println!("No transcription generated (silence detected)");
+        }
+
+        Ok(result)
+    }
+
+    /// Like [`transcribe`](Self::transcribe), keeping each segment's timing instead of joining the text.
+    /// With chunking, segments are offset to their window and ones repeating the overlap are dropped.
+    pub fn transcribe_segments(&mut self, samples: &[f32]) -> Result<Vec<TranscriptSegment>, ClipsterError> {
+        self.token_probabilities.clear();
+        self.detected_language = None;
+
+        let mut segments = Vec::new();
+        if samples.is_empty() {
+            return Ok(segments);
+        }
+
+        let ranges = match self.chunking {
+            Some((window_secs, overlap_secs)) => chunk_ranges(
+                samples.len(),
+                (window_secs * SAMPLE_RATE as f32) as usize,
+                (overlap_secs * SAMPLE_RATE as f32) as usize,
+            ),
+            None => chunk_ranges(samples.len(), samples.len(), 0),
+        };
+
+        for range in ranges {
+            let offset_ms = (range.start * 1000 / SAMPLE_RATE) as i64;
+            let window = self.transcribe_window(&samples[range], None, None)?;
+            append_segments(&mut segments, window, offset_ms);
+        }
+
+        Ok(segments)
+    }
+
+    fn transcribe_window(
+        &mut self,
+        samples: &[f32],
+        on_segment: Option<Rc<RefCell<SegmentCallback>>>,
+        cancel: Option<&Arc<AtomicBool>>,
+    ) -> Result<Vec<TranscriptSegment>, ClipsterError> {
+        if is_cancelled(cancel) {
+            return Err(ClipsterError::Transcription("Transcription cancelled".to_string()));
+        }
+
+        let mut params = self.build_params();
+
+        // whisper-rs' safe abort callback casts its user data to the wrong type, so the flag
+        // goes in as a raw pointer instead.
+        // SAFETY: the callback only reads the flag, and `cancel` outlives `state.full` below.
+        if let Some(cancel) = cancel {
+            unsafe {
+                params.set_abort_callback(Some(abort_requested));
+                params.set_abort_callback_user_data(Arc::as_ptr(cancel) as *mut c_void);
+            }
+        }
+
+        if let Some(on_segment) = on_segment {
+            params.set_segment_callback_safe_lossy(move |segment: whisper_rs::SegmentCallbackData| {
+                (on_segment.borrow_mut())(segment.text.trim());
+            });
+        }
 
         let mut state = self.ctx.create_state()
-            .map_err(|e| format!("Failed to create state: {}", e))?;
+            .map_err(|e| ClipsterError::Transcription(format!("Failed to create state: {}", e)))?;
 
         // transcription
-        state.full(params, samples)
-            .map_err(|e| format!("Transcription failed: {}", e))?;
+        let result = state.full(params, samples);
+        if is_cancelled(cancel) {
+            return Err(ClipsterError::Transcription("Transcription cancelled".to_string()));
+        }
+        result.map_err(|e| ClipsterError::Transcription(format!("Transcription failed: {}", e)))?;
+
+        if self.settings.language.is_none() {
+            self.detected_language = whisper_rs::get_lang_str(state.full_lang_id_from_state()).map(str::to_string);
+        }
 
         // get all segments
-        let mut transcription = String::new();
+        let token_eot = self.ctx.token_eot();
+        let mut segments = Vec::new();
         for segment in state.as_iter() {
-            transcription.push_str(&segment.to_str_lossy()?);
-            transcription.push(' ');
+            let text = segment
+                .to_str_lossy()
+                .map_err(|e| ClipsterError::Transcription(format!("Failed to read segment: {}", e)))?;
+            // whisper.cpp counts in centiseconds
+            segments.push(TranscriptSegment {
+                start_ms: segment.start_timestamp() * 10,
+                end_ms: segment.end_timestamp() * 10,
+                text: text.trim().to_string(),
+            });
+
+            // Timestamps and other special tokens sort after end-of-text
+            let text_tokens = (0..segment.n_tokens())
+                .filter_map(|i| segment.get_token(i))
+                .filter(|token| token.token_id() < token_eot)
+                .map(|token| token.token_probability());
+            self.token_probabilities.extend(text_tokens);
         }
 
-        let result = transcription.trim().to_string();
+        Ok(segments)
+    }
+}
 
-        if result.is_empty() {
-            println!("No transcription generated (silence detected)");
+/// `prompt` as whisper.cpp can take it: trimmed, without NUL bytes (which it can't pass on) and
+/// `None` when nothing is left
+fn initial_prompt(prompt: &str) -> Option<String> {
+    let prompt = prompt.replace('\0', "");
+    let prompt = prompt.trim();
+    (!prompt.is_empty()).then(|| prompt.to_string())
+}
+
+/// Logical CPUs available to this process (hyperthreads count, unlike physical cores),
+/// or 4 where that can't be determined
+pub fn default_threads() -> i32 {
+    std::thread::available_parallelism().map_or(4, |cores| cores.get().min(i32::MAX as usize) as i32)
+}
+
+/// A configured language as [`WhisperModel::with_language`] takes it, `auto` (or nothing) for auto-detection
+pub fn language_setting(language: &str) -> Option<&str> {
+    let language = language.trim();
+    (!language.is_empty() && !language.eq_ignore_ascii_case("auto")).then_some(language)
+}
+
+/// The text of `segments` as one transcript
+fn join_segments(segments: &[TranscriptSegment]) -> String {
+    segments
+        .iter()
+        .map(|segment| segment.text.as_str())
+        .filter(|text| !text.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Adds a window's segments, shifted by where the window starts, skipping the ones that begin
+/// before the last kept segment ends (the audio the windows share)
+fn append_segments(segments: &mut Vec<TranscriptSegment>, window: Vec<TranscriptSegment>, offset_ms: i64) {
+    let kept_until = segments.last().map_or(i64::MIN, |segment| segment.end_ms);
+
+    segments.extend(
+        window
+            .into_iter()
+            .map(|segment| TranscriptSegment {
+                start_ms: segment.start_ms + offset_ms,
+                end_ms: segment.end_ms + offset_ms,
+                text: segment.text,
+            })
+            .filter(|segment| segment.start_ms >= kept_until && !segment.text.is_empty()),
+    );
+}
+
+fn mean_confidence(probabilities: &[f32]) -> Option<f32> {
+    if probabilities.is_empty() {
+        return None;
+    }
+
+    Some(probabilities.iter().sum::<f32>() / probabilities.len() as f32)
+}
+
+fn is_cancelled(cancel: Option<&Arc<AtomicBool>>) -> bool {
+    cancel.is_some_and(|cancel| cancel.load(Ordering::Relaxed))
+}
+
+/// Polled by whisper.cpp between decoding steps, `data` points at the cancel flag
+unsafe extern "C" fn abort_requested(data: *mut c_void) -> bool {
+    (*(data as *const AtomicBool)).load(Ordering::Relaxed)
+}
+
+/// Finds a model file from a configured path or a bare model name like `tiny.en`.
+/// Paths expand `~` and are tried relative to the working directory, then `config_dir`.
+/// Names are looked up as `ggml-<name>.bin` under `./models`, `<config_dir>/models`,
+/// `~/.cache/whisper` and the whisper.cpp HuggingFace cache. The error lists every path tried.
+pub fn resolve_model_path(
+    model: &str,
+    config_dir: Option<&Path>,
+    home: Option<&Path>,
+) -> Result<PathBuf, Box<dyn Error>> {
+    let candidates = model_candidates(model, config_dir, home);
+
+    if let Some(found) = candidates.iter().find(|path| path.is_file()) {
+        return Ok(found.clone());
+    }
+
+    let searched: Vec<String> = candidates
+        .iter()
+        .map(|path| format!("  {}", path.display()))
+        .collect();
+    Err(format!("Whisper model \"{}\" not found, searched:\n{}", model, searched.join("\n")).into())
+}
+
+fn model_candidates(model: &str, config_dir: Option<&Path>, home: Option<&Path>) -> Vec<PathBuf> {
+    let is_path = model.contains('/') || model.contains('\\') || model.ends_with(".bin");
+
+    if is_path {
+        let path = match (model.strip_prefix("~/"), home) {
+            (Some(rest), Some(home)) => home.join(rest),
+            _ => PathBuf::from(model),
+        };
+
+        if path.is_absolute() {
+            return vec![path];
         }
 
-        Ok(result)
+        let mut candidates = vec![path.clone()];
+        candidates.extend(config_dir.map(|dir| dir.join(&path)));
+        return candidates;
+    }
+
+    let file_name = format!("ggml-{}.bin", model);
+    let mut candidates = vec![Path::new("models").join(&file_name)];
+    candidates.extend(config_dir.map(|dir| dir.join("models").join(&file_name)));
+
+    if let Some(home) = home {
+        let cache = home.join(".cache");
+        candidates.push(cache.join("whisper").join(&file_name));
+
+        let snapshots = cache.join("huggingface/hub/models--ggerganov--whisper.cpp/snapshots");
+        if let Ok(entries) = std::fs::read_dir(&snapshots) {
+            let mut revisions: Vec<PathBuf> = entries.flatten().map(|entry| entry.path()).collect();
+            revisions.sort();
+            candidates.extend(revisions.into_iter().map(|revision| revision.join(&file_name)));
+        } else {
+            candidates.push(snapshots.join("*").join(&file_name));
+        }
+    }
+
+    candidates
+}
+
+/// Split `len` samples into windows of `window` samples, each starting `window - overlap` after the previous
+fn chunk_ranges(len: usize, window: usize, overlap: usize) -> Vec<Range<usize>> {
+    let step = window.saturating_sub(overlap).max(1);
+    let mut ranges = Vec::new();
+    let mut start = 0;
+
+    loop {
+        let end = (start + window).min(len);
+        ranges.push(start..end);
+        if end == len {
+            break;
+        }
+        start += step;
+    }
+
+    ranges
+}
+
+/// Append `next` to `previous`, dropping the longest run of leading words in `next`
+/// that repeats the trailing words of `previous` (the audio both windows share)
+fn merge_overlap(previous: &str, next: &str) -> String {
+    let prev_words: Vec<&str> = previous.split_whitespace().collect();
+    let next_words: Vec<&str> = next.split_whitespace().collect();
+
+    let max_overlap = prev_words.len().min(next_words.len()).min(MAX_OVERLAP_WORDS);
+    let overlap = (1..=max_overlap)
+        .rev()
+        .find(|&n| {
+            prev_words[prev_words.len() - n..]
+                .iter()
+                .zip(&next_words[..n])
+                .all(|(a, b)| normalize_word(a) == normalize_word(b))
+        })
+        .unwrap_or(0);
+
+    prev_words
+        .iter()
+        .chain(&next_words[overlap..])
+        .copied()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn normalize_word(word: &str) -> String {
+    word.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("clipster-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_mean_confidence() {
+        assert_eq!(mean_confidence(&[]), None);
+        assert_eq!(mean_confidence(&[0.5, 1.0, 0.75]), Some(0.75));
+    }
+
+    #[test]
+    fn test_resolve_model_path_finds_named_model_in_cache() {
+        let home = temp_dir("home-cache");
+        let cached = home.join(".cache/whisper/ggml-test-cache.bin");
+        std::fs::create_dir_all(cached.parent().unwrap()).unwrap();
+        std::fs::write(&cached, b"").unwrap();
+
+        assert_eq!(resolve_model_path("test-cache", None, Some(&home)).unwrap(), cached);
+
+        std::fs::remove_dir_all(&home).unwrap();
+    }
+
+    #[test]
+    fn test_resolve_model_path_finds_named_model_in_hf_cache() {
+        let home = temp_dir("home-hf");
+        let snapshot = home.join(".cache/huggingface/hub/models--ggerganov--whisper.cpp/snapshots/abc123");
+        std::fs::create_dir_all(&snapshot).unwrap();
+        std::fs::write(snapshot.join("ggml-test-hf.bin"), b"").unwrap();
+
+        assert_eq!(
+            resolve_model_path("test-hf", None, Some(&home)).unwrap(),
+            snapshot.join("ggml-test-hf.bin")
+        );
+
+        std::fs::remove_dir_all(&home).unwrap();
+    }
+
+    #[test]
+    fn test_resolve_model_path_relative_to_config_dir_and_home() {
+        let config_dir = temp_dir("config");
+        std::fs::create_dir_all(config_dir.join("models")).unwrap();
+        std::fs::write(config_dir.join("models/custom.bin"), b"").unwrap();
+
+        assert_eq!(
+            resolve_model_path("models/custom.bin", Some(&config_dir), None).unwrap(),
+            config_dir.join("models/custom.bin")
+        );
+        assert_eq!(
+            resolve_model_path("~/models/custom.bin", None, Some(&config_dir)).unwrap(),
+            config_dir.join("models/custom.bin")
+        );
+
+        std::fs::remove_dir_all(&config_dir).unwrap();
+    }
+
+    #[test]
+    fn test_resolve_model_path_reports_searched_paths() {
+        let home = Path::new("/nonexistent-home");
+        let err = resolve_model_path("test-missing", Some(Path::new("/nonexistent-config")), Some(home))
+            .unwrap_err()
+            .to_string();
+
+        assert!(err.contains("Whisper model \"test-missing\" not found"));
+        assert!(err.contains("  models/ggml-test-missing.bin"));
+        assert!(err.contains("/nonexistent-config/models/ggml-test-missing.bin"));
+        assert!(err.contains("/nonexistent-home/.cache/whisper/ggml-test-missing.bin"));
+        assert!(err.contains("models--ggerganov--whisper.cpp"));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_whisper_settings_defaults() {
+        let settings = WhisperSettings::default();
+
+        assert_eq!(settings.language.as_deref(), Some("en"));
+        assert_eq!(settings.n_threads, default_threads());
+        assert!(settings.n_threads >= 1);
+        assert!(!settings.translate);
+        assert!(!settings.single_segment);
+        // Both are passed to `FullParams` by `build_params`
+        assert!(settings.suppress_blank);
+        assert!(settings.suppress_non_speech_tokens);
+        assert!(matches!(settings.sampling_strategy(), SamplingStrategy::Greedy { best_of: 1 }));
+    }
+
+    /// What [`WhisperSettings::apply`] passed on, as `FullParams` would have received it
+    #[derive(Debug, Default, PartialEq)]
+    struct RecordedParams {
+        language: Option<String>,
+        n_threads: i32,
+        translate: bool,
+        single_segment: bool,
+        suppress_blank: bool,
+        suppress_nst: bool,
+        initial_prompt: Option<String>,
+    }
+
+    impl DecoderParams<'_> for RecordedParams {
+        fn set_language(&mut self, language: Option<&str>) {
+            self.language = language.map(str::to_string);
+        }
+
+        fn set_n_threads(&mut self, n_threads: i32) {
+            self.n_threads = n_threads;
+        }
+
+        fn set_translate(&mut self, translate: bool) {
+            self.translate = translate;
+        }
+
+        fn set_single_segment(&mut self, single_segment: bool) {
+            self.single_segment = single_segment;
+        }
+
+        fn set_suppress_blank(&mut self, suppress_blank: bool) {
+            self.suppress_blank = suppress_blank;
+        }
+
+        fn set_suppress_nst(&mut self, suppress_nst: bool) {
+            self.suppress_nst = suppress_nst;
+        }
+
+        fn set_initial_prompt(&mut self, initial_prompt: &str) {
+            self.initial_prompt = Some(initial_prompt.to_string());
+        }
+    }
+
+    #[test]
+    fn test_build_params_suppresses_blank_and_non_speech_by_default() {
+        let settings = WhisperSettings { n_threads: 4, ..Default::default() };
+        let mut params = RecordedParams::default();
+        settings.apply(&mut params);
+
+        assert_eq!(params, RecordedParams {
+            language: Some("en".to_string()),
+            n_threads: 4,
+            translate: false,
+            single_segment: false,
+            suppress_blank: true,
+            suppress_nst: true,
+            initial_prompt: None,
+        });
+    }
+
+    #[test]
+    fn test_build_params_passes_on_every_setting() {
+        let settings = WhisperSettings {
+            language: None,
+            n_threads: 2,
+            translate: true,
+            best_of: 1,
+            single_segment: true,
+            suppress_blank: false,
+            suppress_non_speech_tokens: false,
+            initial_prompt: Some("Vocabulary: tokio.".to_string()),
+        };
+        let mut params = RecordedParams::default();
+        settings.apply(&mut params);
+
+        assert_eq!(params, RecordedParams {
+            language: None,
+            n_threads: 2,
+            translate: true,
+            single_segment: true,
+            suppress_blank: false,
+            suppress_nst: false,
+            initial_prompt: Some("Vocabulary: tokio.".to_string()),
+        });
+    }
+
+    fn segment(start_ms: i64, end_ms: i64, text: &str) -> TranscriptSegment {
+        TranscriptSegment { start_ms, end_ms, text: text.to_string() }
+    }
+
+    #[test]
+    fn test_join_segments_skips_empty_text() {
+        let segments = vec![segment(0, 1200, "List the files."), segment(1200, 1500, ""), segment(1500, 3000, "Newest first.")];
+        assert_eq!(join_segments(&segments), "List the files. Newest first.");
+        assert_eq!(join_segments(&[]), "");
+    }
+
+    #[test]
+    fn test_append_segments_offsets_and_drops_overlap() {
+        let mut segments = Vec::new();
+        append_segments(&mut segments, vec![segment(0, 4000, "one two"), segment(4000, 9000, "three")], 0);
+        // The second window starts at 8s, so its first segment repeats the end of "three"
+        append_segments(&mut segments, vec![segment(0, 1000, "three"), segment(1000, 3000, "four")], 8000);
+
+        assert_eq!(
+            segments,
+            vec![segment(0, 4000, "one two"), segment(4000, 9000, "three"), segment(9000, 11000, "four")]
+        );
+    }
+
+    #[test]
+    fn test_initial_prompt_skips_blank_text() {
+        assert_eq!(initial_prompt(" Rust, tokio, serde. "), Some("Rust, tokio, serde.".to_string()));
+        assert_eq!(initial_prompt("clip\0ster"), Some("clipster".to_string()));
+        assert_eq!(initial_prompt("   "), None);
+        assert_eq!(initial_prompt(""), None);
+
+        let settings = WhisperSettings { initial_prompt: initial_prompt("Rust"), ..Default::default() };
+        settings.build_params();
+    }
+
+    #[test]
+    fn test_language_setting() {
+        assert_eq!(language_setting("en"), Some("en"));
+        assert_eq!(language_setting(" de "), Some("de"));
+        assert_eq!(language_setting("auto"), None);
+        assert_eq!(language_setting("AUTO"), None);
+        assert_eq!(language_setting(""), None);
+
+        let settings = WhisperSettings { language: language_setting("auto").map(str::to_string), ..Default::default() };
+        assert_eq!(settings.language, None);
+    }
+
+    #[test]
+    fn test_whisper_settings_sampling_strategy_reflects_best_of() {
+        let settings = WhisperSettings { best_of: 3, ..Default::default() };
+        assert!(matches!(settings.sampling_strategy(), SamplingStrategy::Greedy { best_of: 3 }));
+    }
+
+    #[test]
+    fn test_chunk_ranges_single_window() {
+        let ranges = chunk_ranges(100, 480_000, 48_000);
+        assert_eq!(ranges.len(), 1);
+        assert_eq!(ranges[0], 0..100);
+    }
+
+    #[test]
+    fn test_chunk_ranges_overlap() {
+        let ranges = chunk_ranges(25, 10, 2);
+        assert_eq!(ranges, vec![0..10, 8..18, 16..25]);
+    }
+
+    #[test]
+    fn test_chunk_ranges_covers_all_samples() {
+        let ranges = chunk_ranges(1000, 300, 50);
+        assert_eq!(ranges.first().unwrap().start, 0);
+        assert_eq!(ranges.last().unwrap().end, 1000);
+        for pair in ranges.windows(2) {
+            assert!(pair[1].start < pair[0].end);
+        }
+    }
+
+    #[test]
+    fn test_merge_overlap_removes_duplicated_words() {
+        let merged = merge_overlap("write a function that parses", "that parses JSON input");
+        assert_eq!(merged, "write a function that parses JSON input");
+    }
+
+    #[test]
+    fn test_merge_overlap_ignores_case_and_punctuation() {
+        let merged = merge_overlap("Sort the list.", "the list, then reverse it");
+        assert_eq!(merged, "Sort the list. then reverse it");
+    }
+
+    #[test]
+    fn test_merge_overlap_without_shared_words() {
+        assert_eq!(merge_overlap("hello there", "general kenobi"), "hello there general kenobi");
+    }
+
+    #[test]
+    fn test_merge_overlap_with_empty_sides() {
+        assert_eq!(merge_overlap("", "first window"), "first window");
+        assert_eq!(merge_overlap("last window", ""), "last window");
+    }
+}
@@ -1,3 +1,9 @@
 pub mod traits;
 pub mod anthropic;
-pub mod openai;
\ No newline at end of file
+pub mod cohere;
+pub mod gemini;
+pub mod headers;
+pub mod ollama;
+pub mod openai;
+pub mod retry;
+pub mod sse;
@@ -1,16 +1,41 @@
 use async_trait::async_trait;
+use futures_util::StreamExt;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::error::Error;
+use std::time::Duration;
 
-use crate::ai::remote::traits::{AI, Message};
+use crate::ai::remote::headers::{http_client, CustomHeaders, DEFAULT_TIMEOUT, DEFAULT_USER_AGENT};
+use crate::ai::remote::retry::{send_with_retries, RETRY_BASE_DELAY};
+use crate::ai::remote::sse::{event_stream, EventStream};
+use crate::ai::remote::traits::{Message, StreamChunk, TextStream, Usage, AI, SYSTEM};
+use crate::error::{api_key_from_env, ClipsterError};
+
+/// Stable API version sent unless overridden with `with_api_version`
+pub const DEFAULT_API_VERSION: &str = "2023-06-01";
+
+pub type AnthropicStream = EventStream<AnthropicDelta>;
 
 #[derive(Debug, Clone)]
 pub struct AnthropicAI {
     client: reqwest::Client,
+    user_agent: String,
+    timeout: Duration,
+    /// Extra attempts for 429 and 5xx responses, see [`send_with_retries`]
+    max_retries: u32,
+    retry_base_delay: Duration,
     api_key: String,
+    base_url: String,
     model: String,
-    max_tokens: u32,
+    /// `None` uses the model's maximum output, see [`model_max_tokens`]
+    max_tokens: Option<u32>,
     temperature: f32,
+    streaming: bool,
+    api_version: String,
+    beta: Option<String>,
+    headers: CustomHeaders,
+    /// The response ends at the first of these, empty sends none
+    stop_sequences: Vec<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -21,6 +46,10 @@ struct AnthropicRequest {
     temperature: f32,
     #[serde(skip_serializing_if = "Option::is_none")]
     system: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    stop_sequences: Vec<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -32,6 +61,10 @@ pub struct AnthropicMessage {
 #[derive(Debug, Deserialize)]
 struct AnthropicResponse {
     content: Vec<ContentBlock>,
+    #[serde(default)]
+    stop_reason: Option<String>,
+    #[serde(default)]
+    usage: Option<Usage>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -39,24 +72,92 @@ struct ContentBlock {
     text: String,
 }
 
+#[derive(Debug, Deserialize)]
+struct ModelsResponse {
+    data: Vec<ModelInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModelInfo {
+    id: String,
+}
+
+/// Incremental output of a streamed response
+#[derive(Debug, Clone, PartialEq)]
+pub enum AnthropicDelta {
+    Text(String),
+    /// Input tokens from `message_start`, output tokens so far from `message_delta`
+    Usage(Usage),
+    StopReason(String),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum StreamEvent {
+    MessageStart { message: StartMessage },
+    ContentBlockDelta { delta: TextDelta },
+    MessageDelta {
+        delta: MessageDelta,
+        #[serde(default)]
+        usage: Option<Usage>,
+    },
+    Error { error: StreamError },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+struct StartMessage {
+    #[serde(default)]
+    usage: Option<Usage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TextDelta {
+    #[serde(default)]
+    text: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MessageDelta {
+    #[serde(default)]
+    stop_reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamError {
+    message: String,
+}
+
 impl AnthropicAI {
     pub fn new(api_key: String, model: &str) -> Self {
         Self {
-            client: reqwest::Client::new(),
+            client: http_client(DEFAULT_USER_AGENT, DEFAULT_TIMEOUT),
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            timeout: DEFAULT_TIMEOUT,
+            max_retries: 0,
+            retry_base_delay: RETRY_BASE_DELAY,
             api_key,
+            base_url: "https://api.anthropic.com".to_string(),
             model: model.to_string(),
-            max_tokens: 1000,
+            max_tokens: Some(1000),
             temperature: 0.7,
+            streaming: false,
+            api_version: DEFAULT_API_VERSION.to_string(),
+            beta: None,
+            headers: CustomHeaders::default(),
+            stop_sequences: Vec::new(),
         }
     }
 
-    pub fn from_env() -> Result<Self, Box<dyn Error>> {
-        let api_key = std::env::var("ANTHROPIC_API_KEY".to_string())?;
+    pub fn from_env() -> Result<Self, ClipsterError> {
+        let api_key = api_key_from_env("ANTHROPIC_API_KEY")?;
         Ok(Self::new(api_key, "claude-haiku-4-5-20251001"))
     }
 
-    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
-        self.max_tokens = max_tokens;
+    /// Anthropic requires a cap, so `None` sends the model's maximum output
+    pub fn with_max_tokens(mut self, max_tokens: impl Into<Option<u32>>) -> Self {
+        self.max_tokens = max_tokens.into();
         self
     }
 
@@ -65,69 +166,293 @@ impl AnthropicAI {
         self
     }
 
-    #[allow(dead_code)]
     pub fn with_model(mut self, model: &str) -> Self {
         self.model = model.to_string();
         self
     }
 
+    /// Ends the response at any of `sequences`, which aren't included (`stop_reason` is `stop_sequence`)
+    pub fn with_stop(mut self, sequences: Vec<String>) -> Self {
+        self.stop_sequences = sequences;
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Overrides the `anthropic-version` header
+    pub fn with_api_version(mut self, api_version: &str) -> Self {
+        self.api_version = api_version.to_string();
+        self
+    }
+
+    /// Opts into beta features via the `anthropic-beta` header (comma-separated for several)
+    pub fn with_beta(mut self, beta: &str) -> Self {
+        self.beta = Some(beta.to_string()).filter(|beta| !beta.is_empty());
+        self
+    }
+
+    pub fn with_user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = user_agent.to_string();
+        self.client = http_client(&self.user_agent, self.timeout);
+        self
+    }
+
+    /// Retries rate limits and server errors with exponential backoff, or after `Retry-After`
+    pub fn with_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Bounds connecting and each read of the response by `timeout`, 30 seconds by default
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self.client = http_client(&self.user_agent, self.timeout);
+        self
+    }
+
+    /// Sends an extra header with every request, see [`CustomHeaders`] for precedence
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    pub fn with_headers(mut self, headers: &HashMap<String, String>) -> Self {
+        for (name, value) in headers {
+            self.headers.insert(name, value);
+        }
+        self
+    }
+
+    /// Lets a custom header replace the API key header, e.g. for a gateway's own token
+    pub fn with_auth_override(mut self) -> Self {
+        self.headers.allow_auth_override();
+        self
+    }
+
+    /// Use the SSE endpoint for `chat_internal` instead of a single buffered response
+    #[allow(dead_code)]
+    pub fn with_streaming(mut self, streaming: bool) -> Self {
+        self.streaming = streaming;
+        self
+    }
+
     pub async fn chat_internal(
         &self,
         messages: Vec<AnthropicMessage>,
         system: Option<String>,
-    ) -> Result<String, Box<dyn Error>> {
+    ) -> Result<String, ClipsterError> {
+        let (text, _) = self.chat_with_usage_internal(messages, system).await?;
+        Ok(text)
+    }
+
+    async fn chat_with_usage_internal(
+        &self,
+        messages: Vec<AnthropicMessage>,
+        system: Option<String>,
+    ) -> Result<(String, Option<Usage>), ClipsterError> {
+        if self.streaming {
+            let mut stream = self.stream_internal(messages, system).await?;
+            let mut text = String::new();
+            let mut usage: Option<Usage> = None;
+            let mut stop_reason = None;
+
+            while let Some(delta) = stream.next().await {
+                match delta.map_err(ClipsterError::from_boxed)? {
+                    AnthropicDelta::Text(chunk) => text.push_str(&chunk),
+                    AnthropicDelta::Usage(reported) => usage.get_or_insert_default().merge(reported),
+                    AnthropicDelta::StopReason(reason) => stop_reason = Some(reason),
+                }
+            }
+
+            warn_on_stop_reason(stop_reason.as_deref());
+            return Ok((text, usage));
+        }
+
+        let response = self.send(messages, system, false).await?;
+        let anthropic_response: AnthropicResponse = response.json().await.map_err(|e| ClipsterError::from_reqwest(e, self.timeout))?;
+
+        warn_on_stop_reason(anthropic_response.stop_reason.as_deref());
+
+        anthropic_response
+            .content
+            .first()
+            .map(|block| (block.text.clone(), anthropic_response.usage))
+            .ok_or_else(|| ClipsterError::InvalidResponse("No response from Anthropic API".to_string()))
+    }
+
+    /// Streams text deltas as they arrive, then the usage and `stop_reason` from the final `message_delta`
+    pub async fn stream_internal(
+        &self,
+        messages: Vec<AnthropicMessage>,
+        system: Option<String>,
+    ) -> Result<AnthropicStream, ClipsterError> {
+        let response = self.send(messages, system, true).await?;
+        Ok(event_stream(response, self.timeout, parse_stream_event))
+    }
+
+    pub async fn list_models_internal(&self) -> Result<Vec<String>, ClipsterError> {
+        let request = self
+            .client
+            .get(format!("{}/v1/models", self.base_url))
+            .header("x-api-key", &self.api_key)
+            .header("anthropic-version", &self.api_version)
+            .headers(self.headers.to_header_map("x-api-key").map_err(ClipsterError::InvalidConfig)?);
+        let response = send_with_retries(request, self.max_retries, self.retry_base_delay)
+            .await
+            .map_err(|e| ClipsterError::from_reqwest(e, self.timeout))?;
+
+        if !response.status().is_success() {
+            return Err(ClipsterError::from_response("Anthropic", response).await);
+        }
+
+        let models: ModelsResponse = response.json().await.map_err(|e| ClipsterError::from_reqwest(e, self.timeout))?;
+        Ok(models.data.into_iter().map(|model| model.id).collect())
+    }
+
+    async fn send(
+        &self,
+        messages: Vec<AnthropicMessage>,
+        system: Option<String>,
+        stream: bool,
+    ) -> Result<reqwest::Response, ClipsterError> {
         let request = AnthropicRequest {
             model: self.model.clone(),
             messages,
-            max_tokens: self.max_tokens,
+            max_tokens: self.max_tokens.unwrap_or_else(|| model_max_tokens(&self.model)),
             temperature: self.temperature,
             system,
+            stop_sequences: self.stop_sequences.clone(),
+            stream,
         };
 
-        let response = self
+        let mut request_builder = self
             .client
-            .post("https://api.anthropic.com/v1/messages")
+            .post(format!("{}/v1/messages", self.base_url))
             .header("x-api-key", &self.api_key)
-            .header("anthropic-version", "2023-06-01")
-            .header("Content-Type", "application/json")
-            .json(&request)
-            .send()
-            .await?;
+            .header("anthropic-version", &self.api_version)
+            .header("Content-Type", "application/json");
+
+        if let Some(beta) = &self.beta {
+            request_builder = request_builder.header("anthropic-beta", beta);
+        }
+
+        request_builder = request_builder.headers(self.headers.to_header_map("x-api-key").map_err(ClipsterError::InvalidConfig)?);
+
+        let request = request_builder.json(&request);
+        let response = send_with_retries(request, self.max_retries, self.retry_base_delay)
+            .await
+            .map_err(|e| ClipsterError::from_reqwest(e, self.timeout))?;
 
         if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response.text().await?;
-            return Err(format!("Anthropic API error {}: {}", status, error_text).into());
+            return Err(ClipsterError::from_response("Anthropic", response).await);
         }
 
-        let anthropic_response: AnthropicResponse = response.json().await?;
+        Ok(response)
+    }
+}
 
-        anthropic_response
-            .content
-            .first()
-            .map(|block| block.text.clone())
-            .ok_or_else(|| "No response from Anthropic API".into())
+fn parse_stream_event(data: &str) -> Vec<Result<AnthropicDelta, String>> {
+    match serde_json::from_str::<StreamEvent>(data) {
+        Ok(StreamEvent::MessageStart { message }) => message.usage.map(AnthropicDelta::Usage).into_iter().map(Ok).collect(),
+        Ok(StreamEvent::ContentBlockDelta { delta }) => delta.text.map(AnthropicDelta::Text).into_iter().map(Ok).collect(),
+        Ok(StreamEvent::MessageDelta { delta, usage }) => usage
+            .map(AnthropicDelta::Usage)
+            .into_iter()
+            .chain(delta.stop_reason.map(AnthropicDelta::StopReason))
+            .map(Ok)
+            .collect(),
+        Ok(StreamEvent::Error { error }) => vec![Err(format!("Anthropic stream error: {}", error.message))],
+        Ok(StreamEvent::Other) => Vec::new(),
+        Err(e) => vec![Err(format!("Malformed Anthropic stream event: {}", e))],
     }
 }
 
+fn warn_on_stop_reason(stop_reason: Option<&str>) {
+    if stop_reason == Some("max_tokens") {
+        eprintln!("Warning: Anthropic response was truncated (stop_reason: max_tokens)");
+    }
+}
+
+/// Largest output a model accepts, used when no cap is configured since the API requires one
+fn model_max_tokens(model: &str) -> u32 {
+    if model.contains("opus-4") {
+        32000
+    } else if model.contains("sonnet-4") || model.contains("haiku-4") || model.contains("3-7-sonnet") {
+        64000
+    } else if model.contains("3-5") {
+        8192
+    } else {
+        4096
+    }
+}
+
+/// Anthropic takes the system prompt as a separate field rather than a message
+fn split_system(messages: Vec<Message>) -> (Vec<AnthropicMessage>, Option<String>) {
+    let mut system_content: Option<String> = None;
+    let mut anthropic_messages = Vec::new();
+
+    for msg in messages {
+        if msg.role == SYSTEM {
+            system_content = Some(msg.content);
+        } else {
+            anthropic_messages.push(AnthropicMessage {
+                role: msg.role,
+                content: msg.content,
+            });
+        }
+    }
+
+    (anthropic_messages, system_content)
+}
+
 #[async_trait]
 impl AI for AnthropicAI {
+    fn name(&self) -> String {
+        format!("anthropic:{}", self.model)
+    }
+
     async fn chat(&self, messages: Vec<Message>) -> Result<String, Box<dyn Error>> {
-        let mut system_content: Option<String> = None;
-        let mut anthropic_messages = Vec::new();
+        let (anthropic_messages, system_content) = split_system(messages);
+        Ok(self.chat_internal(anthropic_messages, system_content).await?)
+    }
 
-        for msg in messages {
-            if msg.role == "system" {
-                system_content = Some(msg.content);
-            } else {
-                anthropic_messages.push(AnthropicMessage {
-                    role: msg.role,
-                    content: msg.content,
-                });
-            }
-        }
+    async fn chat_with_usage(&self, messages: Vec<Message>) -> Result<(String, Option<Usage>), Box<dyn Error>> {
+        let (anthropic_messages, system_content) = split_system(messages);
+        Ok(self.chat_with_usage_internal(anthropic_messages, system_content).await?)
+    }
+
+    async fn chat_stream(&self, messages: Vec<Message>) -> Result<TextStream, Box<dyn Error>> {
+        let (anthropic_messages, system_content) = split_system(messages);
+        let stream = self.stream_internal(anthropic_messages, system_content).await?;
+
+        // Errors are unboxed while filtering, a pending `Box<dyn Error>` would make the stream !Send
+        let text = stream
+            .map(|delta| match delta {
+                Ok(AnthropicDelta::Text(text)) => Some(Ok(StreamChunk::Text(text))),
+                Ok(AnthropicDelta::Usage(usage)) => Some(Ok(StreamChunk::Usage(usage))),
+                Ok(AnthropicDelta::StopReason(reason)) => {
+                    warn_on_stop_reason(Some(&reason));
+                    None
+                }
+                Err(e) => Some(Err(ClipsterError::from_boxed(e))),
+            })
+            .filter_map(futures_util::future::ready)
+            .map(|chunk| chunk.map_err(|e| e.into()));
 
-        self.chat_internal(anthropic_messages, system_content).await
+        Ok(Box::pin(text))
+    }
+
+    async fn warm_up(&self) -> Result<(), Box<dyn Error>> {
+        self.client.head(&self.base_url).send().await?;
+        Ok(())
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>, Box<dyn Error>> {
+        Ok(self.list_models_internal().await?)
     }
 }
 
@@ -135,12 +460,20 @@ impl AI for AnthropicAI {
 mod tests {
     use super::*;
 
+    #[tokio::test]
+    async fn test_model_max_tokens() {
+        assert_eq!(model_max_tokens("claude-haiku-4-5-20251001"), 64000);
+        assert_eq!(model_max_tokens("claude-opus-4-1"), 32000);
+        assert_eq!(model_max_tokens("claude-3-5-haiku-latest"), 8192);
+        assert_eq!(model_max_tokens("claude-3-opus-20240229"), 4096);
+    }
+
     #[tokio::test]
     async fn test_anthropic_with_max_tokens() {
         let client = AnthropicAI::new("test_key".to_string(), "claude-4")
             .with_max_tokens(2000);
 
-        assert_eq!(client.max_tokens, 2000);
+        assert_eq!(client.max_tokens, Some(2000));
     }
 
     #[tokio::test]
@@ -172,6 +505,99 @@ mod tests {
         assert_eq!(client.model, "claude-sonnet");
     }
 
+    #[tokio::test]
+    async fn test_anthropic_name() {
+        let client = AnthropicAI::new("test_key".to_string(), "claude-4");
+        assert_eq!(client.name(), "anthropic:claude-4");
+    }
+
+    #[tokio::test]
+    async fn test_anthropic_api_version_defaults() {
+        let client = AnthropicAI::new("test_key".to_string(), "claude-4");
+
+        assert_eq!(client.api_version, DEFAULT_API_VERSION);
+        assert_eq!(client.beta, None);
+    }
+
+    #[tokio::test]
+    async fn test_mock_api_version_and_beta_headers() {
+        let mut server = mockito::Server::new_async().await;
+
+        let mock = server.mock("POST", "/v1/messages")
+            .match_header("anthropic-version", "2024-01-01")
+            .match_header("anthropic-beta", "prompt-caching-2024-07-31")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"content": [{"text": "ok"}]}"#)
+            .create_async()
+            .await;
+
+        let client = AnthropicAI::new("test_key".to_string(), "claude-4")
+            .with_base_url(&server.url())
+            .with_api_version("2024-01-01")
+            .with_beta("prompt-caching-2024-07-31");
+
+        let result = client.generate("Hi").await;
+
+        mock.assert_async().await;
+        assert_eq!(result.unwrap(), "ok");
+    }
+
+    #[tokio::test]
+    async fn test_mock_list_models() {
+        let mut server = mockito::Server::new_async().await;
+
+        let mock = server.mock("GET", "/v1/models")
+            .match_header("x-api-key", "test_key")
+            .match_header("anthropic-version", DEFAULT_API_VERSION)
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{
+                "data": [
+                    {"id": "claude-sonnet-4-5", "type": "model"},
+                    {"id": "claude-haiku-4-5", "type": "model"}
+                ],
+                "has_more": false
+            }"#)
+            .create_async()
+            .await;
+
+        let client = AnthropicAI::new("test_key".to_string(), "claude-4").with_base_url(&server.url());
+        let models = client.list_models().await;
+
+        mock.assert_async().await;
+        assert_eq!(models.unwrap(), vec!["claude-sonnet-4-5", "claude-haiku-4-5"]);
+    }
+
+    #[tokio::test]
+    async fn test_mock_custom_headers() {
+        let mut server = mockito::Server::new_async().await;
+
+        let mock = server.mock("POST", "/v1/messages")
+            .match_header("x-api-key", "test_key")
+            .match_header("x-route", "eu")
+            .match_header("anthropic-version", "2024-01-01")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"content": [{"text": "ok"}]}"#)
+            .create_async()
+            .await;
+
+        let headers = HashMap::from([
+            ("x-route".to_string(), "eu".to_string()),
+            ("x-api-key".to_string(), "ignored".to_string()),
+            ("anthropic-version".to_string(), "2024-01-01".to_string()),
+        ]);
+        let client = AnthropicAI::new("test_key".to_string(), "claude-4")
+            .with_base_url(&server.url())
+            .with_headers(&headers);
+
+        let result = client.generate("Hi").await;
+
+        mock.assert_async().await;
+        assert_eq!(result.unwrap(), "ok");
+    }
+
     #[tokio::test]
     async fn test_anthropic_message_conversion() {
         let messages = vec![
@@ -220,12 +646,32 @@ mod tests {
             max_tokens: 1000,
             temperature: 0.7,
             system: Some("You are helpful".to_string()),
+            stop_sequences: Vec::new(),
+            stream: false,
         };
 
         let json = serde_json::to_string(&request).unwrap();
         assert!(json.contains("\"model\":\"claude-4\""));
         assert!(json.contains("\"temperature\":0.7"));
         assert!(json.contains("\"system\":\"You are helpful\""));
+        assert!(!json.contains("\"stream\""));
+        assert!(!json.contains("stop_sequences"));
+    }
+
+    #[test]
+    fn test_anthropic_request_serializes_stop_sequences() {
+        let request = AnthropicRequest {
+            model: "claude-4".to_string(),
+            messages: Vec::new(),
+            max_tokens: 1000,
+            temperature: 0.7,
+            system: None,
+            stop_sequences: vec!["\n```".to_string(), "END".to_string()],
+            stream: false,
+        };
+
+        let json: serde_json::Value = serde_json::to_value(&request).unwrap();
+        assert_eq!(json["stop_sequences"], serde_json::json!(["\n```", "END"]));
     }
 
     #[tokio::test]
@@ -235,11 +681,164 @@ mod tests {
                 {
                     "text": "Test response"
                 }
-            ]
+            ],
+            "usage": {"input_tokens": 12, "output_tokens": 4}
         }"#;
 
         let response: AnthropicResponse = serde_json::from_str(json).unwrap();
         assert_eq!(response.content.len(), 1);
         assert_eq!(response.content[0].text, "Test response");
+        assert_eq!(response.usage, Some(Usage { input_tokens: 12, output_tokens: 4 }));
+    }
+
+    #[tokio::test]
+    async fn test_anthropic_response_deserialization_stop_reason() {
+        let json = r#"{
+            "content": [{"text": "Test"}],
+            "stop_reason": "max_tokens"
+        }"#;
+
+        let response: AnthropicResponse = serde_json::from_str(json).unwrap();
+        assert_eq!(response.stop_reason.as_deref(), Some("max_tokens"));
+    }
+
+    #[tokio::test]
+    async fn test_parse_stream_event() {
+        let start = r#"{"type":"message_start","message":{"id":"msg_1","usage":{"input_tokens":25,"output_tokens":1}}}"#;
+        assert_eq!(
+            parse_stream_event(start),
+            vec![Ok(AnthropicDelta::Usage(Usage { input_tokens: 25, output_tokens: 1 }))]
+        );
+
+        let delta = r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Hi"}}"#;
+        assert_eq!(parse_stream_event(delta), vec![Ok(AnthropicDelta::Text("Hi".to_string()))]);
+
+        let stop = r#"{"type":"message_delta","delta":{"stop_reason":"end_turn"},"usage":{"output_tokens":5}}"#;
+        assert_eq!(parse_stream_event(stop), vec![
+            Ok(AnthropicDelta::Usage(Usage { input_tokens: 0, output_tokens: 5 })),
+            Ok(AnthropicDelta::StopReason("end_turn".to_string())),
+        ]);
+
+        let ping = r#"{"type":"ping"}"#;
+        assert!(parse_stream_event(ping).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_mock_retries_overloaded() {
+        let mut server = mockito::Server::new_async().await;
+
+        let unavailable = server.mock("POST", "/v1/messages")
+            .with_status(503)
+            .expect(1)
+            .create_async()
+            .await;
+        let success = server.mock("POST", "/v1/messages")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"content":[{"type":"text","text":"ls"}]}"#)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let mut client = AnthropicAI::new("test_key".to_string(), "claude-4")
+            .with_base_url(&server.url())
+            .with_retries(1);
+        client.retry_base_delay = Duration::ZERO;
+
+        assert_eq!(client.generate("Hi").await.unwrap(), "ls");
+        unavailable.assert_async().await;
+        success.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_mock_timeout() {
+        let mut server = mockito::Server::new_async().await;
+
+        let mock = server.mock("POST", "/v1/messages")
+            .with_status(200)
+            .with_chunked_body(|body| {
+                std::thread::sleep(Duration::from_millis(500));
+                body.write_all(br#"{"content":[{"type":"text","text":"late"}]}"#)
+            })
+            .create_async()
+            .await;
+
+        let client = AnthropicAI::new("test_key".to_string(), "claude-4")
+            .with_base_url(&server.url())
+            .with_timeout(Duration::from_millis(100));
+
+        let err = client.generate("Hi").await.unwrap_err();
+
+        mock.assert_async().await;
+        assert_eq!(err.to_string(), "request timed out after 0.1s");
+    }
+
+    #[tokio::test]
+    async fn test_mock_streaming_chat() {
+        let mut server = mockito::Server::new_async().await;
+
+        let body = concat!(
+            "event: message_start\n",
+            "data: {\"type\":\"message_start\",\"message\":{\"usage\":{\"input_tokens\":10,\"output_tokens\":1}}}\n\n",
+            "event: content_block_delta\n",
+            "data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"Hello\"}}\n\n",
+            "event: content_block_delta\n",
+            "data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\" world\"}}\n\n",
+            "event: message_delta\n",
+            "data: {\"type\":\"message_delta\",\"delta\":{\"stop_reason\":\"end_turn\"},\"usage\":{\"output_tokens\":2}}\n\n",
+            "event: message_stop\n",
+            "data: {\"type\":\"message_stop\"}\n\n",
+        );
+
+        let mock = server.mock("POST", "/v1/messages")
+            .match_body(mockito::Matcher::PartialJsonString(r#"{"stream":true}"#.to_string()))
+            .with_status(200)
+            .with_header("content-type", "text/event-stream")
+            .with_body(body)
+            .expect(3)
+            .create_async()
+            .await;
+
+        let client = AnthropicAI::new("test_key".to_string(), "claude-4")
+            .with_base_url(&server.url())
+            .with_streaming(true);
+
+        let messages = vec![AnthropicMessage {
+            role: "user".to_string(),
+            content: "Hi".to_string(),
+        }];
+
+        let mut stream = client.stream_internal(messages.clone(), None).await.unwrap();
+        let mut deltas = Vec::new();
+        while let Some(delta) = stream.next().await {
+            deltas.push(delta.unwrap());
+        }
+
+        assert_eq!(deltas, vec![
+            AnthropicDelta::Usage(Usage { input_tokens: 10, output_tokens: 1 }),
+            AnthropicDelta::Text("Hello".to_string()),
+            AnthropicDelta::Text(" world".to_string()),
+            AnthropicDelta::Usage(Usage { input_tokens: 0, output_tokens: 2 }),
+            AnthropicDelta::StopReason("end_turn".to_string()),
+        ]);
+
+        let result = client.chat_with_usage_internal(messages, None).await.unwrap();
+        assert_eq!(result, ("Hello world".to_string(), Some(Usage { input_tokens: 10, output_tokens: 2 })));
+
+        let chunks: Vec<StreamChunk> = client
+            .chat_stream(vec![Message::user("Hi")])
+            .await
+            .unwrap()
+            .map(|chunk| chunk.unwrap())
+            .collect()
+            .await;
+        assert_eq!(chunks, vec![
+            StreamChunk::Usage(Usage { input_tokens: 10, output_tokens: 1 }),
+            StreamChunk::Text("Hello".to_string()),
+            StreamChunk::Text(" world".to_string()),
+            StreamChunk::Usage(Usage { input_tokens: 0, output_tokens: 2 }),
+        ]);
+
+        mock.assert_async().await;
     }
 }
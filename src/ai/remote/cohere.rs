@@ -0,0 +1,302 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::time::Duration;
+
+use crate::ai::remote::headers::{http_client, DEFAULT_TIMEOUT, DEFAULT_USER_AGENT};
+use crate::ai::remote::retry::{send_with_retries, RETRY_BASE_DELAY};
+use crate::ai::remote::traits::{Message, Usage, AI, ASSISTANT, SYSTEM, USER};
+use crate::error::ClipsterError;
+
+#[derive(Debug, Clone)]
+pub struct CohereAI {
+    client: reqwest::Client,
+    user_agent: String,
+    timeout: Duration,
+    /// Extra attempts for 429 and 5xx responses, see [`send_with_retries`]
+    max_retries: u32,
+    retry_base_delay: Duration,
+    api_key: String,
+    base_url: String,
+    model: String,
+    /// `None` leaves the cap to Cohere
+    max_tokens: Option<u32>,
+    temperature: f32,
+}
+
+#[derive(Debug, Serialize)]
+struct CohereRequest {
+    model: String,
+    messages: Vec<CohereMessage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_tokens: Option<u32>,
+    temperature: f32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct CohereMessage {
+    role: String,
+    content: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct CohereResponse {
+    message: CohereResponseMessage,
+    #[serde(default)]
+    usage: Option<CohereUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CohereUsage {
+    #[serde(default)]
+    billed_units: Option<Usage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CohereResponseMessage {
+    #[serde(default)]
+    content: Vec<CohereContent>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CohereContent {
+    #[serde(default)]
+    text: Option<String>,
+}
+
+impl CohereAI {
+    pub fn new(api_key: String, model: &str) -> Self {
+        Self {
+            client: http_client(DEFAULT_USER_AGENT, DEFAULT_TIMEOUT),
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            timeout: DEFAULT_TIMEOUT,
+            max_retries: 0,
+            retry_base_delay: RETRY_BASE_DELAY,
+            api_key,
+            base_url: "https://api.cohere.com/v2".to_string(),
+            model: model.to_string(),
+            max_tokens: Some(1000),
+            temperature: 0.7,
+        }
+    }
+
+    pub fn from_env() -> Result<Self, Box<dyn Error>> {
+        let api_key = std::env::var("COHERE_API_KEY")?;
+        Ok(Self::new(api_key, "command-a-03-2025"))
+    }
+
+    /// `None` sends no cap so Cohere's default applies
+    pub fn with_max_tokens(mut self, max_tokens: impl Into<Option<u32>>) -> Self {
+        self.max_tokens = max_tokens.into();
+        self
+    }
+
+    pub fn with_temperature(mut self, temperature: f32) -> Self {
+        self.temperature = temperature.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn with_model(mut self, model: &str) -> Self {
+        self.model = model.to_string();
+        self
+    }
+
+    pub fn with_user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = user_agent.to_string();
+        self.client = http_client(&self.user_agent, self.timeout);
+        self
+    }
+
+    /// Retries rate limits and server errors with exponential backoff, or after `Retry-After`
+    pub fn with_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Bounds connecting and each read of the response by `timeout`, 30 seconds by default
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self.client = http_client(&self.user_agent, self.timeout);
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    async fn do_chat(&self, messages: Vec<CohereMessage>) -> Result<(String, Option<Usage>), Box<dyn Error>> {
+        let request = CohereRequest {
+            model: self.model.clone(),
+            messages,
+            max_tokens: self.max_tokens,
+            temperature: self.temperature,
+        };
+
+        let request = self
+            .client
+            .post(format!("{}/chat", self.base_url))
+            .header("Authorization", format!("Bearer {}", self.api_key))
+            .header("Content-Type", "application/json")
+            .json(&request);
+        let response = send_with_retries(request, self.max_retries, self.retry_base_delay)
+            .await
+            .map_err(|e| ClipsterError::from_reqwest(e, self.timeout))?;
+
+        if !response.status().is_success() {
+            return Err(ClipsterError::from_response("Cohere", response).await.into());
+        }
+
+        let cohere_response: CohereResponse = response.json().await.map_err(|e| ClipsterError::from_reqwest(e, self.timeout))?;
+
+        let text: String = cohere_response
+            .message
+            .content
+            .into_iter()
+            .filter_map(|content| content.text)
+            .collect();
+
+        if text.is_empty() {
+            return Err("No response from Cohere API".into());
+        }
+
+        let usage = cohere_response.usage.and_then(|usage| usage.billed_units);
+        Ok((text, usage))
+    }
+}
+
+/// Cohere accepts system/user/assistant roles; anything else is sent as user input
+fn to_cohere_message(message: Message) -> CohereMessage {
+    let role = match message.role.as_str() {
+        SYSTEM | USER | ASSISTANT => message.role,
+        _ => USER.to_string(),
+    };
+
+    CohereMessage {
+        role,
+        content: message.content,
+    }
+}
+
+#[async_trait]
+impl AI for CohereAI {
+    fn name(&self) -> String {
+        format!("cohere:{}", self.model)
+    }
+
+    async fn chat(&self, messages: Vec<Message>) -> Result<String, Box<dyn Error>> {
+        let (text, _) = self.chat_with_usage(messages).await?;
+        Ok(text)
+    }
+
+    async fn chat_with_usage(&self, messages: Vec<Message>) -> Result<(String, Option<Usage>), Box<dyn Error>> {
+        let cohere_messages = messages.into_iter().map(to_cohere_message).collect();
+        self.do_chat(cohere_messages).await
+    }
+
+    async fn warm_up(&self) -> Result<(), Box<dyn Error>> {
+        self.client.head(&self.base_url).send().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito;
+
+    #[tokio::test]
+    async fn test_cohere_builders() {
+        let client = CohereAI::new("test_key".to_string(), "command-r")
+            .with_max_tokens(2000)
+            .with_temperature(1.5)
+            .with_model("command-a");
+
+        assert_eq!(client.max_tokens, Some(2000));
+        assert_eq!(client.temperature, 1.0);
+        assert_eq!(client.model, "command-a");
+        assert_eq!(client.name(), "cohere:command-a");
+    }
+
+    #[tokio::test]
+    async fn test_cohere_role_mapping() {
+        let roles: Vec<String> = ["system", "user", "assistant", "tool"]
+            .iter()
+            .map(|role| to_cohere_message(Message {
+                role: role.to_string(),
+                content: "x".to_string(),
+            }).role)
+            .collect();
+
+        assert_eq!(roles, vec!["system", "user", "assistant", "user"]);
+    }
+
+    #[tokio::test]
+    async fn test_cohere_response_deserialization() {
+        let json = r#"{
+            "id": "abc",
+            "finish_reason": "COMPLETE",
+            "message": {
+                "role": "assistant",
+                "content": [
+                    {"type": "text", "text": "Hello"},
+                    {"type": "text", "text": " there"}
+                ]
+            }
+        }"#;
+
+        let response: CohereResponse = serde_json::from_str(json).unwrap();
+        assert_eq!(response.message.content.len(), 2);
+        assert_eq!(response.message.content[0].text.as_deref(), Some("Hello"));
+    }
+
+    #[tokio::test]
+    async fn test_mock_api_success() {
+        let mut server = mockito::Server::new_async().await;
+
+        let mock = server.mock("POST", "/chat")
+            .match_header("authorization", "Bearer test_key")
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{"messages":[{"role":"system","content":"Be brief"},{"role":"user","content":"Hi"}]}"#.to_string()
+            ))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{
+                "message": {"role": "assistant", "content": [{"type": "text", "text": "Hello!"}]},
+                "usage": {"billed_units": {"input_tokens": 3, "output_tokens": 2}, "tokens": {"input_tokens": 70, "output_tokens": 2}}
+            }"#)
+            .create_async()
+            .await;
+
+        let client = CohereAI::new("test_key".to_string(), "command-a").with_base_url(&server.url());
+
+        let messages = vec![
+            Message { role: "system".to_string(), content: "Be brief".to_string() },
+            Message { role: "user".to_string(), content: "Hi".to_string() },
+        ];
+
+        let result = client.chat_with_usage(messages).await;
+
+        mock.assert_async().await;
+        assert_eq!(result.unwrap(), ("Hello!".to_string(), Some(Usage { input_tokens: 3, output_tokens: 2 })));
+    }
+
+    #[tokio::test]
+    async fn test_mock_api_error() {
+        let mut server = mockito::Server::new_async().await;
+
+        let mock = server.mock("POST", "/chat")
+            .with_status(401)
+            .with_body("invalid api token")
+            .create_async()
+            .await;
+
+        let client = CohereAI::new("test_key".to_string(), "command-a").with_base_url(&server.url());
+
+        let result = client.generate("Hi").await;
+
+        mock.assert_async().await;
+        assert_eq!(result.unwrap_err().to_string(), "Cohere API error 401 Unauthorized: invalid api token");
+    }
+}
@@ -0,0 +1,365 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::time::Duration;
+
+use crate::ai::remote::headers::{http_client, CustomHeaders, DEFAULT_TIMEOUT, DEFAULT_USER_AGENT};
+use crate::ai::remote::retry::{send_with_retries, RETRY_BASE_DELAY};
+use crate::ai::remote::traits::{Message, Usage, AI, ASSISTANT, SYSTEM};
+use crate::error::{api_key_from_env, ClipsterError};
+
+#[derive(Debug, Clone)]
+pub struct GeminiAI {
+    client: reqwest::Client,
+    user_agent: String,
+    timeout: Duration,
+    /// Extra attempts for 429 and 5xx responses, see [`send_with_retries`]
+    max_retries: u32,
+    retry_base_delay: Duration,
+    api_key: String,
+    base_url: String,
+    model: String,
+    /// `None` leaves the cap to Gemini
+    max_tokens: Option<u32>,
+    temperature: f32,
+    headers: CustomHeaders,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiRequest {
+    contents: Vec<GeminiContent>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system_instruction: Option<GeminiContent>,
+    generation_config: GenerationConfig,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+struct GeminiContent {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    role: Option<String>,
+    #[serde(default)]
+    parts: Vec<GeminiPart>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+struct GeminiPart {
+    #[serde(default)]
+    text: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GenerationConfig {
+    temperature: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_output_tokens: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiResponse {
+    #[serde(default)]
+    candidates: Vec<Candidate>,
+    #[serde(default)]
+    usage_metadata: Option<UsageMetadata>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Candidate {
+    #[serde(default)]
+    content: Option<GeminiContent>,
+    #[serde(default)]
+    finish_reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UsageMetadata {
+    #[serde(default)]
+    prompt_token_count: u64,
+    #[serde(default)]
+    candidates_token_count: u64,
+}
+
+impl GeminiAI {
+    pub fn new(api_key: String, model: &str) -> Self {
+        Self {
+            client: http_client(DEFAULT_USER_AGENT, DEFAULT_TIMEOUT),
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            timeout: DEFAULT_TIMEOUT,
+            max_retries: 0,
+            retry_base_delay: RETRY_BASE_DELAY,
+            api_key,
+            base_url: "https://generativelanguage.googleapis.com/v1beta".to_string(),
+            model: model.to_string(),
+            max_tokens: None,
+            temperature: 0.7,
+            headers: CustomHeaders::default(),
+        }
+    }
+
+    pub fn from_env() -> Result<Self, ClipsterError> {
+        let api_key = api_key_from_env("GEMINI_API_KEY")?;
+        Ok(Self::new(api_key, "gemini-2.5-flash"))
+    }
+
+    /// `None` sends no cap so Gemini's default applies
+    pub fn with_max_tokens(mut self, max_tokens: impl Into<Option<u32>>) -> Self {
+        self.max_tokens = max_tokens.into();
+        self
+    }
+
+    pub fn with_temperature(mut self, temperature: f32) -> Self {
+        self.temperature = temperature.clamp(0.0, 2.0);
+        self
+    }
+
+    pub fn with_model(mut self, model: &str) -> Self {
+        self.model = model.to_string();
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    pub fn with_user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = user_agent.to_string();
+        self.client = http_client(&self.user_agent, self.timeout);
+        self
+    }
+
+    /// Retries rate limits and server errors with exponential backoff, or after `Retry-After`
+    pub fn with_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Bounds connecting and each read of the response by `timeout`, 30 seconds by default
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self.client = http_client(&self.user_agent, self.timeout);
+        self
+    }
+
+    /// Sends an extra header with every request, see [`CustomHeaders`] for precedence
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    pub fn with_headers(mut self, headers: &HashMap<String, String>) -> Self {
+        for (name, value) in headers {
+            self.headers.insert(name, value);
+        }
+        self
+    }
+
+    async fn do_chat(&self, messages: Vec<Message>) -> Result<(String, Option<Usage>), ClipsterError> {
+        let (contents, system_instruction) = to_gemini_contents(messages);
+        let request = GeminiRequest {
+            contents,
+            system_instruction,
+            generation_config: GenerationConfig {
+                temperature: self.temperature,
+                max_output_tokens: self.max_tokens,
+            },
+        };
+
+        let request = self
+            .client
+            .post(format!("{}/models/{}:generateContent", self.base_url, self.model))
+            .header("x-goog-api-key", &self.api_key)
+            .headers(self.headers.to_header_map("x-goog-api-key").map_err(ClipsterError::InvalidConfig)?)
+            .header("Content-Type", "application/json")
+            .json(&request);
+        let response = send_with_retries(request, self.max_retries, self.retry_base_delay)
+            .await
+            .map_err(|e| ClipsterError::from_reqwest(e, self.timeout))?;
+
+        if !response.status().is_success() {
+            return Err(ClipsterError::from_response("Gemini", response).await);
+        }
+
+        let gemini_response: GeminiResponse =
+            response.json().await.map_err(|e| ClipsterError::from_reqwest(e, self.timeout))?;
+        let usage = gemini_response.usage_metadata.map(|usage| Usage {
+            input_tokens: usage.prompt_token_count,
+            output_tokens: usage.candidates_token_count,
+        });
+
+        let Some(candidate) = gemini_response.candidates.into_iter().next() else {
+            return Err(ClipsterError::InvalidResponse("No response from Gemini API".to_string()));
+        };
+
+        let text: String = candidate
+            .content
+            .map(|content| content.parts.into_iter().map(|part| part.text).collect())
+            .unwrap_or_default();
+
+        if text.is_empty() {
+            // Blocked prompts come back as a candidate without content, only a finish reason
+            let reason = candidate.finish_reason.unwrap_or_else(|| "unknown".to_string());
+            return Err(ClipsterError::InvalidResponse(format!("No response from Gemini API (finish reason {})", reason)));
+        }
+
+        Ok((text, usage))
+    }
+}
+
+/// Gemini only knows `user` and `model` turns, system prompts go in `systemInstruction`
+fn to_gemini_contents(messages: Vec<Message>) -> (Vec<GeminiContent>, Option<GeminiContent>) {
+    let mut system_parts = Vec::new();
+    let mut contents = Vec::new();
+
+    for message in messages {
+        let part = GeminiPart { text: message.content };
+        match message.role.as_str() {
+            SYSTEM => system_parts.push(part),
+            ASSISTANT => contents.push(GeminiContent { role: Some("model".to_string()), parts: vec![part] }),
+            _ => contents.push(GeminiContent { role: Some("user".to_string()), parts: vec![part] }),
+        }
+    }
+
+    let system_instruction = (!system_parts.is_empty()).then_some(GeminiContent { role: None, parts: system_parts });
+    (contents, system_instruction)
+}
+
+#[async_trait]
+impl AI for GeminiAI {
+    fn name(&self) -> String {
+        format!("gemini:{}", self.model)
+    }
+
+    async fn chat(&self, messages: Vec<Message>) -> Result<String, Box<dyn Error>> {
+        let (text, _) = self.chat_with_usage(messages).await?;
+        Ok(text)
+    }
+
+    async fn chat_with_usage(&self, messages: Vec<Message>) -> Result<(String, Option<Usage>), Box<dyn Error>> {
+        Ok(self.do_chat(messages).await?)
+    }
+
+    async fn warm_up(&self) -> Result<(), Box<dyn Error>> {
+        self.client.head(&self.base_url).send().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito;
+
+    #[tokio::test]
+    async fn test_gemini_builders() {
+        let client = GeminiAI::new("test_key".to_string(), "gemini-2.5-flash")
+            .with_max_tokens(512)
+            .with_temperature(3.0)
+            .with_model("gemini-2.5-pro");
+
+        assert_eq!(client.max_tokens, Some(512));
+        assert_eq!(client.temperature, 2.0);
+        assert_eq!(client.name(), "gemini:gemini-2.5-pro");
+    }
+
+    #[tokio::test]
+    async fn test_gemini_content_conversion() {
+        let messages = vec![
+            Message::system("You are a code assistant"),
+            Message::user("list files"),
+            Message { role: "assistant".to_string(), content: "ls".to_string() },
+            Message::user("with hidden ones"),
+        ];
+
+        let (contents, system_instruction) = to_gemini_contents(messages);
+
+        let roles: Vec<&str> = contents.iter().filter_map(|content| content.role.as_deref()).collect();
+        assert_eq!(roles, vec!["user", "model", "user"]);
+        assert_eq!(system_instruction.unwrap().parts[0].text, "You are a code assistant");
+    }
+
+    #[tokio::test]
+    async fn test_gemini_request_serialization() {
+        let (contents, system_instruction) = to_gemini_contents(vec![Message::system("Be brief"), Message::user("Hi")]);
+        let request = GeminiRequest {
+            contents,
+            system_instruction,
+            generation_config: GenerationConfig { temperature: 0.5, max_output_tokens: None },
+        };
+
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "contents": [{"role": "user", "parts": [{"text": "Hi"}]}],
+                "systemInstruction": {"parts": [{"text": "Be brief"}]},
+                "generationConfig": {"temperature": 0.5}
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_mock_api_success() {
+        let mut server = mockito::Server::new_async().await;
+
+        let mock = server.mock("POST", "/models/gemini-2.5-flash:generateContent")
+            .match_header("x-goog-api-key", "test_key")
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{"contents":[{"role":"user","parts":[{"text":"Hi"}]}]}"#.to_string()
+            ))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{
+                "candidates": [{"content": {"role": "model", "parts": [{"text": "Hello"}, {"text": "!"}]}, "finishReason": "STOP"}],
+                "usageMetadata": {"promptTokenCount": 4, "candidatesTokenCount": 2, "totalTokenCount": 6}
+            }"#)
+            .create_async()
+            .await;
+
+        let client = GeminiAI::new("test_key".to_string(), "gemini-2.5-flash").with_base_url(&server.url());
+        let result = client.chat_with_usage(vec![Message::user("Hi")]).await;
+
+        mock.assert_async().await;
+        assert_eq!(result.unwrap(), ("Hello!".to_string(), Some(Usage { input_tokens: 4, output_tokens: 2 })));
+    }
+
+    #[tokio::test]
+    async fn test_mock_blocked_prompt() {
+        let mut server = mockito::Server::new_async().await;
+
+        let mock = server.mock("POST", "/models/gemini-2.5-flash:generateContent")
+            .with_status(200)
+            .with_body(r#"{"candidates": [{"finishReason": "SAFETY"}]}"#)
+            .create_async()
+            .await;
+
+        let client = GeminiAI::new("test_key".to_string(), "gemini-2.5-flash").with_base_url(&server.url());
+        let err = client.generate("Hi").await.unwrap_err();
+
+        mock.assert_async().await;
+        assert!(err.to_string().contains("SAFETY"));
+    }
+
+    #[tokio::test]
+    async fn test_mock_api_error() {
+        let mut server = mockito::Server::new_async().await;
+
+        let mock = server.mock("POST", "/models/gemini-2.5-flash:generateContent")
+            .with_status(400)
+            .with_body(r#"{"error": {"code": 400, "message": "API key not valid", "status": "INVALID_ARGUMENT"}}"#)
+            .create_async()
+            .await;
+
+        let client = GeminiAI::new("bad_key".to_string(), "gemini-2.5-flash").with_base_url(&server.url());
+        let result = client.do_chat(vec![Message::user("Hi")]).await;
+
+        mock.assert_async().await;
+        assert!(matches!(result, Err(ClipsterError::ApiError { status: 400, .. })));
+    }
+}
@@ -0,0 +1,113 @@
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use std::time::Duration;
+
+/// Sent by every provider client unless it's given another user agent
+pub const DEFAULT_USER_AGENT: &str = concat!("clipster/", env!("CARGO_PKG_VERSION"));
+
+/// Limit on connecting and on each wait for more of the response, so a stalled network fails
+/// instead of hanging while long answers and streams can take as long as they keep arriving
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// HTTP client identifying itself as `user_agent`, falling back to reqwest's own
+/// when that isn't a valid header value. `timeout` bounds connecting and every read,
+/// not the whole response, see [`DEFAULT_TIMEOUT`].
+pub fn http_client(user_agent: &str, timeout: Duration) -> reqwest::Client {
+    reqwest::Client::builder()
+        .user_agent(user_agent)
+        .connect_timeout(timeout)
+        .read_timeout(timeout)
+        .build()
+        .unwrap_or_else(|e| {
+            eprintln!("Ignoring user agent \"{}\": {}", user_agent, e);
+            reqwest::Client::builder()
+                .connect_timeout(timeout)
+                .read_timeout(timeout)
+                .build()
+                .unwrap_or_default()
+        })
+}
+
+/// Extra headers sent with every request, e.g. tenant ids or routing hints for a gateway.
+/// They're applied after the client's own headers and replace them by name, except for the
+/// auth header (`Authorization`, `x-api-key`), which is only replaced after `allow_auth_override`.
+#[derive(Debug, Clone, Default)]
+pub struct CustomHeaders {
+    headers: Vec<(String, String)>,
+    allow_auth_override: bool,
+}
+
+impl CustomHeaders {
+    pub fn insert(&mut self, name: &str, value: &str) {
+        self.headers.retain(|(existing, _)| !existing.eq_ignore_ascii_case(name));
+        self.headers.push((name.to_string(), value.to_string()));
+    }
+
+    pub fn allow_auth_override(&mut self) {
+        self.allow_auth_override = true;
+    }
+
+    /// Headers to merge into a request whose credentials go in `auth_header`
+    pub fn to_header_map(&self, auth_header: &str) -> Result<HeaderMap, String> {
+        let mut map = HeaderMap::new();
+
+        for (name, value) in &self.headers {
+            if name.eq_ignore_ascii_case(auth_header) && !self.allow_auth_override {
+                continue;
+            }
+
+            let header_name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|e| format!("Invalid header name \"{}\": {}", name, e))?;
+            let header_value = HeaderValue::from_str(value)
+                .map_err(|e| format!("Invalid value for header \"{}\": {}", name, e))?;
+            map.insert(header_name, header_value);
+        }
+
+        Ok(map)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_custom_headers_skip_auth_by_default() {
+        let mut headers = CustomHeaders::default();
+        headers.insert("X-Tenant", "acme");
+        headers.insert("authorization", "Bearer other");
+
+        let map = headers.to_header_map("Authorization").unwrap();
+
+        assert_eq!(map.len(), 1);
+        assert_eq!(map["x-tenant"], "acme");
+    }
+
+    #[test]
+    fn test_custom_headers_auth_override() {
+        let mut headers = CustomHeaders::default();
+        headers.insert("x-api-key", "gateway-key");
+        headers.allow_auth_override();
+
+        let map = headers.to_header_map("x-api-key").unwrap();
+        assert_eq!(map["x-api-key"], "gateway-key");
+    }
+
+    #[test]
+    fn test_custom_headers_last_insert_wins() {
+        let mut headers = CustomHeaders::default();
+        headers.insert("X-Route", "a");
+        headers.insert("x-route", "b");
+
+        let map = headers.to_header_map("Authorization").unwrap();
+        assert_eq!(map.get_all("x-route").iter().count(), 1);
+        assert_eq!(map["x-route"], "b");
+    }
+
+    #[test]
+    fn test_custom_headers_invalid_name() {
+        let mut headers = CustomHeaders::default();
+        headers.insert("Bad Header", "value");
+
+        assert!(headers.to_header_map("Authorization").is_err());
+    }
+}
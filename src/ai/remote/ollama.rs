@@ -0,0 +1,384 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::time::Duration;
+
+use crate::ai::remote::headers::{http_client, DEFAULT_USER_AGENT};
+use crate::ai::remote::retry::{send_with_retries, RETRY_BASE_DELAY};
+use crate::ai::remote::traits::{Message, Usage, AI};
+use crate::error::ClipsterError;
+
+/// Where `ollama serve` listens unless `OLLAMA_HOST` says otherwise
+pub const DEFAULT_OLLAMA_URL: &str = "http://localhost:11434";
+const DEFAULT_OLLAMA_PORT: u16 = 11434;
+
+/// The first request after `ollama serve` starts also loads the model, which can take a while
+pub const DEFAULT_OLLAMA_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// A model served by a local (or self-hosted) Ollama, no API key involved
+#[derive(Debug, Clone)]
+pub struct Ollama {
+    client: reqwest::Client,
+    user_agent: String,
+    timeout: Duration,
+    /// Extra attempts for 429 and 5xx responses, see [`send_with_retries`]
+    max_retries: u32,
+    retry_base_delay: Duration,
+    base_url: String,
+    model: String,
+    /// Sent as `num_predict`, `None` leaves the cap to the model
+    max_tokens: Option<u32>,
+    temperature: f32,
+}
+
+#[derive(Debug, Serialize)]
+struct OllamaRequest {
+    model: String,
+    messages: Vec<Message>,
+    stream: bool,
+    options: OllamaOptions,
+}
+
+#[derive(Debug, Serialize)]
+struct OllamaOptions {
+    temperature: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    num_predict: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaResponse {
+    message: Option<OllamaMessage>,
+    #[serde(default)]
+    prompt_eval_count: Option<u64>,
+    #[serde(default)]
+    eval_count: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaMessage {
+    content: String,
+}
+
+/// `GET /api/tags`, the models pulled to the server
+#[derive(Debug, Deserialize)]
+struct TagsResponse {
+    models: Vec<TagInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TagInfo {
+    name: String,
+}
+
+impl Ollama {
+    pub fn new(base_url: &str, model: &str) -> Self {
+        Self {
+            client: http_client(DEFAULT_USER_AGENT, DEFAULT_OLLAMA_TIMEOUT),
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            timeout: DEFAULT_OLLAMA_TIMEOUT,
+            max_retries: 0,
+            retry_base_delay: RETRY_BASE_DELAY,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            model: model.to_string(),
+            max_tokens: None,
+            temperature: 0.7,
+        }
+    }
+
+    /// `llama3.2` on the default local server
+    pub fn default_local() -> Self {
+        Self::new(DEFAULT_OLLAMA_URL, "llama3.2")
+    }
+
+    /// `llama3.2` on the server in `OLLAMA_HOST`, or on the default local one when it isn't set
+    pub fn from_env() -> Result<Self, ClipsterError> {
+        let url = std::env::var("OLLAMA_HOST").map_or_else(|_| DEFAULT_OLLAMA_URL.to_string(), |host| host_url(&host));
+        reqwest::Url::parse(&url)
+            .map_err(|e| ClipsterError::InvalidConfig(format!("OLLAMA_HOST ({}) isn't a valid address: {}", url, e)))?;
+
+        Ok(Self::new(&url, "llama3.2"))
+    }
+
+    pub fn with_max_tokens(mut self, max_tokens: impl Into<Option<u32>>) -> Self {
+        self.max_tokens = max_tokens.into();
+        self
+    }
+
+    pub fn with_temperature(mut self, temperature: f32) -> Self {
+        self.temperature = temperature.max(0.0);
+        self
+    }
+
+    pub fn with_model(mut self, model: &str) -> Self {
+        self.model = model.to_string();
+        self
+    }
+
+    pub fn with_user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = user_agent.to_string();
+        self.client = http_client(&self.user_agent, self.timeout);
+        self
+    }
+
+    /// Retries rate limits and server errors with exponential backoff, or after `Retry-After`
+    pub fn with_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Bounds connecting and each read of the response by `timeout`, 2 minutes by default to leave
+    /// room for loading the model, see [`DEFAULT_OLLAMA_TIMEOUT`]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self.client = http_client(&self.user_agent, self.timeout);
+        self
+    }
+
+    async fn do_chat(&self, messages: Vec<Message>) -> Result<(String, Option<Usage>), ClipsterError> {
+        let request = OllamaRequest {
+            model: self.model.clone(),
+            messages,
+            stream: false,
+            options: OllamaOptions {
+                temperature: self.temperature,
+                num_predict: self.max_tokens,
+            },
+        };
+
+        let request = self
+            .client
+            .post(format!("{}/api/chat", self.base_url))
+            .header("Content-Type", "application/json")
+            .json(&request);
+        let response = send_with_retries(request, self.max_retries, self.retry_base_delay)
+            .await
+            .map_err(|e| ClipsterError::from_reqwest(e, self.timeout))?;
+
+        if !response.status().is_success() {
+            return Err(ClipsterError::from_response("Ollama", response).await);
+        }
+
+        let ollama_response: OllamaResponse =
+            response.json().await.map_err(|e| ClipsterError::from_reqwest(e, self.timeout))?;
+
+        let usage = match (ollama_response.prompt_eval_count, ollama_response.eval_count) {
+            (Some(input_tokens), Some(output_tokens)) => Some(Usage { input_tokens, output_tokens }),
+            _ => None,
+        };
+
+        ollama_response
+            .message
+            .map(|message| message.content)
+            .filter(|content| !content.is_empty())
+            .map(|content| (content, usage))
+            .ok_or_else(|| ClipsterError::InvalidResponse("No response from Ollama".to_string()))
+    }
+
+    async fn do_list_models(&self) -> Result<Vec<String>, ClipsterError> {
+        let request = self.client.get(format!("{}/api/tags", self.base_url));
+        let response = send_with_retries(request, self.max_retries, self.retry_base_delay)
+            .await
+            .map_err(|e| ClipsterError::from_reqwest(e, self.timeout))?;
+
+        if !response.status().is_success() {
+            return Err(ClipsterError::from_response("Ollama", response).await);
+        }
+
+        let tags: TagsResponse = response.json().await.map_err(|e| ClipsterError::from_reqwest(e, self.timeout))?;
+        let mut names: Vec<String> = tags.models.into_iter().map(|model| model.name).collect();
+        names.sort();
+
+        Ok(names)
+    }
+}
+
+/// `OLLAMA_HOST` as a URL. It's often just `host` or `host:port` (`0.0.0.0`, `gpu-box:8080`),
+/// which gets `http://` and Ollama's port. Full URLs are used as they are.
+fn host_url(host: &str) -> String {
+    let host = host.trim().trim_end_matches('/');
+    if host.is_empty() {
+        return DEFAULT_OLLAMA_URL.to_string();
+    }
+    if host.contains("://") {
+        return host.to_string();
+    }
+
+    let has_port = host.rsplit_once(':').is_some_and(|(_, port)| port.parse::<u16>().is_ok());
+    if has_port {
+        format!("http://{}", host)
+    } else {
+        format!("http://{}:{}", host, DEFAULT_OLLAMA_PORT)
+    }
+}
+
+#[async_trait]
+impl AI for Ollama {
+    fn name(&self) -> String {
+        format!("ollama:{}", self.model)
+    }
+
+    async fn chat(&self, messages: Vec<Message>) -> Result<String, Box<dyn Error>> {
+        let (text, _) = self.chat_with_usage(messages).await?;
+        Ok(text)
+    }
+
+    async fn chat_with_usage(&self, messages: Vec<Message>) -> Result<(String, Option<Usage>), Box<dyn Error>> {
+        Ok(self.do_chat(messages).await?)
+    }
+
+    async fn warm_up(&self) -> Result<(), Box<dyn Error>> {
+        self.client.get(&self.base_url).send().await?;
+        Ok(())
+    }
+
+    /// Models pulled to the server, e.g. `llama3.2:latest`
+    async fn list_models(&self) -> Result<Vec<String>, Box<dyn Error>> {
+        Ok(self.do_list_models().await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito;
+
+    #[tokio::test]
+    async fn test_ollama_builders() {
+        let client = Ollama::default_local()
+            .with_max_tokens(256)
+            .with_temperature(-1.0)
+            .with_model("qwen2.5");
+
+        assert_eq!(client.base_url, DEFAULT_OLLAMA_URL);
+        assert_eq!(client.max_tokens, Some(256));
+        assert_eq!(client.temperature, 0.0);
+        assert_eq!(client.name(), "ollama:qwen2.5");
+    }
+
+    #[test]
+    fn test_host_url() {
+        assert_eq!(host_url("http://localhost:11434/"), "http://localhost:11434");
+        assert_eq!(host_url("0.0.0.0"), "http://0.0.0.0:11434");
+        assert_eq!(host_url("gpu-box:8080"), "http://gpu-box:8080");
+        assert_eq!(host_url("https://ollama.example.com"), "https://ollama.example.com");
+        assert_eq!(host_url(""), DEFAULT_OLLAMA_URL);
+    }
+
+    #[tokio::test]
+    async fn test_mock_api_success() {
+        let mut server = mockito::Server::new_async().await;
+
+        let mock = server.mock("POST", "/api/chat")
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{"model":"llama3.2","stream":false,"messages":[{"role":"system","content":"Be brief"},{"role":"user","content":"Hi"}]}"#.to_string()
+            ))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{
+                "model": "llama3.2",
+                "message": {"role": "assistant", "content": "Hello!"},
+                "done": true,
+                "prompt_eval_count": 12,
+                "eval_count": 3
+            }"#)
+            .create_async()
+            .await;
+
+        let client = Ollama::new(&server.url(), "llama3.2");
+
+        let messages = vec![Message::system("Be brief"), Message::user("Hi")];
+        let result = client.chat_with_usage(messages).await;
+
+        mock.assert_async().await;
+        assert_eq!(result.unwrap(), ("Hello!".to_string(), Some(Usage { input_tokens: 12, output_tokens: 3 })));
+    }
+
+    #[tokio::test]
+    async fn test_mock_max_tokens_sent_as_num_predict() {
+        let mut server = mockito::Server::new_async().await;
+
+        let mock = server.mock("POST", "/api/chat")
+            .match_body(mockito::Matcher::PartialJsonString(r#"{"options":{"num_predict":64}}"#.to_string()))
+            .with_status(200)
+            .with_body(r#"{"message": {"role": "assistant", "content": "ls"}, "done": true}"#)
+            .create_async()
+            .await;
+
+        let client = Ollama::new(&server.url(), "llama3.2").with_max_tokens(64);
+
+        assert_eq!(client.generate("Hi").await.unwrap(), "ls");
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_mock_api_error() {
+        let mut server = mockito::Server::new_async().await;
+
+        let mock = server.mock("POST", "/api/chat")
+            .with_status(404)
+            .with_body(r#"{"error":"model \"llama3.2\" not found, try pulling it first"}"#)
+            .create_async()
+            .await;
+
+        let client = Ollama::new(&server.url(), "llama3.2");
+        let result = client.do_chat(vec![Message::user("Hi")]).await;
+
+        mock.assert_async().await;
+        match result {
+            Err(ClipsterError::ApiError { provider, status, body }) => {
+                assert_eq!(provider, "Ollama");
+                assert_eq!(status, 404);
+                assert!(body.contains("try pulling it first"));
+            }
+            other => panic!("expected an API error, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mock_list_models() {
+        let mut server = mockito::Server::new_async().await;
+
+        let mock = server.mock("GET", "/api/tags")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"models":[
+                {"name":"qwen2.5:latest","model":"qwen2.5:latest","size":4683087332},
+                {"name":"llama3.2:latest","model":"llama3.2:latest","size":2019393189}
+            ]}"#)
+            .create_async()
+            .await;
+
+        let client = Ollama::new(&server.url(), "llama3.2");
+
+        assert_eq!(client.list_models().await.unwrap(), vec!["llama3.2:latest", "qwen2.5:latest"]);
+        mock.assert_async().await;
+    }
+
+    #[test]
+    fn test_from_env_defaults_to_local_server() {
+        // OLLAMA_HOST is never set by the tests, setting it here would race with other tests
+        if std::env::var_os("OLLAMA_HOST").is_none() {
+            let client = Ollama::from_env().unwrap();
+            assert_eq!(client.base_url, DEFAULT_OLLAMA_URL);
+            assert_eq!(client.timeout, DEFAULT_OLLAMA_TIMEOUT);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mock_empty_response() {
+        let mut server = mockito::Server::new_async().await;
+
+        let mock = server.mock("POST", "/api/chat")
+            .with_status(200)
+            .with_body(r#"{"message": {"role": "assistant", "content": ""}, "done": true}"#)
+            .create_async()
+            .await;
+
+        let client = Ollama::new(&server.url(), "llama3.2");
+        let err = client.generate("Hi").await.unwrap_err();
+
+        mock.assert_async().await;
+        assert_eq!(err.to_string(), "No response from Ollama");
+    }
+}
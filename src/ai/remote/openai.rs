@@ -1,18 +1,51 @@
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::error::Error;
+use std::time::Duration;
 
-use crate::ai::remote::traits::{AI, Message};
+use crate::ai::remote::headers::{http_client, CustomHeaders, DEFAULT_TIMEOUT, DEFAULT_USER_AGENT};
+use crate::ai::remote::retry::{send_with_retries, RETRY_BASE_DELAY};
+use crate::ai::remote::sse::event_stream;
+use crate::ai::remote::traits::{whole_response_stream, Message, Roles, StreamChunk, TextStream, Usage, AI, SYSTEM};
+use crate::error::{api_key_from_env, ClipsterError};
+
+/// Most stop sequences OpenAI accepts in one request
+pub const MAX_STOP_SEQUENCES: usize = 4;
 
 #[derive(Debug, Clone)]
 pub struct OpenAI {
     client: reqwest::Client,
+    user_agent: String,
+    timeout: Duration,
+    /// Extra attempts for 429 and 5xx responses, see [`send_with_retries`]
+    max_retries: u32,
+    retry_base_delay: Duration,
     api_key: String,
+    /// Provider prefix in `name()`, the same client serves every OpenAI-compatible API
+    provider: String,
     base_url: String,
     model: String,
-    max_tokens: u32,
+    /// `None` leaves the cap to the provider
+    max_tokens: Option<u32>,
     temperature: f32,
+    /// Sampling controls, `None` leaves them out of the request so the API's defaults apply
+    top_p: Option<f32>,
+    presence_penalty: Option<f32>,
+    frequency_penalty: Option<f32>,
+    /// Asks for repeatable sampling, see [`OpenAI::with_seed`]
+    seed: Option<i64>,
+    /// Ask for a JSON object, see [`OpenAI::with_json_mode`]
+    json_mode: bool,
+    /// The response ends before the first of these, empty sends none
+    stop: Vec<String>,
     uses_completion_tokens: bool,
+    /// Use `/responses` instead of `/chat/completions`, only OpenAI itself implements it
+    responses_api: bool,
+    headers: CustomHeaders,
+    roles: Roles,
+    /// `api-version` of an Azure OpenAI resource, which switches to deployment URLs and `api-key` auth
+    azure_api_version: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -24,11 +57,62 @@ struct ChatRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     max_completion_tokens: Option<u32>,
     temperature: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    presence_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    frequency_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_format: Option<ResponseFormat>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    stop: Vec<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream_options: Option<StreamOptions>,
+}
+
+/// `{"include_usage": true}`, so a stream ends with a chunk holding the token counts
+#[derive(Debug, Clone, Serialize)]
+struct StreamOptions {
+    include_usage: bool,
+}
+
+impl StreamOptions {
+    fn include_usage() -> Self {
+        Self { include_usage: true }
+    }
+}
+
+/// `{"type": "json_object"}`, the only format clipster asks for
+#[derive(Debug, Clone, Serialize)]
+struct ResponseFormat {
+    #[serde(rename = "type")]
+    kind: &'static str,
+}
+
+impl ResponseFormat {
+    fn json_object() -> Self {
+        Self { kind: "json_object" }
+    }
 }
 
 #[derive(Debug, Deserialize)]
 struct ChatResponse {
     choices: Vec<Choice>,
+    #[serde(default)]
+    usage: Option<ChatUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatUsage {
+    #[serde(default)]
+    prompt_tokens: u64,
+    #[serde(default)]
+    completion_tokens: u64,
 }
 
 #[derive(Debug, Deserialize)]
@@ -36,31 +120,160 @@ struct Choice {
     message: Message,
 }
 
+/// One `data:` event of a streamed chat completion
+#[derive(Debug, Deserialize)]
+struct ChatChunk {
+    #[serde(default)]
+    choices: Vec<ChunkChoice>,
+    /// Only in the last chunk, and only with `stream_options.include_usage`
+    #[serde(default)]
+    usage: Option<ChatUsage>,
+    #[serde(default)]
+    error: Option<ChunkError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChunkChoice {
+    delta: ChunkDelta,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChunkDelta {
+    #[serde(default)]
+    content: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChunkError {
+    message: String,
+}
+
+/// Request body for the Responses API, system messages go in `instructions`
+#[derive(Debug, Serialize)]
+struct ResponsesRequest {
+    model: String,
+    input: Vec<Message>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    instructions: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_output_tokens: Option<u32>,
+    temperature: f32,
+    /// The Responses API has no penalties, only nucleus sampling
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    text: Option<ResponsesText>,
+}
+
+/// The Responses API's place for `response_format`
+#[derive(Debug, Serialize)]
+struct ResponsesText {
+    format: ResponseFormat,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResponsesResponse {
+    output: Vec<OutputItem>,
+    #[serde(default)]
+    usage: Option<Usage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OutputItem {
+    #[serde(default)]
+    content: Vec<OutputContent>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OutputContent {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    text: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModelsResponse {
+    data: Vec<ModelInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModelInfo {
+    id: String,
+}
+
 impl OpenAI {
     pub fn new(api_key: String, base_url: &str, model: &str, uses_completion_tokens: bool) -> Self {
         Self {
-            client: reqwest::Client::new(),
+            client: http_client(DEFAULT_USER_AGENT, DEFAULT_TIMEOUT),
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            timeout: DEFAULT_TIMEOUT,
+            max_retries: 0,
+            retry_base_delay: RETRY_BASE_DELAY,
             api_key,
+            provider: "openai".to_string(),
             base_url: base_url.trim_end_matches('/').to_string(),
             model: model.to_string(),
-            max_tokens: 1000,
+            max_tokens: Some(1000),
             temperature: 0.7,
-            uses_completion_tokens
+            top_p: None,
+            presence_penalty: None,
+            frequency_penalty: None,
+            seed: None,
+            json_mode: false,
+            stop: Vec::new(),
+            uses_completion_tokens,
+            responses_api: false,
+            headers: CustomHeaders::default(),
+            roles: Roles::default(),
+            azure_api_version: None,
         }
     }
 
-    pub fn openai_5() -> Result<Self, Box<dyn Error>> {
-        let api_key = std::env::var("OPENAI_API_KEY")?;
+    pub fn openai_5() -> Result<Self, ClipsterError> {
+        let api_key = api_key_from_env("OPENAI_API_KEY")?;
         Ok(Self::new(api_key, "https://api.openai.com/v1", "gpt-5.1", true))
     }
 
-    pub fn grok() -> Result<Self, Box<dyn Error>> {
-        let api_key = std::env::var("XAI_API_KEY")?;
-        Ok(Self::new(api_key, "https://api.x.ai/v1", "grok-4-latest", false))
+    pub fn grok() -> Result<Self, ClipsterError> {
+        let api_key = api_key_from_env("XAI_API_KEY")?;
+        Ok(Self::new(api_key, "https://api.x.ai/v1", "grok-4-latest", false).with_provider("xai"))
+    }
+
+    pub fn mistral() -> Result<Self, ClipsterError> {
+        let api_key = api_key_from_env("MISTRAL_API_KEY")?;
+        Ok(Self::new(api_key, "https://api.mistral.ai/v1", "mistral-large-latest", false).with_provider("mistral"))
+    }
+
+    /// A deployment on an Azure OpenAI resource, e.g. `https://acme.openai.azure.com`, keyed by `AZURE_OPENAI_API_KEY`.
+    /// The deployment stands in for the model, so `with_model` picks another deployment.
+    /// API versions before `2024-09-01` get `max_tokens`, they reject `max_completion_tokens`.
+    pub fn azure(endpoint: &str, deployment: &str, api_version: &str) -> Result<Self, ClipsterError> {
+        let api_key = api_key_from_env("AZURE_OPENAI_API_KEY")?;
+        Ok(Self::azure_deployment(api_key, endpoint, deployment, api_version))
+    }
+
+    fn azure_deployment(api_key: String, endpoint: &str, deployment: &str, api_version: &str) -> Self {
+        Self::new(api_key, endpoint, deployment, is_recent_azure_api(api_version))
+            .with_provider("azure")
+            .with_azure_api_version(api_version)
     }
 
-    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
-        self.max_tokens = max_tokens;
+    /// Talk to Azure OpenAI: `{base_url}/openai/deployments/{model}/chat/completions?api-version=…`
+    /// with an `api-key` header instead of a bearer token. Azure has no Responses API or model listing here.
+    pub fn with_azure_api_version(mut self, api_version: &str) -> Self {
+        self.azure_api_version = Some(api_version.to_string());
+        self
+    }
+
+    pub fn with_provider(mut self, provider: &str) -> Self {
+        self.provider = provider.to_string();
+        self
+    }
+
+    /// `None` sends no cap so the provider's default applies
+    pub fn with_max_tokens(mut self, max_tokens: impl Into<Option<u32>>) -> Self {
+        self.max_tokens = max_tokens.into();
         self
     }
 
@@ -69,52 +282,255 @@ impl OpenAI {
         self
     }
 
-    #[allow(dead_code)]
+    /// Nucleus sampling, between 0 and 1. OpenAI recommends changing this or the temperature, not both.
+    pub fn with_top_p(mut self, top_p: impl Into<Option<f32>>) -> Self {
+        self.top_p = top_p.into();
+        self
+    }
+
+    /// Between -2 and 2, positive values push towards tokens that haven't appeared yet. Chat completions only.
+    pub fn with_presence_penalty(mut self, presence_penalty: impl Into<Option<f32>>) -> Self {
+        self.presence_penalty = presence_penalty.into();
+        self
+    }
+
+    /// Between -2 and 2, positive values penalize tokens by how often they've appeared. Chat completions only.
+    pub fn with_frequency_penalty(mut self, frequency_penalty: impl Into<Option<f32>>) -> Self {
+        self.frequency_penalty = frequency_penalty.into();
+        self
+    }
+
+    /// Same seed and request, mostly the same response, for regression-testing prompts.
+    /// OpenAI only promises best effort, and only chat completions take a seed.
+    pub fn with_seed(mut self, seed: i64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Forces the response to be a JSON object. OpenAI rejects requests whose messages
+    /// don't mention JSON, so the system prompt has to ask for it.
+    pub fn with_json_mode(mut self, enabled: bool) -> Self {
+        self.json_mode = enabled;
+        self
+    }
+
+    /// Ends the response before any of `sequences`, which aren't included. OpenAI takes up to
+    /// [`MAX_STOP_SEQUENCES`], and the Responses API has no stop sequences.
+    pub fn with_stop(mut self, sequences: Vec<String>) -> Self {
+        self.stop = sequences;
+        self
+    }
+
     pub fn with_model(mut self, model: &str) -> Self {
         self.model = model.to_string();
         self
     }
 
+    /// Target the Responses API (`/responses`) instead of chat completions
+    pub fn with_responses_api(mut self, responses_api: bool) -> Self {
+        self.responses_api = responses_api;
+        self
+    }
+
+    pub fn with_user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = user_agent.to_string();
+        self.client = http_client(&self.user_agent, self.timeout);
+        self
+    }
+
+    /// Retries rate limits and server errors with exponential backoff, or after `Retry-After`
+    pub fn with_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Bounds connecting and each read of the response by `timeout`, 30 seconds by default
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self.client = http_client(&self.user_agent, self.timeout);
+        self
+    }
+
+    /// Role names for servers that don't use `system`/`user`/`assistant`
+    pub fn with_roles(mut self, roles: Roles) -> Self {
+        self.roles = roles;
+        self
+    }
+
+    fn rename_roles(&self, messages: Vec<Message>) -> Vec<Message> {
+        messages
+            .into_iter()
+            .map(|message| Message { role: self.roles.rename(&message.role), ..message })
+            .collect()
+    }
+
+    /// Sends an extra header with every request, see [`CustomHeaders`] for precedence
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    pub fn with_headers(mut self, headers: &HashMap<String, String>) -> Self {
+        for (name, value) in headers {
+            self.headers.insert(name, value);
+        }
+        self
+    }
+
+    /// Lets a custom header replace the API key header, e.g. for a gateway's own token
+    pub fn with_auth_override(mut self) -> Self {
+        self.headers.allow_auth_override();
+        self
+    }
+
+    /// Only OpenAI itself has the Responses API, Azure requests always use chat completions
+    fn uses_responses_api(&self) -> bool {
+        self.responses_api && self.azure_api_version.is_none()
+    }
+
     #[allow(dead_code)]
     pub async fn generate(&self, prompt: &str) -> Result<String, Box<dyn Error>> {
-        let messages = vec![Message {
-            role: "user".to_string(),
-            content: prompt.to_string(),
-        }];
-        self.chat(messages).await
+        self.chat(vec![Message::user(prompt)]).await
     }
 
-    async fn do_chat(&self, messages: Vec<Message>) -> Result<String, Box<dyn Error>> {
+    async fn send_chat(&self, messages: Vec<Message>, stream: bool) -> Result<reqwest::Response, ClipsterError> {
         let request = ChatRequest {
             model: self.model.clone(),
-            messages,
-            max_tokens: if self.uses_completion_tokens { None } else { Some(self.max_tokens) },
-            max_completion_tokens: if self.uses_completion_tokens { Some(self.max_tokens) } else { None },
+            messages: self.rename_roles(messages),
+            max_tokens: self.max_tokens.filter(|_| !self.uses_completion_tokens),
+            max_completion_tokens: self.max_tokens.filter(|_| self.uses_completion_tokens),
             temperature: self.temperature,
+            top_p: self.top_p,
+            presence_penalty: self.presence_penalty,
+            frequency_penalty: self.frequency_penalty,
+            seed: self.seed,
+            response_format: self.json_mode.then(ResponseFormat::json_object),
+            stop: self.stop.clone(),
+            stream,
+            stream_options: stream
+                .then(StreamOptions::include_usage)
+                .filter(|_| self.azure_api_version.as_deref().is_none_or(is_recent_azure_api)),
         };
 
-        let response = self
-            .client
-            .post(format!("{}/chat/completions", self.base_url))
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .header("Content-Type", "application/json")
-            .json(&request)
-            .send()
-            .await?;
+        let builder = match &self.azure_api_version {
+            Some(api_version) => self
+                .client
+                .post(format!("{}/openai/deployments/{}/chat/completions", self.base_url, self.model))
+                .query(&[("api-version", api_version)])
+                .header("api-key", &self.api_key)
+                .headers(self.headers.to_header_map("api-key").map_err(ClipsterError::InvalidConfig)?),
+            None => self
+                .client
+                .post(format!("{}/chat/completions", self.base_url))
+                .header("Authorization", format!("Bearer {}", self.api_key))
+                .headers(self.headers.to_header_map("Authorization").map_err(ClipsterError::InvalidConfig)?),
+        };
+        let request = builder.header("Content-Type", "application/json").json(&request);
+        let response = send_with_retries(request, self.max_retries, self.retry_base_delay)
+            .await
+            .map_err(|e| ClipsterError::from_reqwest(e, self.timeout))?;
 
         if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response.text().await?;
-            return Err(format!("API error {}: {}", status, error_text).into());
+            return Err(ClipsterError::from_response(&self.provider, response).await);
         }
 
-        let chat_response: ChatResponse = response.json().await?;
+        Ok(response)
+    }
+
+    async fn do_chat(&self, messages: Vec<Message>) -> Result<(String, Option<Usage>), ClipsterError> {
+        let response = self.send_chat(messages, false).await?;
+        let chat_response: ChatResponse = response.json().await.map_err(|e| ClipsterError::from_reqwest(e, self.timeout))?;
+        let usage = chat_response.usage.map(|usage| Usage {
+            input_tokens: usage.prompt_tokens,
+            output_tokens: usage.completion_tokens,
+        });
 
         chat_response
             .choices
             .first()
-            .map(|choice| choice.message.content.clone())
-            .ok_or_else(|| "No response from API".into())
+            .map(|choice| (choice.message.content.clone(), usage))
+            .ok_or_else(|| ClipsterError::InvalidResponse("No response from API".to_string()))
+    }
+
+    async fn do_responses(&self, messages: Vec<Message>) -> Result<(String, Option<Usage>), ClipsterError> {
+        let (system, input): (Vec<Message>, Vec<Message>) =
+            messages.into_iter().partition(|message| message.role == SYSTEM);
+        let instructions = (!system.is_empty()).then(|| {
+            system.into_iter().map(|message| message.content).collect::<Vec<_>>().join("\n\n")
+        });
+
+        let request = ResponsesRequest {
+            model: self.model.clone(),
+            input: self.rename_roles(input),
+            instructions,
+            max_output_tokens: self.max_tokens,
+            temperature: self.temperature,
+            top_p: self.top_p,
+            text: self.json_mode.then(|| ResponsesText { format: ResponseFormat::json_object() }),
+        };
+
+        let request = self
+            .client
+            .post(format!("{}/responses", self.base_url))
+            .header("Authorization", format!("Bearer {}", self.api_key))
+            .headers(self.headers.to_header_map("Authorization").map_err(ClipsterError::InvalidConfig)?)
+            .header("Content-Type", "application/json")
+            .json(&request);
+        let response = send_with_retries(request, self.max_retries, self.retry_base_delay)
+            .await
+            .map_err(|e| ClipsterError::from_reqwest(e, self.timeout))?;
+
+        if !response.status().is_success() {
+            return Err(ClipsterError::from_response(&self.provider, response).await);
+        }
+
+        let responses_response: ResponsesResponse = response.json().await.map_err(|e| ClipsterError::from_reqwest(e, self.timeout))?;
+
+        let text: String = responses_response
+            .output
+            .iter()
+            .flat_map(|item| &item.content)
+            .filter(|content| content.kind == "output_text")
+            .map(|content| content.text.as_str())
+            .collect();
+
+        if text.is_empty() {
+            return Err(ClipsterError::InvalidResponse("No response from API".to_string()));
+        }
+
+        Ok((text, responses_response.usage))
+    }
+
+    /// Chat completion with `stream: true`, yielding each content delta and then the usage
+    async fn do_chat_stream(&self, messages: Vec<Message>) -> Result<TextStream, ClipsterError> {
+        let response = self.send_chat(messages, true).await?;
+        Ok(event_stream(response, self.timeout, parse_chunk))
+    }
+
+    /// Azure deployments aren't listed, its models endpoint names models rather than deployments
+    async fn do_list_models(&self) -> Result<Vec<String>, ClipsterError> {
+        if self.azure_api_version.is_some() {
+            return Ok(Vec::new());
+        }
+
+        let request = self
+            .client
+            .get(format!("{}/models", self.base_url))
+            .header("Authorization", format!("Bearer {}", self.api_key))
+            .headers(self.headers.to_header_map("Authorization").map_err(ClipsterError::InvalidConfig)?);
+        let response = send_with_retries(request, self.max_retries, self.retry_base_delay)
+            .await
+            .map_err(|e| ClipsterError::from_reqwest(e, self.timeout))?;
+
+        if !response.status().is_success() {
+            return Err(ClipsterError::from_response(&self.provider, response).await);
+        }
+
+        let models: ModelsResponse = response.json().await.map_err(|e| ClipsterError::from_reqwest(e, self.timeout))?;
+        let mut ids: Vec<String> = models.data.into_iter().map(|model| model.id).collect();
+        ids.sort();
+
+        Ok(ids)
     }
 
     #[allow(dead_code)]
@@ -123,30 +539,99 @@ impl OpenAI {
         system_prompt: &str,
         user_prompt: &str,
     ) -> Result<String, Box<dyn Error>> {
-        let messages = vec![
-            Message {
-                role: "system".to_string(),
-                content: system_prompt.to_string(),
-            },
-            Message {
-                role: "user".to_string(),
-                content: user_prompt.to_string(),
-            },
-        ];
-        self.chat(messages).await
+        self.chat(vec![Message::system(system_prompt), Message::user(user_prompt)]).await
+    }
+}
+
+/// Whether an Azure `api-version` (`2024-10-21`, `2024-08-01-preview`, …) is from `2024-09-01` on,
+/// when `max_completion_tokens` and `stream_options` arrived. Undated ones like `preview` are current.
+fn is_recent_azure_api(api_version: &str) -> bool {
+    let date = api_version.get(..10).unwrap_or(api_version);
+    let is_date = date.len() == 10
+        && date
+            .bytes()
+            .enumerate()
+            .all(|(i, b)| if i == 4 || i == 7 { b == b'-' } else { b.is_ascii_digit() });
+
+    !is_date || date >= "2024-09-01"
+}
+
+/// Content and usage of a streamed chunk, skipping empty deltas and the final `[DONE]`
+fn parse_chunk(data: &str) -> Vec<Result<StreamChunk, String>> {
+    if data == "[DONE]" {
+        return Vec::new();
+    }
+
+    match serde_json::from_str::<ChatChunk>(data) {
+        Ok(ChatChunk { error: Some(error), .. }) => vec![Err(format!("API stream error: {}", error.message))],
+        Ok(chunk) => {
+            let text = chunk
+                .choices
+                .into_iter()
+                .next()
+                .and_then(|choice| choice.delta.content)
+                .filter(|content| !content.is_empty())
+                .map(StreamChunk::Text);
+            let usage = chunk.usage.map(|usage| {
+                StreamChunk::Usage(Usage {
+                    input_tokens: usage.prompt_tokens,
+                    output_tokens: usage.completion_tokens,
+                })
+            });
+
+            text.into_iter().chain(usage).map(Ok).collect()
+        }
+        Err(e) => vec![Err(format!("Malformed stream chunk: {}", e))],
     }
 }
 
 #[async_trait]
 impl AI for OpenAI {
+    fn name(&self) -> String {
+        format!("{}:{}", self.provider, self.model)
+    }
+
+    fn returns_json(&self) -> bool {
+        self.json_mode
+    }
+
     async fn chat(&self, messages: Vec<Message>) -> Result<String, Box<dyn Error>> {
-        self.do_chat(messages).await
+        let (text, _) = self.chat_with_usage(messages).await?;
+        Ok(text)
+    }
+
+    async fn chat_with_usage(&self, messages: Vec<Message>) -> Result<(String, Option<Usage>), Box<dyn Error>> {
+        if self.uses_responses_api() {
+            Ok(self.do_responses(messages).await?)
+        } else {
+            Ok(self.do_chat(messages).await?)
+        }
+    }
+
+    /// The Responses API isn't streamed, it arrives as a single chunk
+    async fn chat_stream(&self, messages: Vec<Message>) -> Result<TextStream, Box<dyn Error>> {
+        if self.uses_responses_api() {
+            let (text, usage) = self.do_responses(messages).await?;
+            return Ok(whole_response_stream(text, usage));
+        }
+
+        Ok(self.do_chat_stream(messages).await?)
+    }
+
+    async fn warm_up(&self) -> Result<(), Box<dyn Error>> {
+        self.client.head(&self.base_url).send().await?;
+        Ok(())
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>, Box<dyn Error>> {
+        Ok(self.do_list_models().await?)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use futures_util::StreamExt;
     use mockito;
 
     #[tokio::test]
@@ -154,7 +639,8 @@ mod tests {
         let client = OpenAI::new("test_key".to_string(), "https://api.test.com", "gpt-4", true)
             .with_max_tokens(2000);
 
-        assert_eq!(client.max_tokens, 2000);
+        assert_eq!(client.max_tokens, Some(2000));
+        assert_eq!(client.with_max_tokens(None).max_tokens, None);
     }
 
     #[tokio::test]
@@ -215,11 +701,273 @@ mod tests {
             max_tokens: Some(100),
             max_completion_tokens: None,
             temperature: 0.7,
+            top_p: None,
+            presence_penalty: None,
+            frequency_penalty: None,
+            seed: None,
+            response_format: None,
+            stop: Vec::new(),
+            stream: false,
+            stream_options: None,
         };
 
         let json = serde_json::to_string(&request).unwrap();
         assert!(json.contains("\"model\":\"gpt-4\""));
         assert!(json.contains("\"temperature\":0.7"));
+        assert!(!json.contains("top_p"));
+        assert!(!json.contains("presence_penalty"));
+        assert!(!json.contains("frequency_penalty"));
+        assert!(!json.contains("seed"));
+        assert!(!json.contains("response_format"));
+        assert!(!json.contains("stop"));
+    }
+
+    #[tokio::test]
+    async fn test_mock_seed_sent_when_set() {
+        let mut server = mockito::Server::new_async().await;
+
+        let mock = server.mock("POST", "/chat/completions")
+            .match_body(mockito::Matcher::PartialJsonString(r#"{"seed":42}"#.to_string()))
+            .with_status(200)
+            .with_body(r#"{"choices":[{"message":{"role":"assistant","content":"ls"}}]}"#)
+            .create_async()
+            .await;
+
+        let client = OpenAI::new("test_key".to_string(), &server.url(), "gpt-4", false).with_seed(42);
+
+        assert_eq!(client.generate("Hi").await.unwrap(), "ls");
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_mock_azure_deployment_url_and_key() {
+        let mut server = mockito::Server::new_async().await;
+
+        let mock = server.mock("POST", "/openai/deployments/team-gpt4o/chat/completions")
+            .match_query(mockito::Matcher::UrlEncoded("api-version".to_string(), "2024-10-21".to_string()))
+            .match_header("api-key", "test_key")
+            .match_header("authorization", mockito::Matcher::Missing)
+            .match_body(mockito::Matcher::PartialJsonString(r#"{"max_completion_tokens":1000}"#.to_string()))
+            .with_status(200)
+            .with_body(r#"{"choices":[{"message":{"role":"assistant","content":"ls -la"}}]}"#)
+            .create_async()
+            .await;
+
+        let client = OpenAI::new("test_key".to_string(), &server.url(), "team-gpt4o", true)
+            .with_provider("azure")
+            .with_azure_api_version("2024-10-21")
+            .with_responses_api(true);
+
+        assert_eq!(client.name(), "azure:team-gpt4o");
+        assert_eq!(client.generate("list files").await.unwrap(), "ls -la");
+        assert!(client.list_models().await.unwrap().is_empty());
+        mock.assert_async().await;
+    }
+
+    #[test]
+    fn test_is_recent_azure_api() {
+        assert!(is_recent_azure_api("2024-10-21"));
+        assert!(is_recent_azure_api("2024-09-01-preview"));
+        assert!(is_recent_azure_api("2025-04-01-preview"));
+        assert!(is_recent_azure_api("preview"));
+        assert!(!is_recent_azure_api("2024-08-01-preview"));
+        assert!(!is_recent_azure_api("2024-06-01"));
+        assert!(!is_recent_azure_api("2023-05-15"));
+    }
+
+    #[tokio::test]
+    async fn test_mock_azure_older_api_version_uses_max_tokens() {
+        let mut server = mockito::Server::new_async().await;
+
+        let mock = server.mock("POST", "/openai/deployments/team-gpt4/chat/completions")
+            .match_query(mockito::Matcher::UrlEncoded("api-version".to_string(), "2024-06-01".to_string()))
+            .match_body(mockito::Matcher::PartialJsonString(r#"{"max_tokens":1000}"#.to_string()))
+            .with_status(200)
+            .with_body(r#"{"choices":[{"message":{"role":"assistant","content":"ls"}}]}"#)
+            .create_async()
+            .await;
+
+        let client = OpenAI::azure_deployment("test_key".to_string(), &server.url(), "team-gpt4", "2024-06-01");
+
+        assert!(!client.uses_completion_tokens);
+        assert_eq!(client.generate("list files").await.unwrap(), "ls");
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_mock_stop_sequences_sent_when_set() {
+        let mut server = mockito::Server::new_async().await;
+
+        let mock = server.mock("POST", "/chat/completions")
+            .match_body(mockito::Matcher::PartialJsonString(r#"{"stop":["\n```"]}"#.to_string()))
+            .with_status(200)
+            .with_body(r#"{"choices":[{"message":{"role":"assistant","content":"```bash\nls"}}]}"#)
+            .create_async()
+            .await;
+
+        let client = OpenAI::new("test_key".to_string(), &server.url(), "gpt-4", false).with_stop(vec!["\n```".to_string()]);
+
+        assert_eq!(client.generate("Hi").await.unwrap(), "```bash\nls");
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_mock_json_mode_sends_response_format() {
+        let mut server = mockito::Server::new_async().await;
+
+        let mock = server.mock("POST", "/chat/completions")
+            .match_body(mockito::Matcher::PartialJsonString(r#"{"response_format":{"type":"json_object"}}"#.to_string()))
+            .with_status(200)
+            .with_body(r#"{"choices":[{"message":{"role":"assistant","content":"{\"command\":\"ls\"}"}}]}"#)
+            .create_async()
+            .await;
+
+        let client = OpenAI::new("test_key".to_string(), &server.url(), "gpt-4", false).with_json_mode(true);
+
+        assert!(client.returns_json());
+        assert_eq!(client.generate("Reply in JSON").await.unwrap(), r#"{"command":"ls"}"#);
+        mock.assert_async().await;
+        assert!(!client.with_json_mode(false).returns_json());
+    }
+
+    #[tokio::test]
+    async fn test_mock_sampling_options_sent_when_set() {
+        let mut server = mockito::Server::new_async().await;
+
+        let mock = server.mock("POST", "/chat/completions")
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{"top_p":0.5,"presence_penalty":0.25,"frequency_penalty":-0.5}"#.to_string()
+            ))
+            .with_status(200)
+            .with_body(r#"{"choices":[{"message":{"role":"assistant","content":"ls"}}]}"#)
+            .create_async()
+            .await;
+
+        let client = OpenAI::new("test_key".to_string(), &server.url(), "gpt-4", false)
+            .with_top_p(0.5)
+            .with_presence_penalty(0.25)
+            .with_frequency_penalty(-0.5);
+
+        assert_eq!(client.generate("Hi").await.unwrap(), "ls");
+        mock.assert_async().await;
+        assert_eq!(client.with_top_p(None).top_p, None);
+    }
+
+    #[tokio::test]
+    async fn test_parse_chunk() {
+        let chunk = r#"{"choices":[{"index":0,"delta":{"content":"Hel"}}]}"#;
+        assert_eq!(parse_chunk(chunk), vec![Ok(StreamChunk::Text("Hel".to_string()))]);
+
+        let role_only = r#"{"choices":[{"index":0,"delta":{"role":"assistant","content":""}}]}"#;
+        assert!(parse_chunk(role_only).is_empty());
+        assert!(parse_chunk("[DONE]").is_empty());
+
+        let usage = r#"{"choices":[],"usage":{"prompt_tokens":12,"completion_tokens":3,"total_tokens":15}}"#;
+        assert_eq!(
+            parse_chunk(usage),
+            vec![Ok(StreamChunk::Usage(Usage { input_tokens: 12, output_tokens: 3 }))]
+        );
+
+        let error = r#"{"error":{"message":"overloaded"}}"#;
+        assert_eq!(parse_chunk(error), vec![Err("API stream error: overloaded".to_string())]);
+    }
+
+    #[tokio::test]
+    async fn test_mock_chat_stream() {
+        let mut server = mockito::Server::new_async().await;
+
+        let body = concat!(
+            "data: {\"choices\":[{\"index\":0,\"delta\":{\"role\":\"assistant\",\"content\":\"\"}}]}\n\n",
+            "data: {\"choices\":[{\"index\":0,\"delta\":{\"content\":\"ls\"}}]}\n\n",
+            "data: {\"choices\":[{\"index\":0,\"delta\":{\"content\":\" -la\"}}]}\n\n",
+            "data: {\"choices\":[],\"usage\":{\"prompt_tokens\":9,\"completion_tokens\":2}}\n\n",
+            "data: [DONE]\n\n",
+        );
+
+        let mock = server.mock("POST", "/chat/completions")
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{"stream":true,"stream_options":{"include_usage":true}}"#.to_string(),
+            ))
+            .with_status(200)
+            .with_header("content-type", "text/event-stream")
+            .with_body(body)
+            .create_async()
+            .await;
+
+        let client = OpenAI::new("test_key".to_string(), &server.url(), "gpt-4", false);
+        let chunks: Vec<StreamChunk> = client
+            .chat_stream(vec![Message::user("list files")])
+            .await
+            .unwrap()
+            .map(|chunk| chunk.unwrap())
+            .collect()
+            .await;
+
+        mock.assert_async().await;
+        assert_eq!(chunks, vec![
+            StreamChunk::Text("ls".to_string()),
+            StreamChunk::Text(" -la".to_string()),
+            StreamChunk::Usage(Usage { input_tokens: 9, output_tokens: 2 }),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_mock_chat_stream_timeout_is_per_read() {
+        let mut server = mockito::Server::new_async().await;
+
+        let mock = server.mock("POST", "/chat/completions")
+            .with_status(200)
+            .with_header("content-type", "text/event-stream")
+            .with_chunked_body(|body| {
+                // Longer than the timeout in total, but never silent for that long
+                for word in ["ls", " -la", " ~"] {
+                    std::thread::sleep(Duration::from_millis(150));
+                    write!(body, "data: {{\"choices\":[{{\"index\":0,\"delta\":{{\"content\":\"{}\"}}}}]}}\n\n", word)?;
+                    body.flush()?;
+                }
+                std::thread::sleep(Duration::from_millis(600));
+                body.write_all(b"data: [DONE]\n\n")
+            })
+            .create_async()
+            .await;
+
+        let client = OpenAI::new("test_key".to_string(), &server.url(), "gpt-4", false)
+            .with_timeout(Duration::from_millis(400));
+        let chunks: Vec<Result<StreamChunk, Box<dyn Error>>> = client
+            .chat_stream(vec![Message::user("list files")])
+            .await
+            .unwrap()
+            .collect()
+            .await;
+
+        mock.assert_async().await;
+        assert_eq!(chunks.len(), 4);
+        let text: Vec<&StreamChunk> = chunks[..3].iter().map(|chunk| chunk.as_ref().unwrap()).collect();
+        assert_eq!(text, vec![
+            &StreamChunk::Text("ls".to_string()),
+            &StreamChunk::Text(" -la".to_string()),
+            &StreamChunk::Text(" ~".to_string()),
+        ]);
+        assert!(matches!(
+            chunks[3].as_ref().unwrap_err().downcast_ref::<ClipsterError>(),
+            Some(ClipsterError::Timeout(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_request_without_max_tokens_omits_cap() {
+        let request = ResponsesRequest {
+            model: "gpt-5.1".to_string(),
+            input: vec![Message::user("Hello")],
+            instructions: None,
+            max_output_tokens: None,
+            temperature: 0.7,
+            top_p: None,
+            text: None,
+        };
+
+        let json = serde_json::to_string(&request).unwrap();
+        assert!(!json.contains("max_output_tokens"));
     }
 
     #[tokio::test]
@@ -239,7 +987,8 @@ mod tests {
                             "content": "Hello! How can I help?"
                         }
                     }
-                ]
+                ],
+                "usage": {"prompt_tokens": 9, "completion_tokens": 6, "total_tokens": 15}
             }"#)
             .create_async()
             .await;
@@ -255,7 +1004,10 @@ mod tests {
 
         mock.assert_async().await;
         assert!(result.is_ok());
-        assert_eq!(result.unwrap(), "Hello! How can I help?");
+        assert_eq!(result.unwrap(), (
+            "Hello! How can I help?".to_string(),
+            Some(Usage { input_tokens: 9, output_tokens: 6 })
+        ));
     }
 
     #[tokio::test]
@@ -277,8 +1029,287 @@ mod tests {
 
         let result = client.do_chat(messages).await;
 
+        mock.assert_async().await;
+        match result {
+            Err(ClipsterError::ApiError { provider, status, body }) => {
+                assert_eq!(provider, "openai");
+                assert_eq!(status, 401);
+                assert_eq!(body, "Unauthorized");
+            }
+            other => panic!("expected an API error, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_openai_name() {
+        let client = OpenAI::new("test_key".to_string(), "https://api.test.com", "gpt-4", true);
+        assert_eq!(client.name(), "openai:gpt-4");
+
+        let client = client.with_provider("mistral").with_model("mistral-small-latest");
+        assert_eq!(client.name(), "mistral:mistral-small-latest");
+    }
+
+    #[tokio::test]
+    async fn test_mock_custom_headers() {
+        let mut server = mockito::Server::new_async().await;
+
+        let mock = server.mock("POST", "/chat/completions")
+            .match_header("authorization", "Bearer test_key")
+            .match_header("x-tenant", "acme")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"choices": [{"message": {"role": "assistant", "content": "ok"}}]}"#)
+            .create_async()
+            .await;
+
+        let client = OpenAI::new("test_key".to_string(), &server.url(), "gpt-4", false)
+            .with_header("X-Tenant", "acme")
+            .with_header("Authorization", "Bearer ignored");
+
+        let result = client.generate("Hi").await;
+
+        mock.assert_async().await;
+        assert_eq!(result.unwrap(), "ok");
+    }
+
+    #[tokio::test]
+    async fn test_mock_responses_api() {
+        let mut server = mockito::Server::new_async().await;
+
+        let mock = server.mock("POST", "/responses")
+            .match_header("authorization", "Bearer test_key")
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "model": "gpt-5.1",
+                "instructions": "Be brief",
+                "input": [{"role": "user", "content": "Hi"}],
+                "max_output_tokens": 1000
+            })))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{
+                "output": [
+                    {"type": "reasoning", "summary": []},
+                    {
+                        "type": "message",
+                        "role": "assistant",
+                        "content": [{"type": "output_text", "text": "Hello!", "annotations": []}]
+                    }
+                ]
+            }"#)
+            .create_async()
+            .await;
+
+        let client = OpenAI::new("test_key".to_string(), &server.url(), "gpt-5.1", true)
+            .with_responses_api(true);
+
+        let messages = vec![
+            Message { role: "system".to_string(), content: "Be brief".to_string() },
+            Message { role: "user".to_string(), content: "Hi".to_string() },
+        ];
+
+        let result = client.chat(messages).await;
+
+        mock.assert_async().await;
+        assert_eq!(result.unwrap(), "Hello!");
+    }
+
+    #[tokio::test]
+    async fn test_mock_responses_api_empty_output() {
+        let mut server = mockito::Server::new_async().await;
+
+        let mock = server.mock("POST", "/responses")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"output": []}"#)
+            .create_async()
+            .await;
+
+        let client = OpenAI::new("test_key".to_string(), &server.url(), "gpt-5.1", true)
+            .with_responses_api(true);
+
+        let result = client.generate("Hi").await;
+
         mock.assert_async().await;
         assert!(result.is_err());
-        assert!(result.unwrap_err().to_string().contains("401"));
+    }
+
+    #[tokio::test]
+    async fn test_mock_list_models() {
+        let mut server = mockito::Server::new_async().await;
+
+        let mock = server.mock("GET", "/models")
+            .match_header("authorization", "Bearer test_key")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{
+                "object": "list",
+                "data": [
+                    {"id": "gpt-5.1", "object": "model"},
+                    {"id": "gpt-4o", "object": "model"}
+                ]
+            }"#)
+            .create_async()
+            .await;
+
+        let client = OpenAI::new("test_key".to_string(), &server.url(), "gpt-4", false);
+        let models = client.list_models().await;
+
+        mock.assert_async().await;
+        assert_eq!(models.unwrap(), vec!["gpt-4o", "gpt-5.1"]);
+    }
+
+    #[tokio::test]
+    async fn test_mock_warm_up_ignores_status() {
+        let mut server = mockito::Server::new_async().await;
+
+        let mock = server.mock("HEAD", "/")
+            .match_header("authorization", mockito::Matcher::Missing)
+            .with_status(404)
+            .create_async()
+            .await;
+
+        let client = OpenAI::new("test_key".to_string(), &server.url(), "gpt-4", false);
+        let result = client.warm_up().await;
+
+        mock.assert_async().await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_mock_user_agent() {
+        let mut server = mockito::Server::new_async().await;
+
+        let default = server.mock("HEAD", "/")
+            .match_header("user-agent", DEFAULT_USER_AGENT)
+            .create_async()
+            .await;
+        let client = OpenAI::new("test_key".to_string(), &server.url(), "gpt-4", false);
+        client.warm_up().await.unwrap();
+        default.assert_async().await;
+
+        let custom = server.mock("HEAD", "/")
+            .match_header("user-agent", "acme-dictation/2")
+            .create_async()
+            .await;
+        let client = client.with_user_agent("acme-dictation/2");
+        client.warm_up().await.unwrap();
+        custom.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_mock_timeout() {
+        let mut server = mockito::Server::new_async().await;
+
+        let mock = server.mock("POST", "/chat/completions")
+            .with_status(200)
+            .with_chunked_body(|body| {
+                std::thread::sleep(Duration::from_millis(500));
+                body.write_all(br#"{"choices":[{"message":{"role":"assistant","content":"late"}}]}"#)
+            })
+            .create_async()
+            .await;
+
+        let client = OpenAI::new("test_key".to_string(), &server.url(), "gpt-4", false)
+            .with_timeout(Duration::from_millis(100));
+
+        let err = client.generate("Hi").await.unwrap_err();
+
+        mock.assert_async().await;
+        assert_eq!(err.to_string(), "request timed out after 0.1s");
+    }
+
+    #[tokio::test]
+    async fn test_mock_retries_server_errors() {
+        let mut server = mockito::Server::new_async().await;
+
+        let unavailable = server.mock("POST", "/chat/completions")
+            .with_status(503)
+            .expect(1)
+            .create_async()
+            .await;
+        let rate_limited = server.mock("POST", "/chat/completions")
+            .with_status(429)
+            .with_header("retry-after", "0")
+            .expect(1)
+            .create_async()
+            .await;
+        let success = server.mock("POST", "/chat/completions")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"choices":[{"message":{"role":"assistant","content":"ls"}}]}"#)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let mut client = OpenAI::new("test_key".to_string(), &server.url(), "gpt-4", false).with_retries(2);
+        client.retry_base_delay = Duration::ZERO;
+
+        assert_eq!(client.generate("Hi").await.unwrap(), "ls");
+        unavailable.assert_async().await;
+        rate_limited.assert_async().await;
+        success.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_mock_gives_up_on_long_retry_after() {
+        let mut server = mockito::Server::new_async().await;
+
+        let mock = server.mock("POST", "/chat/completions")
+            .with_status(429)
+            .with_header("retry-after", "3600")
+            .with_body("slow down")
+            .expect(1)
+            .create_async()
+            .await;
+
+        let client = OpenAI::new("test_key".to_string(), &server.url(), "gpt-4", false).with_retries(2);
+        let err = client.generate("Hi").await.unwrap_err();
+
+        mock.assert_async().await;
+        assert!(matches!(
+            err.downcast_ref::<ClipsterError>(),
+            Some(ClipsterError::ApiError { status: 429, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_mock_client_errors_fail_fast() {
+        let mut server = mockito::Server::new_async().await;
+
+        let mock = server.mock("POST", "/chat/completions")
+            .with_status(401)
+            .with_body("invalid key")
+            .expect(1)
+            .create_async()
+            .await;
+
+        let mut client = OpenAI::new("test_key".to_string(), &server.url(), "gpt-4", false).with_retries(3);
+        client.retry_base_delay = Duration::ZERO;
+
+        let err = client.generate("Hi").await.unwrap_err();
+
+        mock.assert_async().await;
+        assert!(err.to_string().contains("401"));
+    }
+
+    #[tokio::test]
+    async fn test_mock_custom_roles() {
+        let mut server = mockito::Server::new_async().await;
+
+        let mock = server.mock("POST", "/chat/completions")
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{"messages":[{"role":"developer","content":"Be brief"},{"role":"human","content":"Hi"}]}"#.to_string()
+            ))
+            .with_status(200)
+            .with_body(r#"{"choices": [{"message": {"role": "assistant", "content": "ok"}}]}"#)
+            .create_async()
+            .await;
+
+        let roles = Roles { system: "developer".to_string(), user: "human".to_string(), ..Roles::default() };
+        let client = OpenAI::new("test_key".to_string(), &server.url(), "gpt-4", false).with_roles(roles);
+        let result = client.generate_with_system("Be brief", "Hi").await;
+
+        mock.assert_async().await;
+        assert_eq!(result.unwrap(), "ok");
     }
 }
@@ -0,0 +1,113 @@
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::{RequestBuilder, Response, StatusCode};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Retries a provider gets when the config doesn't say
+pub const DEFAULT_API_RETRIES: u32 = 2;
+
+/// Wait before the first retry, doubled for each one after it
+pub const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+
+/// Longest `Retry-After` waited out, anything longer fails with the response instead of
+/// stalling the queue behind it
+pub const MAX_RETRY_AFTER: Duration = Duration::from_secs(30);
+
+/// Rate limits and server errors that tend to clear up on their own
+pub fn is_retryable(status: StatusCode) -> bool {
+    matches!(status.as_u16(), 429 | 500 | 502 | 503)
+}
+
+/// `Retry-After` given in seconds, the HTTP-date form isn't supported
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let seconds = headers.get(RETRY_AFTER)?.to_str().ok()?.trim().parse().ok()?;
+    Some(Duration::from_secs(seconds))
+}
+
+/// `base` doubled for each earlier retry, plus up to a quarter of that again so clients
+/// that failed together don't all retry at the same moment
+pub fn backoff(retry: u32, base: Duration) -> Duration {
+    let delay = base.saturating_mul(2u32.saturating_pow(retry));
+    let fraction = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.subsec_nanos()) as f64
+        / 1e9;
+
+    delay + delay.mul_f64(fraction / 4.0)
+}
+
+/// Sends `request`, retrying retryable statuses up to `max_retries` times. Other statuses,
+/// including client errors like 400 and 401, come back on the first attempt.
+pub async fn send_with_retries(
+    request: RequestBuilder,
+    max_retries: u32,
+    base_delay: Duration,
+) -> Result<Response, reqwest::Error> {
+    let mut retries = 0;
+
+    loop {
+        // Bodies that can't be cloned (streams) only get one attempt
+        let Some(attempt) = request.try_clone().filter(|_| retries < max_retries) else {
+            return request.send().await;
+        };
+
+        let response = attempt.send().await?;
+        if !is_retryable(response.status()) {
+            return Ok(response);
+        }
+
+        let delay = match retry_after(response.headers()) {
+            Some(delay) if delay > MAX_RETRY_AFTER => {
+                eprintln!("API returned {}, asking to retry in {}s, giving up", response.status(), delay.as_secs());
+                return Ok(response);
+            }
+            Some(delay) => delay,
+            None => backoff(retries, base_delay),
+        };
+        retries += 1;
+        eprintln!(
+            "API returned {}, retrying in {:.1}s ({}/{})",
+            response.status(),
+            delay.as_secs_f32(),
+            retries,
+            max_retries
+        );
+        tokio::time::sleep(delay).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn test_is_retryable() {
+        for status in [429, 500, 502, 503] {
+            assert!(is_retryable(StatusCode::from_u16(status).unwrap()));
+        }
+        for status in [200, 400, 401, 404, 504] {
+            assert!(!is_retryable(StatusCode::from_u16(status).unwrap()));
+        }
+    }
+
+    #[test]
+    fn test_retry_after_seconds() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("3"));
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(3)));
+
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"));
+        assert_eq!(retry_after(&headers), None);
+    }
+
+    #[test]
+    fn test_backoff_doubles_with_jitter() {
+        for retry in 0..4 {
+            let base = Duration::from_millis(500) * 2u32.pow(retry);
+            let delay = backoff(retry, Duration::from_millis(500));
+            assert!(delay >= base && delay <= base + base / 4, "retry {}: {:?}", retry, delay);
+        }
+    }
+}
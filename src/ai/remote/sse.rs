@@ -0,0 +1,203 @@
+use futures_util::Stream;
+use std::collections::VecDeque;
+use std::error::Error;
+use std::pin::Pin;
+use std::time::Duration;
+
+use crate::error::ClipsterError;
+
+/// A single server-sent event, as dispatched by a blank line in the stream.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SseEvent {
+    pub event: Option<String>,
+    pub data: String,
+}
+
+/// Incremental `text/event-stream` parser.
+///
+/// Raw bytes are buffered until a full line is available, so multi-byte
+/// UTF-8 characters split across network chunks are decoded intact.
+#[derive(Debug, Default)]
+pub struct SseParser {
+    buffer: Vec<u8>,
+    event: Option<String>,
+    data: Vec<String>,
+}
+
+impl SseParser {
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<SseEvent> {
+        self.buffer.extend_from_slice(chunk);
+
+        let mut events = Vec::new();
+        while let Some(newline_pos) = self.buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=newline_pos).collect();
+            events.extend(self.line(&line));
+        }
+
+        events
+    }
+
+    /// Ends the stream, dispatching an event the server didn't close with a blank line
+    pub fn finish(&mut self) -> Option<SseEvent> {
+        let line = std::mem::take(&mut self.buffer);
+        self.line(&line).or_else(|| self.dispatch())
+    }
+
+    fn line(&mut self, line: &[u8]) -> Option<SseEvent> {
+        let line = String::from_utf8_lossy(line);
+        let line = line.trim_end_matches(['\n', '\r']);
+
+        if line.is_empty() {
+            return self.dispatch();
+        }
+
+        // Comment lines (keep-alives) start with a colon
+        if line.starts_with(':') {
+            return None;
+        }
+
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+
+        match field {
+            "event" => self.event = Some(value.to_string()),
+            "data" => self.data.push(value.to_string()),
+            _ => {}
+        }
+        None
+    }
+
+    fn dispatch(&mut self) -> Option<SseEvent> {
+        let event = self.event.take();
+        if self.data.is_empty() {
+            return None;
+        }
+
+        let data = self.data.join("\n");
+        self.data.clear();
+        Some(SseEvent { event, data })
+    }
+}
+
+pub type EventStream<T> = Pin<Box<dyn Stream<Item = Result<T, Box<dyn Error>>> + Send>>;
+
+/// Streams a `text/event-stream` response body as the items `parse` makes of each event's data.
+/// An event may make any number of items, and the stream ends after the first error.
+/// A read that waits longer than the client's `timeout` ends it with [`ClipsterError::Timeout`].
+pub fn event_stream<T: Send + 'static>(
+    response: reqwest::Response,
+    timeout: Duration,
+    parse: fn(&str) -> Vec<Result<T, String>>,
+) -> EventStream<T> {
+    let stream = futures_util::stream::unfold(
+        (response, SseParser::default(), VecDeque::new(), false),
+        move |(mut response, mut parser, mut pending, mut done)| async move {
+            loop {
+                if let Some(item) = pending.pop_front() {
+                    let item: Result<T, ClipsterError> = item;
+                    return Some((item.map_err(|e| e.into()), (response, parser, pending, done)));
+                }
+                if done {
+                    return None;
+                }
+
+                match response.chunk().await {
+                    Ok(Some(bytes)) => {
+                        for event in parser.push(&bytes) {
+                            done = done || queue(&mut pending, parse(&event.data));
+                        }
+                    }
+                    Ok(None) => {
+                        if let Some(event) = parser.finish() {
+                            queue(&mut pending, parse(&event.data));
+                        }
+                        done = true;
+                    }
+                    Err(e) => {
+                        pending.push_back(Err(ClipsterError::from_reqwest(e, timeout)));
+                        done = true;
+                    }
+                }
+            }
+        },
+    );
+
+    Box::pin(stream)
+}
+
+/// Queues parsed items up to and including the first error, returning whether there was one
+fn queue<T>(pending: &mut VecDeque<Result<T, ClipsterError>>, items: Vec<Result<T, String>>) -> bool {
+    for item in items {
+        let failed = item.is_err();
+        pending.push_back(item.map_err(ClipsterError::InvalidResponse));
+        if failed {
+            return true;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sse_parser_single_event() {
+        let mut parser = SseParser::default();
+        let events = parser.push(b"event: ping\ndata: {}\n\n");
+
+        assert_eq!(events, vec![SseEvent {
+            event: Some("ping".to_string()),
+            data: "{}".to_string(),
+        }]);
+    }
+
+    #[test]
+    fn test_sse_parser_event_split_across_chunks() {
+        let mut parser = SseParser::default();
+
+        assert!(parser.push(b"data: hel").is_empty());
+        assert!(parser.push(b"lo\n").is_empty());
+        let events = parser.push(b"\n");
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event, None);
+        assert_eq!(events[0].data, "hello");
+    }
+
+    #[test]
+    fn test_sse_parser_buffers_partial_utf8() {
+        let mut parser = SseParser::default();
+        let bytes = "data: héllo\n\n".as_bytes();
+
+        // Split in the middle of the two-byte 'é'
+        let split = bytes.iter().position(|&b| b == 0xC3).unwrap() + 1;
+        assert!(parser.push(&bytes[..split]).is_empty());
+        let events = parser.push(&bytes[split..]);
+
+        assert_eq!(events[0].data, "héllo");
+    }
+
+    #[test]
+    fn test_sse_parser_finish_flushes_unterminated_event() {
+        let mut parser = SseParser::default();
+
+        assert_eq!(parser.push(b"data: a\n\nevent: done\ndata: b").len(), 1);
+        assert_eq!(parser.finish(), Some(SseEvent {
+            event: Some("done".to_string()),
+            data: "b".to_string(),
+        }));
+        assert_eq!(parser.finish(), None);
+    }
+
+    #[test]
+    fn test_sse_parser_handles_crlf_and_comments() {
+        let mut parser = SseParser::default();
+        let events = parser.push(b": keep-alive\r\n\r\ndata: a\r\ndata: b\r\n\r\n");
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].data, "a\nb");
+    }
+}
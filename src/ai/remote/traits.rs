@@ -1,24 +1,193 @@
 use async_trait::async_trait;
+use futures_util::Stream;
 use std::error::Error;
 use std::fmt::Debug;
+use std::pin::Pin;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// Role names used when building messages, providers translate them with [`Roles`] if needed
+pub const SYSTEM: &str = "system";
+pub const USER: &str = "user";
+pub const ASSISTANT: &str = "assistant";
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct Message {
     pub role: String,
     pub content: String,
 }
 
+impl Message {
+    pub fn system(content: &str) -> Self {
+        Self { role: SYSTEM.to_string(), content: content.to_string() }
+    }
+
+    pub fn user(content: &str) -> Self {
+        Self { role: USER.to_string(), content: content.to_string() }
+    }
+
+    pub fn assistant(content: &str) -> Self {
+        Self { role: ASSISTANT.to_string(), content: content.to_string() }
+    }
+}
+
+/// Role names a server expects, for OpenAI-compatible servers that don't use the standard ones
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct Roles {
+    pub system: String,
+    pub user: String,
+    pub assistant: String,
+}
+
+impl Default for Roles {
+    fn default() -> Self {
+        Self {
+            system: SYSTEM.to_string(),
+            user: USER.to_string(),
+            assistant: ASSISTANT.to_string(),
+        }
+    }
+}
+
+impl Roles {
+    /// The server's name for a standard role, other roles pass through unchanged
+    pub fn rename(&self, role: &str) -> String {
+        match role {
+            SYSTEM => self.system.clone(),
+            USER => self.user.clone(),
+            ASSISTANT => self.assistant.clone(),
+            other => other.to_string(),
+        }
+    }
+}
+
+/// Tokens billed for a single request, as reported by the API
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub struct Usage {
+    #[serde(default)]
+    pub input_tokens: u64,
+    #[serde(default)]
+    pub output_tokens: u64,
+}
+
+impl Usage {
+    /// Folds in counts reported partway through a stream. They're running totals, so the larger
+    /// one wins, e.g. Anthropic's input tokens from `message_start` and output from `message_delta`.
+    pub fn merge(&mut self, other: Usage) {
+        self.input_tokens = self.input_tokens.max(other.input_tokens);
+        self.output_tokens = self.output_tokens.max(other.output_tokens);
+    }
+}
+
+/// One item of a streamed response
+#[derive(Debug, Clone, PartialEq)]
+pub enum StreamChunk {
+    Text(String),
+    /// Token counts, usually sent once the text is complete
+    Usage(Usage),
+}
+
+/// Response text in pieces as the provider produces them, with usage if it's reported
+pub type TextStream = Pin<Box<dyn Stream<Item = Result<StreamChunk, Box<dyn Error>>> + Send>>;
+
+/// A response that arrived all at once as a [`TextStream`], the text followed by its usage
+pub fn whole_response_stream(text: String, usage: Option<Usage>) -> TextStream {
+    let chunks = std::iter::once(StreamChunk::Text(text)).chain(usage.map(StreamChunk::Usage));
+    Box::pin(futures_util::stream::iter(chunks.map(Ok)))
+}
+
 #[async_trait]
 pub trait AI: Send + Sync + Debug {
+    /// Provider and model for logging, e.g. `openai:gpt-5.1`
+    fn name(&self) -> String;
+
+    /// Provider and model that answered the last request, the same as `name` unless this is a
+    /// chain of providers. Usage is counted and priced under this name.
+    fn responder(&self) -> String {
+        self.name()
+    }
+
+    /// Whether responses are raw JSON, which post-processing like fence stripping must leave alone
+    fn returns_json(&self) -> bool {
+        false
+    }
+
     async fn chat(&self, messages: Vec<Message>) -> Result<String, Box<dyn Error>>;
 
+    /// Like `chat`, also returning token usage when the provider reports it
+    async fn chat_with_usage(&self, messages: Vec<Message>) -> Result<(String, Option<Usage>), Box<dyn Error>> {
+        Ok((self.chat(messages).await?, None))
+    }
+
+    /// Like `chat_with_usage`, yielding text as it arrives. By default the whole response is a single item.
+    async fn chat_stream(&self, messages: Vec<Message>) -> Result<TextStream, Box<dyn Error>> {
+        let (text, usage) = self.chat_with_usage(messages).await?;
+        Ok(whole_response_stream(text, usage))
+    }
+
     #[allow(dead_code)]
     async fn generate(&self, prompt: &str) -> Result<String, Box<dyn Error>> {
-        let messages = vec![Message {
-            role: "user".to_string(),
-            content: prompt.to_string(),
-        }];
-        self.chat(messages).await
+        self.chat(vec![Message::user(prompt)]).await
+    }
+
+    /// Opens (or reuses) a connection to the API without a billed request, so the next real
+    /// request skips the TLS handshake. The response status doesn't matter.
+    async fn warm_up(&self) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    /// Model ids the provider accepts, empty when it has no listing endpoint
+    async fn list_models(&self) -> Result<Vec<String>, Box<dyn Error>> {
+        Ok(Vec::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::StreamExt;
+
+    #[derive(Debug)]
+    struct Fixed;
+
+    #[async_trait]
+    impl AI for Fixed {
+        fn name(&self) -> String {
+            "fixed".to_string()
+        }
+
+        async fn chat(&self, _messages: Vec<Message>) -> Result<String, Box<dyn Error>> {
+            Ok("whole response".to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_default_chat_stream_yields_whole_response() {
+        let chunks: Vec<StreamChunk> = Fixed
+            .chat_stream(vec![Message::user("hi")])
+            .await
+            .unwrap()
+            .map(|chunk| chunk.unwrap())
+            .collect()
+            .await;
+
+        assert_eq!(chunks, vec![StreamChunk::Text("whole response".to_string())]);
+    }
+
+    #[test]
+    fn test_usage_merge_keeps_running_totals() {
+        let mut usage = Usage { input_tokens: 25, output_tokens: 1 };
+        usage.merge(Usage { input_tokens: 0, output_tokens: 15 });
+
+        assert_eq!(usage, Usage { input_tokens: 25, output_tokens: 15 });
+    }
+
+    #[test]
+    fn test_roles_rename() {
+        let roles = Roles { system: "developer".to_string(), ..Roles::default() };
+
+        assert_eq!(roles.rename(SYSTEM), "developer");
+        assert_eq!(roles.rename(USER), "user");
+        assert_eq!(roles.rename("tool"), "tool");
     }
 }
@@ -0,0 +1,433 @@
+use futures_util::StreamExt;
+use std::collections::VecDeque;
+use std::error::Error;
+use std::sync::Arc;
+
+use crate::ai::remote::traits::{Message, StreamChunk, Usage, AI};
+use crate::postprocess::ResponsePostProcessor;
+
+pub const SYSTEM_PROMPT: &str = "\
+You are a code assistant.
+ONLY respond with the requested code, command, or snippet.
+NO explanations.
+NO markdown (unless it was specifically asked for).
+NO unnecessary quotes around response.
+BE CONCISE and immediately usable.
+
+Correct example:
+User: \"Regex for email\"
+Response: \"[a-zA-Z0-9._%+-]+@[a-zA-Z0-9.-]+\\.[a-zA-Z]{2,}\"
+
+Incorrect example:
+User: \"Regex for email\"\
+Response: \"```text
+`^[^\\s@]+@[^\\s@]+\\.[^\\s@]+$
+```
+
+Or more comprehensive:
+
+```text
+^[a-zA-Z0-9.!#$%&'*+/=?^_`{|}~-]+@[a-zA-Z0-9](?:[a-zA-Z0-9-]{0,61}[a-zA-Z0-9])?(?: ...\"
+```
+";
+
+/// `history` holds earlier turns (see [`Conversation`]), sent between the system prompt and the new prompt.
+/// Responses from an AI that [returns JSON](AI::returns_json) skip `post_processor`.
+pub async fn get_ai_response(
+    ai: &Arc<Box<dyn AI>>,
+    system_prompt: &str,
+    history: &[Message],
+    user_prompt: &str,
+    user_template: Option<&str>,
+    persona: Option<&str>,
+    post_processor: &dyn ResponsePostProcessor,
+) -> Result<(String, Option<Usage>), Box<dyn Error>> {
+    let user_prompt = build_user_prompt(user_prompt, user_template, persona);
+
+    let messages = build_messages(system_prompt, history, &user_prompt);
+
+    let (response, usage) = ai.chat_with_usage(messages).await?;
+
+    Ok((post_process(ai, post_processor, response), usage))
+}
+
+/// Like [`get_ai_response`], handing each piece of the response to `on_chunk` as it arrives.
+/// Post-processing applies to the whole response once the stream ends.
+#[allow(clippy::too_many_arguments)]
+pub async fn get_ai_response_streaming(
+    ai: &Arc<Box<dyn AI>>,
+    system_prompt: &str,
+    history: &[Message],
+    user_prompt: &str,
+    user_template: Option<&str>,
+    persona: Option<&str>,
+    post_processor: &dyn ResponsePostProcessor,
+    on_chunk: &mut (dyn FnMut(&str) + Send),
+) -> Result<(String, Option<Usage>), Box<dyn Error>> {
+    let user_prompt = build_user_prompt(user_prompt, user_template, persona);
+
+    let messages = build_messages(system_prompt, history, &user_prompt);
+
+    let mut stream = ai.chat_stream(messages).await?;
+    let mut response = String::new();
+    let mut usage: Option<Usage> = None;
+
+    while let Some(chunk) = stream.next().await {
+        match chunk? {
+            StreamChunk::Text(text) => {
+                on_chunk(&text);
+                response.push_str(&text);
+            }
+            StreamChunk::Usage(reported) => usage.get_or_insert_default().merge(reported),
+        }
+    }
+
+    Ok((post_process(ai, post_processor, response), usage))
+}
+
+/// Raw JSON is passed through untouched, fence stripping or a length cap would only break it
+pub fn post_process(ai: &Arc<Box<dyn AI>>, post_processor: &dyn ResponsePostProcessor, response: String) -> String {
+    if ai.returns_json() {
+        response
+    } else {
+        post_processor.process(response)
+    }
+}
+
+/// The system prompt first, then earlier turns, then the new prompt
+pub fn build_messages(system_prompt: &str, history: &[Message], user_prompt: &str) -> Vec<Message> {
+    let mut messages = Vec::with_capacity(history.len() + 2);
+    messages.push(Message::system(system_prompt));
+    messages.extend_from_slice(history);
+    messages.push(Message::user(user_prompt));
+    messages
+}
+
+/// Recent prompts and responses for one provider, so follow-ups like "now make it async" have
+/// something to refer to. Only the last `max_turns` exchanges are kept.
+#[derive(Debug, Clone, Default)]
+pub struct Conversation {
+    turns: VecDeque<(String, String)>,
+    max_turns: usize,
+}
+
+impl Conversation {
+    pub fn new(max_turns: usize) -> Self {
+        Self { turns: VecDeque::new(), max_turns }
+    }
+
+    /// Remembers an exchange, forgetting the oldest one past `max_turns`
+    pub fn record(&mut self, user_prompt: &str, response: &str) {
+        if self.max_turns == 0 {
+            return;
+        }
+
+        self.turns.push_back((user_prompt.to_string(), response.to_string()));
+        while self.turns.len() > self.max_turns {
+            self.turns.pop_front();
+        }
+    }
+
+    /// The kept exchanges as alternating user and assistant messages, oldest first
+    pub fn messages(&self) -> Vec<Message> {
+        self.turns
+            .iter()
+            .flat_map(|(user_prompt, response)| [Message::user(user_prompt), Message::assistant(response)])
+            .collect()
+    }
+
+    pub fn turns(&self) -> usize {
+        self.turns.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.turns.clear();
+    }
+}
+
+/// The user message for a transcript: the template applied first, then the persona prepended as-is
+pub fn build_user_prompt(transcript: &str, user_template: Option<&str>, persona: Option<&str>) -> String {
+    let prompt = match user_template {
+        Some(template) => apply_template(template, transcript),
+        None => transcript.to_string(),
+    };
+
+    match persona {
+        Some(persona) => format!("{}{}", persona, prompt),
+        None => prompt,
+    }
+}
+
+/// Inserts the transcript into a prompt template at `{transcript}`,
+/// or appends it on a new line if the template has no placeholder
+pub fn apply_template(template: &str, transcript: &str) -> String {
+    if template.contains("{transcript}") {
+        template.replace("{transcript}", transcript)
+    } else {
+        format!("{}\n{}", template, transcript)
+    }
+}
+
+/// Fills a clipboard template such as `Q: {transcript}\n\nA: {response}`
+pub fn format_clipboard(template: &str, transcript: &str, response: &str) -> String {
+    template
+        .replace("{transcript}", transcript)
+        .replace("{response}", response)
+}
+
+pub fn with_trailing_newline(text: &str, trailing_newline: bool) -> String {
+    let text = text.trim_end_matches(['\n', '\r']);
+    if trailing_newline {
+        format!("{}\n", text)
+    } else {
+        text.to_string()
+    }
+}
+
+/// First `max_chars` characters of the response for the terminal, with `...` if anything was cut
+pub fn preview(response: &str, max_chars: usize) -> String {
+    match response.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}...", &response[..end]),
+        None => response.to_string(),
+    }
+}
+
+/// Markdown code fence markers
+const FENCES: [&str; 2] = ["```", "~~~"];
+
+/// Unwraps a response that is one fenced code block, with or without a language tag or closing fence.
+/// Responses with several blocks, or prose around a block, are kept as they are apart from
+/// a stray closing fence at the end.
+pub fn clean_response(response: &str) -> String {
+    let trimmed = response.trim();
+
+    let Some(fence) = FENCES.into_iter().find(|fence| trimmed.starts_with(fence)) else {
+        return strip_stray_fence(trimmed).to_string();
+    };
+    let rest = &trimmed[fence.len()..];
+
+    let Some((_language, body)) = rest.split_once('\n') else {
+        // Single-line fence such as ```ls -la```, there's no language tag to drop
+        return rest.strip_suffix(fence).unwrap_or(rest).trim().to_string();
+    };
+    let body = body.strip_suffix(fence).unwrap_or(body);
+
+    if body.lines().any(|line| line.trim_start().starts_with(fence)) {
+        return trimmed.to_string();
+    }
+
+    body.trim().to_string()
+}
+
+/// `text` without a last-line fence that closes nothing, fences that pair up are left alone
+fn strip_stray_fence(text: &str) -> &str {
+    for fence in FENCES {
+        if text.matches(fence).count() % 2 == 1 {
+            if let Some(stripped) = text.strip_suffix(fence) {
+                return stripped.trim_end();
+            }
+        }
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_messages_pins_system_prompt_first() {
+        let history = vec![Message::user("ls"), Message::assistant("ls -la")];
+        let messages = build_messages("Be brief", &history, "sorted by size");
+
+        assert_eq!(
+            messages,
+            vec![
+                Message::system("Be brief"),
+                Message::user("ls"),
+                Message::assistant("ls -la"),
+                Message::user("sorted by size"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_conversation_keeps_last_turns() {
+        let mut conversation = Conversation::new(2);
+        conversation.record("one", "1");
+        conversation.record("two", "2");
+        conversation.record("three", "3");
+
+        assert_eq!(conversation.turns(), 2);
+        assert_eq!(
+            conversation.messages(),
+            vec![Message::user("two"), Message::assistant("2"), Message::user("three"), Message::assistant("3")]
+        );
+
+        conversation.clear();
+        assert!(conversation.messages().is_empty());
+    }
+
+    #[test]
+    fn test_conversation_disabled_with_zero_turns() {
+        let mut conversation = Conversation::new(0);
+        conversation.record("one", "1");
+        assert_eq!(conversation.turns(), 0);
+    }
+
+    #[test]
+    fn test_clean_response_removes_code_fences() {
+        let response = "```rust\nfn main() {}\n```";
+        let cleaned = clean_response(response);
+        assert_eq!(cleaned, "fn main() {}");
+    }
+
+    #[test]
+    fn test_clean_response_removes_code_fences_with_language() {
+        let response = "```python\nprint('hello')\n```";
+        let cleaned = clean_response(response);
+        assert_eq!(cleaned, "print('hello')");
+    }
+
+    #[test]
+    fn test_clean_response_handles_no_code_fences() {
+        let response = "Just plain text";
+        let cleaned = clean_response(response);
+        assert_eq!(cleaned, "Just plain text");
+    }
+
+    #[test]
+    fn test_clean_response_trims_whitespace() {
+        let response = "   some text   ";
+        let cleaned = clean_response(response);
+        assert_eq!(cleaned, "some text");
+    }
+
+    #[test]
+    fn test_clean_response_handles_empty_string() {
+        let response = "";
+        let cleaned = clean_response(response);
+        assert_eq!(cleaned, "");
+    }
+
+    #[test]
+    fn test_clean_response_handles_only_code_fences() {
+        let response = "```\ncode here\n```";
+        let cleaned = clean_response(response);
+        assert_eq!(cleaned, "code here");
+    }
+
+    #[test]
+    fn test_clean_response_handles_multiline_code() {
+        let response = "```javascript\nconst x = 1;\nconst y = 2;\nconsole.log(x + y);\n```";
+        let cleaned = clean_response(response);
+        assert_eq!(cleaned, "const x = 1;\nconst y = 2;\nconsole.log(x + y);");
+    }
+
+    #[test]
+    fn test_clean_response_closing_fence_without_newline() {
+        assert_eq!(clean_response("```\nls -la```"), "ls -la");
+        assert_eq!(clean_response("```python\nprint('hello')```"), "print('hello')");
+    }
+
+    #[test]
+    fn test_clean_response_fences_with_trailing_whitespace() {
+        assert_eq!(clean_response("```bash  \nls -la  \n```  \n"), "ls -la");
+        assert_eq!(clean_response("```\r\nls -la\r\n```\r\n"), "ls -la");
+    }
+
+    #[test]
+    fn test_clean_response_without_closing_fence() {
+        assert_eq!(clean_response("```rust\nfn main() {}"), "fn main() {}");
+        assert_eq!(clean_response("```\nls -la\n"), "ls -la");
+    }
+
+    #[test]
+    fn test_clean_response_single_line_fence() {
+        assert_eq!(clean_response("```ls -la```"), "ls -la");
+        assert_eq!(clean_response("```"), "");
+    }
+
+    #[test]
+    fn test_clean_response_tilde_fences_and_leading_whitespace() {
+        assert_eq!(clean_response("  ~~~sh\nls -la\n~~~"), "ls -la");
+        assert_eq!(clean_response("\n\n```rust\nfn main() {}\n```"), "fn main() {}");
+    }
+
+    #[test]
+    fn test_clean_response_keeps_multiple_blocks() {
+        let response = "```bash\ncargo build\n```\nthen\n```bash\ncargo test\n```";
+        assert_eq!(clean_response(response), response);
+
+        let prose = "Run this:\n```\nls -la\n```";
+        assert_eq!(clean_response(prose), prose);
+    }
+
+    #[test]
+    fn test_clean_response_stray_trailing_fence() {
+        assert_eq!(clean_response("ls -la\n```"), "ls -la");
+        assert_eq!(clean_response("ls -la ~~~"), "ls -la");
+    }
+
+    #[test]
+    fn test_with_trailing_newline_after_clean_response() {
+        let fenced = clean_response("```rust\nfn main() {}\n```\n");
+        assert_eq!(with_trailing_newline(&fenced, false), "fn main() {}");
+        assert_eq!(with_trailing_newline(&fenced, true), "fn main() {}\n");
+
+        let plain = clean_response("ls -la\n\n");
+        assert_eq!(with_trailing_newline(&plain, true), "ls -la\n");
+    }
+
+    #[test]
+    fn test_with_trailing_newline_does_not_duplicate() {
+        assert_eq!(with_trailing_newline("code\n", true), "code\n");
+        assert_eq!(with_trailing_newline("code\r\n", false), "code");
+        assert_eq!(with_trailing_newline("", true), "\n");
+    }
+
+    #[test]
+    fn test_preview_truncates_on_char_boundary() {
+        assert_eq!(preview("short", 100), "short");
+        assert_eq!(preview("abcdef", 3), "abc...");
+        assert_eq!(preview("héllo wörld", 4), "héll...");
+        assert_eq!(preview("日本語テキスト", 3), "日本語...");
+        assert_eq!(preview("exact", 5), "exact");
+    }
+
+    #[test]
+    fn test_format_clipboard_fills_transcript_and_response() {
+        let template = "Q: {transcript}\n\nA: {response}";
+        assert_eq!(
+            format_clipboard(template, "largest files here", "du -ah . | sort -rh | head"),
+            "Q: largest files here\n\nA: du -ah . | sort -rh | head"
+        );
+        assert_eq!(format_clipboard("{response}", "ignored", "ls"), "ls");
+    }
+
+    #[test]
+    fn test_build_user_prompt_composes_persona_and_template() {
+        assert_eq!(build_user_prompt("fix this", None, None), "fix this");
+        assert_eq!(
+            build_user_prompt("is this safe", None, Some("As a senior Rust reviewer, ")),
+            "As a senior Rust reviewer, is this safe"
+        );
+        assert_eq!(
+            build_user_prompt("unwrap everywhere", Some("Review: {transcript}"), Some("As a senior Rust reviewer, ")),
+            "As a senior Rust reviewer, Review: unwrap everywhere"
+        );
+    }
+
+    #[test]
+    fn test_apply_template_replaces_placeholder() {
+        let prompt = apply_template("Translate the following to Japanese:\n{transcript}", "good morning");
+        assert_eq!(prompt, "Translate the following to Japanese:\ngood morning");
+    }
+
+    #[test]
+    fn test_apply_template_without_placeholder_appends() {
+        let prompt = apply_template("Write a conventional-commit message for:", "fixed the login bug");
+        assert_eq!(prompt, "Write a conventional-commit message for:\nfixed the login bug");
+    }
+}
@@ -1,23 +1,56 @@
 use rubato::{
     Resampler, SincFixedIn, SincInterpolationType, SincInterpolationParameters, WindowFunction,
 };
+use serde::Deserialize;
 use std::error::Error;
+use std::path::Path;
 
-pub fn resample_to_16khz(samples: &[f32], original_rate: u32) -> Result<Vec<f32>, Box<dyn Error>> {
-    if original_rate == 16000 {
+/// Trade-off between resampling cost and quality
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResampleQuality {
+    /// Short sinc for slow machines
+    Fast,
+    #[default]
+    Balanced,
+    /// Long sinc with cubic interpolation, `high` in the config
+    #[serde(rename = "high")]
+    HighQuality,
+}
+
+impl ResampleQuality {
+    fn sinc_params(&self) -> SincInterpolationParameters {
+        let (sinc_len, oversampling_factor, interpolation) = match self {
+            ResampleQuality::Fast => (64, 128, SincInterpolationType::Linear),
+            ResampleQuality::Balanced => (256, 256, SincInterpolationType::Linear),
+            ResampleQuality::HighQuality => (512, 256, SincInterpolationType::Cubic),
+        };
+
+        SincInterpolationParameters {
+            sinc_len,
+            f_cutoff: 0.95,
+            interpolation,
+            oversampling_factor,
+            window: WindowFunction::BlackmanHarris2,
+        }
+    }
+}
+
+/// Resamples mono audio from `from_rate` to `to_rate`, returning the input as is when they match
+pub fn resample(
+    samples: &[f32],
+    from_rate: u32,
+    to_rate: u32,
+    quality: ResampleQuality,
+) -> Result<Vec<f32>, Box<dyn Error>> {
+    if from_rate == to_rate {
         return Ok(samples.to_vec());
     }
 
-    let params = SincInterpolationParameters {
-        sinc_len: 256,
-        f_cutoff: 0.95,
-        interpolation: SincInterpolationType::Linear,
-        oversampling_factor: 256,
-        window: WindowFunction::BlackmanHarris2,
-    };
+    let params = quality.sinc_params();
 
     let mut resampler = SincFixedIn::<f32>::new(
-        16000 as f64 / original_rate as f64,
+        to_rate as f64 / from_rate as f64,
         2.0,
         params,
         samples.len(),
@@ -30,6 +63,20 @@ pub fn resample_to_16khz(samples: &[f32], original_rate: u32) -> Result<Vec<f32>
     Ok(waves_out[0].clone())
 }
 
+/// [`resample_to_16khz_with_quality`] at [`ResampleQuality::Balanced`]
+pub fn resample_to_16khz(samples: &[f32], original_rate: u32) -> Result<Vec<f32>, Box<dyn Error>> {
+    resample_to_16khz_with_quality(samples, original_rate, ResampleQuality::Balanced)
+}
+
+/// Resamples mono audio to the 16kHz Whisper expects
+pub fn resample_to_16khz_with_quality(
+    samples: &[f32],
+    original_rate: u32,
+    quality: ResampleQuality,
+) -> Result<Vec<f32>, Box<dyn Error>> {
+    resample(samples, original_rate, WHISPER_SAMPLE_RATE, quality)
+}
+
 pub fn normalize_audio(samples: &[f32]) -> Vec<f32> {
     let max_amplitude = samples
         .iter()
@@ -43,6 +90,147 @@ pub fn normalize_audio(samples: &[f32]) -> Vec<f32> {
     }
 }
 
+/// RMS level targeted by [`Normalization::Rms`] unless configured, loud enough for Whisper
+/// without clipping typical speech
+pub const DEFAULT_TARGET_RMS: f32 = 0.1;
+
+/// How recordings are leveled before transcription
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Normalization {
+    /// Scale so the loudest sample is 1.0
+    #[default]
+    Peak,
+    /// Scale to an RMS level, clipped to [-1, 1]; a single loud click won't flatten the speech
+    Rms,
+    /// Feed the samples as recorded
+    None,
+}
+
+impl Normalization {
+    /// `target_rms` is only used by [`Normalization::Rms`]
+    pub fn apply(&self, samples: &[f32], target_rms: f32) -> Vec<f32> {
+        match self {
+            Normalization::Peak => normalize_audio(samples),
+            Normalization::Rms => normalize_rms(samples, target_rms),
+            Normalization::None => samples.to_vec(),
+        }
+    }
+}
+
+pub fn normalize_rms(samples: &[f32], target_rms: f32) -> Vec<f32> {
+    if samples.is_empty() {
+        return Vec::new();
+    }
+
+    let rms = (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt();
+    if rms == 0.0 {
+        return samples.to_vec();
+    }
+
+    let gain = target_rms / rms;
+    samples.iter().map(|&s| (s * gain).clamp(-1.0, 1.0)).collect()
+}
+
+/// Sample rate of the buffers handed to Whisper
+pub const WHISPER_SAMPLE_RATE: u32 = 16000;
+
+/// Margin [`trim_silence`] keeps around speech so edge words aren't clipped
+pub const TRIM_PADDING_MS: u32 = 200;
+
+/// Removes silence from both ends of a buffer recorded at `sample_rate`, keeping
+/// [`TRIM_PADDING_MS`] around the first and last samples above `threshold`.
+/// An entirely silent buffer trims to nothing.
+pub fn trim_silence(samples: &[f32], threshold: f32, sample_rate: u32) -> Vec<f32> {
+    trim_silence_with_margin(samples, threshold, sample_rate, TRIM_PADDING_MS)
+}
+
+/// [`trim_silence`] with `margin_ms` of padding instead of [`TRIM_PADDING_MS`]
+pub fn trim_silence_with_margin(samples: &[f32], threshold: f32, sample_rate: u32, margin_ms: u32) -> Vec<f32> {
+    let Some(first) = samples.iter().position(|s| s.abs() > threshold) else {
+        return Vec::new();
+    };
+    let last = samples.iter().rposition(|s| s.abs() > threshold).unwrap_or(first);
+
+    let margin = (sample_rate as u64 * margin_ms as u64 / 1000) as usize;
+    let start = first.saturating_sub(margin);
+    let end = last.saturating_add(1).saturating_add(margin).min(samples.len());
+
+    samples[start..end].to_vec()
+}
+
+/// How quickly the noise gate's envelope and gain follow the signal, per sample
+const GATE_SMOOTHING: f32 = 0.01;
+
+/// Noise gate for steady background noise (fans, AC) on a 16kHz buffer. The noise floor is
+/// the peak of the first `noise_window_ms`, which is assumed to be silence before speech.
+/// Wherever the smoothed envelope stays below that floor, samples are scaled by `floor_gain`.
+pub fn reduce_noise(samples: &[f32], noise_window_ms: u32, floor_gain: f32) -> Vec<f32> {
+    let window = (WHISPER_SAMPLE_RATE as u64 * noise_window_ms as u64 / 1000) as usize;
+    let noise_floor = samples[..window.min(samples.len())]
+        .iter()
+        .map(|s| s.abs())
+        .fold(0.0f32, f32::max);
+
+    if noise_floor == 0.0 {
+        return samples.to_vec();
+    }
+
+    let mut envelope = 0.0f32;
+    let mut gain = floor_gain;
+
+    samples
+        .iter()
+        .map(|&s| {
+            envelope += GATE_SMOOTHING * (s.abs() - envelope);
+            let target = if envelope > noise_floor { 1.0 } else { floor_gain };
+            gain += GATE_SMOOTHING * (target - gain);
+            s * gain
+        })
+        .collect()
+}
+
+/// Writes a mono buffer as 16-bit PCM
+pub fn save_wav(samples: &[f32], sample_rate: u32, path: &Path) -> Result<(), Box<dyn Error>> {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+
+    let mut writer = hound::WavWriter::create(path, spec)?;
+    for &sample in samples {
+        writer.write_sample((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)?;
+    }
+    writer.finalize()?;
+
+    Ok(())
+}
+
+/// Reads a 16-bit PCM or float WAV as mono, averaging the channels, along with its sample rate
+pub fn load_wav(path: &Path) -> Result<(Vec<f32>, u32), Box<dyn Error>> {
+    let mut reader = hound::WavReader::open(path)?;
+    let spec = reader.spec();
+
+    let interleaved: Vec<f32> = match spec.sample_format {
+        hound::SampleFormat::Int if spec.bits_per_sample == 16 => reader
+            .samples::<i16>()
+            .map(|sample| sample.map(|sample| sample as f32 / i16::MAX as f32))
+            .collect::<Result<_, _>>()?,
+        hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>()?,
+        _ => return Err(format!("Unsupported WAV format: {}-bit {:?}", spec.bits_per_sample, spec.sample_format).into()),
+    };
+
+    let channels = spec.channels.max(1) as usize;
+    let samples = interleaved
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / frame.len() as f32)
+        .collect();
+
+    Ok((samples, spec.sample_rate))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -90,6 +278,36 @@ mod tests {
         assert!((max_amplitude - 1.0).abs() < 1e-6);
     }
 
+    #[test]
+    fn test_normalize_rms_reaches_target() {
+        let samples: Vec<f32> = (0..1000).map(|i| if i % 2 == 0 { 0.01 } else { -0.01 }).collect();
+        let normalized = normalize_rms(&samples, 0.1);
+
+        let rms = (normalized.iter().map(|s| s * s).sum::<f32>() / normalized.len() as f32).sqrt();
+        assert!((rms - 0.1).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_normalize_rms_clips_and_handles_silence() {
+        let normalized = normalize_rms(&[0.001, 0.001, 0.5], 0.5);
+        assert!(normalized.iter().all(|s| s.abs() <= 1.0));
+        assert_eq!(normalize_rms(&[0.0, 0.0], 0.1), vec![0.0, 0.0]);
+        assert!(normalize_rms(&[], 0.1).is_empty());
+    }
+
+    #[test]
+    fn test_normalization_modes() {
+        let samples = vec![0.25, -0.5];
+
+        assert_eq!(Normalization::Peak.apply(&samples, DEFAULT_TARGET_RMS), vec![0.5, -1.0]);
+        assert_eq!(Normalization::None.apply(&samples, DEFAULT_TARGET_RMS), samples);
+
+        let rms = Normalization::Rms.apply(&samples, 0.2);
+        let level = (rms.iter().map(|s| s * s).sum::<f32>() / rms.len() as f32).sqrt();
+        assert!((level - 0.2).abs() < 1e-5);
+        assert_eq!(Normalization::default(), Normalization::Peak);
+    }
+
     #[test]
     fn test_resample_to_16khz_no_change_when_already_16khz() {
         let samples = vec![0.1, 0.2, 0.3, 0.4];
@@ -106,4 +324,156 @@ mod tests {
         // Allow some margin due to resampling algorithm
         assert!(result.len() < samples.len());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_resample_to_16khz_length_for_each_quality() {
+        // 0.5s at 48kHz should come out as 0.5s at 16kHz, less the filter delay
+        // of up to half the sinc length that a single pass leaves at the tail
+        let samples: Vec<f32> = (0..24000).map(|i| (i as f32 * 0.05).sin()).collect();
+
+        for quality in [ResampleQuality::Fast, ResampleQuality::Balanced, ResampleQuality::HighQuality] {
+            let result = resample_to_16khz_with_quality(&samples, 48000, quality).unwrap();
+            let max_delay = quality.sinc_params().sinc_len / 2;
+            assert!(
+                result.len() <= 8000 && result.len() >= 8000 - max_delay,
+                "{quality:?} produced {} samples",
+                result.len()
+            );
+        }
+    }
+
+    #[test]
+    fn test_resample_to_16khz_defaults_to_balanced() {
+        let samples: Vec<f32> = (0..4800).map(|i| (i as f32 * 0.05).sin()).collect();
+
+        assert_eq!(
+            resample_to_16khz(&samples, 48000).unwrap(),
+            resample_to_16khz_with_quality(&samples, 48000, ResampleQuality::Balanced).unwrap()
+        );
+        assert_eq!(ResampleQuality::default(), ResampleQuality::Balanced);
+    }
+
+    #[test]
+    fn test_resample_no_change_for_equal_rates() {
+        let samples = vec![0.1, 0.2, 0.3, 0.4];
+        assert_eq!(resample(&samples, 44100, 44100, ResampleQuality::default()).unwrap(), samples);
+    }
+
+    #[test]
+    fn test_resample_upsamples_to_44100() {
+        // 0.5s at 16kHz comes out as 0.5s at 44.1kHz, less the filter delay at the tail
+        let samples: Vec<f32> = (0..8000).map(|i| (i as f32 * 0.05).sin()).collect();
+        let result = resample(&samples, 16000, 44100, ResampleQuality::default()).unwrap();
+
+        let max_delay = ResampleQuality::default().sinc_params().sinc_len * 3;
+        assert!(result.len() <= 22050 && result.len() >= 22050 - max_delay, "produced {} samples", result.len());
+    }
+
+    #[test]
+    fn test_resample_to_16khz_tiny_inputs() {
+        // An accidental tap can be a handful of samples, shorter than any sinc window
+        for quality in [ResampleQuality::Fast, ResampleQuality::Balanced, ResampleQuality::HighQuality] {
+            let result = resample_to_16khz_with_quality(&[0.1, -0.2, 0.3], 44100, quality).unwrap();
+            assert!(result.len() <= 2, "{quality:?} produced {} samples", result.len());
+            assert!(resample_to_16khz_with_quality(&[], 44100, quality).unwrap().is_empty());
+        }
+    }
+
+    fn rms(samples: &[f32]) -> f32 {
+        (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+    }
+
+    #[test]
+    fn test_reduce_noise_attenuates_noise_and_keeps_tone() {
+        // Deterministic noise at +-0.05, with a 440Hz tone at 0.5 from 0.3s to 0.8s
+        let mut seed = 12345u32;
+        let samples: Vec<f32> = (0..16000)
+            .map(|i| {
+                seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                let noise = ((seed >> 16) as f32 / 32768.0 - 1.0) * 0.05;
+                let tone = if (4800..12800).contains(&i) {
+                    (i as f32 * 440.0 * 2.0 * std::f32::consts::PI / 16000.0).sin() * 0.5
+                } else {
+                    0.0
+                };
+                tone + noise
+            })
+            .collect();
+
+        let reduced = reduce_noise(&samples, 200, 0.1);
+        assert_eq!(reduced.len(), samples.len());
+
+        // Trailing noise, well after the gate has closed again
+        let noise = 14000..16000;
+        assert!(rms(&reduced[noise.clone()]) < rms(&samples[noise]) * 0.2);
+
+        // Body of the tone, after the gate has opened
+        let tone = 6000..12000;
+        assert!(rms(&reduced[tone.clone()]) > rms(&samples[tone]) * 0.95);
+    }
+
+    #[test]
+    fn test_reduce_noise_silent_buffer_unchanged() {
+        let samples = vec![0.0; 8000];
+        assert_eq!(reduce_noise(&samples, 200, 0.1), samples);
+        assert!(reduce_noise(&[], 200, 0.1).is_empty());
+    }
+
+    #[test]
+    fn test_trim_silence_with_margin_keeps_quiet_gaps() {
+        // 1s silence, 0.5s speech with a quiet gap in the middle, 1s silence
+        let mut samples = vec![0.0; 16000];
+        samples.extend(vec![0.5; 4000]);
+        samples.extend(vec![0.0; 2000]);
+        samples.extend(vec![0.5; 2000]);
+        samples.extend(vec![0.0; 16000]);
+
+        let trimmed = trim_silence_with_margin(&samples, 0.1, 16000, 100);
+
+        // 8000 samples of speech plus 100ms (1600 samples) margin each side
+        assert_eq!(trimmed.len(), 8000 + 2 * 1600);
+        assert_eq!(trimmed[1600], 0.5);
+        assert_eq!(trimmed[trimmed.len() - 1601], 0.5);
+    }
+
+    #[test]
+    fn test_trim_silence_pads_by_sample_rate() {
+        // 1s silence, 0.25s speech, 1s silence at 48kHz
+        let mut samples = vec![0.0; 48000];
+        samples.extend(vec![0.5; 12000]);
+        samples.extend(vec![0.0; 48000]);
+
+        let trimmed = trim_silence(&samples, 0.1, 48000);
+
+        // 200ms of padding is 9600 samples at 48kHz
+        assert_eq!(trimmed.len(), 12000 + 2 * 9600);
+        assert_eq!(trimmed[9600], 0.5);
+        assert_eq!(trimmed[9599], 0.0);
+    }
+
+    #[test]
+    fn test_trim_silence_edge_cases() {
+        assert!(trim_silence(&[0.01; 1000], 0.1, 16000).is_empty());
+        assert!(trim_silence(&[], 0.1, 16000).is_empty());
+
+        // Speech at both ends, padding would run past either bound
+        let samples = [0.5, 0.0, 0.0, 0.5];
+        assert_eq!(trim_silence(&samples, 0.1, 44100), samples);
+    }
+
+    #[test]
+    fn test_save_and_load_wav_round_trip() {
+        let path = std::env::temp_dir().join(format!("clipster-test-{}.wav", std::process::id()));
+        let samples = vec![0.0, 0.5, -0.5, 1.0, -1.0];
+
+        save_wav(&samples, 44100, &path).unwrap();
+        let (loaded, sample_rate) = load_wav(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(sample_rate, 44100);
+        assert_eq!(loaded.len(), samples.len());
+        for (loaded, original) in loaded.iter().zip(&samples) {
+            assert!((loaded - original).abs() < 1e-3);
+        }
+    }
+}
@@ -1,44 +1,306 @@
 use rodio::cpal::{self, traits::{DeviceTrait, HostTrait, StreamTrait}, Sample, SizedSample};
-use std::error::Error;
+use serde::Deserialize;
+use std::fmt::Display;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 
+use crate::error::ClipsterError;
+
+/// Summary of a finished recording
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct RecordingStats {
+    pub duration_secs: f32,
+    pub peak: f32,
+    pub rms: f32,
+    pub sample_count: usize,
+}
+
+impl RecordingStats {
+    pub fn from_samples(samples: &[f32], sample_rate: u32) -> Self {
+        if samples.is_empty() || sample_rate == 0 {
+            return Self::default();
+        }
+
+        let peak = samples.iter().map(|s| s.abs()).fold(0.0f32, f32::max);
+
+        Self {
+            duration_secs: samples.len() as f32 / sample_rate as f32,
+            peak,
+            rms: rms(samples),
+            sample_count: samples.len(),
+        }
+    }
+}
+
+fn rms(samples: &[f32]) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    let sum_squares: f32 = samples.iter().map(|s| s * s).sum();
+    (sum_squares / samples.len() as f32).sqrt()
+}
+
+/// Whether the last `window_ms` of `samples` stayed below `threshold` RMS after at least one
+/// louder 20 ms frame, so a recording that hasn't heard any speech yet doesn't end on its own
+pub fn ends_in_silence(samples: &[f32], sample_rate: u32, window_ms: u32, threshold: f32) -> bool {
+    SilenceDetector::default().ends_in_silence(samples, sample_rate, window_ms, threshold)
+}
+
+/// [`ends_in_silence`] for a buffer that keeps growing. Each frame is checked for speech only
+/// once, so a poll costs the trailing window rather than the whole recording.
+#[derive(Debug, Default)]
+pub struct SilenceDetector {
+    /// Samples already checked for speech
+    scanned: usize,
+    heard_speech: bool,
+}
+
+impl SilenceDetector {
+    /// `samples` must be the same recording as in earlier calls, with new samples appended
+    pub fn ends_in_silence(&mut self, samples: &[f32], sample_rate: u32, window_ms: u32, threshold: f32) -> bool {
+        let window = (sample_rate as u64 * window_ms as u64 / 1000) as usize;
+        if window == 0 || samples.len() <= window {
+            return false;
+        }
+
+        let (before, tail) = samples.split_at(samples.len() - window);
+        let frame = (sample_rate as usize / 50).max(1);
+
+        while !self.heard_speech && self.scanned + frame <= before.len() {
+            self.heard_speech = rms(&before[self.scanned..self.scanned + frame]) >= threshold;
+            self.scanned += frame;
+        }
+
+        self.heard_speech && rms(tail) < threshold
+    }
+}
+
+/// How multi-channel input becomes the mono signal Whisper needs,
+/// e.g. `capture = "mono_average"` or `capture = { channel = 0 }`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Capture {
+    /// Average all channels
+    #[default]
+    MonoAverage,
+    /// Keep a single channel by zero-based index, for interfaces with one mic per channel
+    Channel(usize),
+}
+
+impl Capture {
+    fn downmix(&self, frame: &[f32]) -> f32 {
+        match self {
+            Capture::MonoAverage => frame.iter().sum::<f32>() / frame.len() as f32,
+            Capture::Channel(index) => frame.get(*index).copied().unwrap_or(0.0),
+        }
+    }
+
+    /// Errors when the selected channel doesn't exist on a device with `channels` channels
+    pub fn validate(&self, channels: usize, device_name: &str) -> Result<(), String> {
+        match self {
+            Capture::Channel(index) if *index >= channels => Err(format!(
+                "Capture channel {} doesn't exist, {} has {} channel(s) (0 to {})",
+                index,
+                device_name,
+                channels,
+                channels.saturating_sub(1)
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// State shared with the cpal input callback
+#[derive(Default)]
+struct CaptureBuffer {
+    samples: Mutex<Vec<f32>>,
+    paused: AtomicBool,
+    /// First stream error since recording started, e.g. the device was unplugged
+    error: Mutex<Option<String>>,
+    /// Sample count after which a one-time "still recording" warning is printed, 0 disables it
+    warn_after_samples: AtomicUsize,
+    warned: AtomicBool,
+    /// Set with `warned` and cleared once the hotkey loop has printed the warning
+    warning_due: AtomicBool,
+    /// Sample count at which capture stops, 0 for no limit
+    max_samples: AtomicUsize,
+    /// Set once frames were dropped for reaching `max_samples`
+    overflowed: AtomicBool,
+    mode: Mutex<Capture>,
+}
+
+impl CaptureBuffer {
+    fn record_error(&self, err: impl std::fmt::Display) {
+        eprintln!("Error in audio stream: {}", err);
+        self.error.lock().unwrap().get_or_insert_with(|| err.to_string());
+    }
+
+    fn push_frames<T>(&self, data: &[T], channels: usize)
+    where
+        T: Sample,
+        f32: cpal::FromSample<T>,
+    {
+        if self.paused.load(Ordering::Relaxed) {
+            return;
+        }
+
+        let mode = *self.mode.lock().unwrap();
+        let mut samples = self.samples.lock().unwrap();
+        let mut frame_buffer = Vec::with_capacity(channels);
+
+        // Checked under the samples lock, so concurrent callbacks can't overshoot the cap
+        let max_samples = self.max_samples.load(Ordering::Relaxed);
+
+        // Convert to mono
+        for frame in data.chunks(channels) {
+            // Reported from the hotkey loop, printing here could stall the real-time audio thread
+            if max_samples > 0 && samples.len() >= max_samples {
+                self.overflowed.store(true, Ordering::Relaxed);
+                break;
+            }
+            frame_buffer.clear();
+            frame_buffer.extend(frame.iter().map(|&s| f32::from_sample(s)));
+            samples.push(mode.downmix(&frame_buffer));
+        }
+
+        if self.passed_warning_threshold(samples.len()) {
+            self.warning_due.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// True exactly once per recording, when `len` first reaches the warning threshold
+    fn passed_warning_threshold(&self, len: usize) -> bool {
+        let threshold = self.warn_after_samples.load(Ordering::Relaxed);
+        threshold > 0 && len >= threshold && !self.warned.swap(true, Ordering::Relaxed)
+    }
+}
+
+/// Longest recording kept unless [`AudioRecorder::with_max_duration`] says otherwise
+pub const DEFAULT_MAX_RECORDING_SECS: u32 = 120;
+
 pub struct AudioRecorder {
-    samples: Arc<Mutex<Vec<f32>>>,
+    capture: Arc<CaptureBuffer>,
     stream: Option<cpal::Stream>,
     sample_rate: u32,
+    last_stats: Option<RecordingStats>,
+    warn_after_secs: u32,
+    max_duration_secs: u32,
+    /// Device to record from instead of the system default, see [`find_device`]
+    preferred_device: Option<String>,
+    /// Default input device used by the previous recording
+    device_name: Option<String>,
+    verbose: bool,
+    capture_mode: Capture,
+    /// Speech heard so far in the current recording, for [`poll_silence`](Self::poll_silence)
+    silence: SilenceDetector,
 }
 
 impl AudioRecorder {
-    pub fn new() -> Result<Self, Box<dyn Error>> {
+    pub fn new() -> Result<Self, ClipsterError> {
         Ok(Self {
-            samples: Arc::new(Mutex::new(Vec::new())),
+            capture: Arc::new(CaptureBuffer::default()),
             stream: None,
             sample_rate: 0,
+            last_stats: None,
+            warn_after_secs: 0,
+            max_duration_secs: DEFAULT_MAX_RECORDING_SECS,
+            preferred_device: None,
+            device_name: None,
+            verbose: false,
+            capture_mode: Capture::default(),
+            silence: SilenceDetector::default(),
         })
     }
 
-    pub fn start_recording(&mut self) -> Result<(), Box<dyn Error>> {
+    pub fn with_capture(mut self, capture: Capture) -> Self {
+        self.capture_mode = capture;
+        self
+    }
+
+    /// Record from the device with this name, or the only one containing it, instead of
+    /// following the system default
+    pub fn with_device_name(mut self, name: &str) -> Self {
+        self.preferred_device = Some(name.to_string());
+        self
+    }
+
+    /// Print the input device at the start of every recording
+    pub fn with_verbose(mut self, verbose: bool) -> Self {
+        self.verbose = verbose;
+        self
+    }
+
+    /// Print a single warning once a recording runs longer than this, without stopping it.
+    /// 0 disables the warning.
+    pub fn with_long_recording_warning(mut self, secs: u32) -> Self {
+        self.warn_after_secs = secs;
+        self
+    }
+
+    /// Stop keeping samples once a recording reaches this length, so a stuck hotkey can't use up
+    /// all memory. [`is_overflowed`](Self::is_overflowed) tells when it happened. 0 removes the limit.
+    pub fn with_max_duration(mut self, secs: u32) -> Self {
+        self.max_duration_secs = secs;
+        self
+    }
+
+    pub fn start_recording(&mut self) -> Result<(), ClipsterError> {
         let host = cpal::default_host();
-        let device = host
-            .default_input_device()
-            .ok_or("No input device available")?;
+        let device = match &self.preferred_device {
+            Some(name) => named_device(&host, name)?,
+            None => host
+                .default_input_device()
+                .ok_or_else(|| ClipsterError::Audio("No input device available".to_string()))?,
+        };
+
+        // The default device is resolved per recording, so plugging in a headset switches to it
+        let device_name = device.name().unwrap_or_else(|_| "unknown device".to_string());
+        if let Some(previous) = self.note_device(&device_name) {
+            println!("Input device changed: {} -> {}", previous, device_name);
+        }
+        if self.verbose {
+            println!("Recording from {}", device_name);
+        }
 
-        let config = device.default_input_config()?;
+        let config = device.default_input_config().map_err(audio_error)?;
+        self.capture_mode
+            .validate(config.channels() as usize, &device_name)
+            .map_err(ClipsterError::Audio)?;
         self.sample_rate = config.sample_rate().0;
 
-        self.samples.lock().unwrap().clear();
+        self.capture.samples.lock().unwrap().clear();
+        self.silence = SilenceDetector::default();
+        self.capture.paused.store(false, Ordering::Relaxed);
+        self.capture.error.lock().unwrap().take();
+        self.capture.warned.store(false, Ordering::Relaxed);
+        self.capture.warning_due.store(false, Ordering::Relaxed);
+        self.capture.overflowed.store(false, Ordering::Relaxed);
+        self.capture.max_samples.store(
+            self.max_duration_secs as usize * self.sample_rate as usize,
+            Ordering::Relaxed,
+        );
+        *self.capture.mode.lock().unwrap() = self.capture_mode;
+        self.capture.warn_after_samples.store(
+            self.warn_after_secs as usize * self.sample_rate as usize,
+            Ordering::Relaxed,
+        );
 
-        let samples = Arc::clone(&self.samples);
+        let capture = Arc::clone(&self.capture);
         let channels = config.channels() as usize;
 
+        // Every format converts to f32 through `FromSample`; I8, I64, U32 and U64 are rare enough
+        // in default input configs that they're still rejected
         let stream = match config.sample_format() {
-            cpal::SampleFormat::F32 => self.build_stream::<f32>(&device, &config.into(), samples, channels)?,
-            cpal::SampleFormat::I16 => self.build_stream::<i16>(&device, &config.into(), samples, channels)?,
-            cpal::SampleFormat::U16 => self.build_stream::<u16>(&device, &config.into(), samples, channels)?,
-            _ => return Err("Unsupported sample format".into()),
+            cpal::SampleFormat::F32 => self.build_stream::<f32>(&device, &config.into(), capture, channels)?,
+            cpal::SampleFormat::I16 => self.build_stream::<i16>(&device, &config.into(), capture, channels)?,
+            cpal::SampleFormat::U16 => self.build_stream::<u16>(&device, &config.into(), capture, channels)?,
+            cpal::SampleFormat::I32 => self.build_stream::<i32>(&device, &config.into(), capture, channels)?,
+            cpal::SampleFormat::U8 => self.build_stream::<u8>(&device, &config.into(), capture, channels)?,
+            cpal::SampleFormat::F64 => self.build_stream::<f64>(&device, &config.into(), capture, channels)?,
+            format => return Err(ClipsterError::Audio(format!("Unsupported sample format {format}"))),
         };
 
-        stream.play()?;
+        stream.play().map_err(audio_error)?;
         self.stream = Some(stream);
 
         Ok(())
@@ -48,53 +310,494 @@ impl AudioRecorder {
         &self,
         device: &cpal::Device,
         config: &cpal::StreamConfig,
-        samples: Arc<Mutex<Vec<f32>>>,
+        capture: Arc<CaptureBuffer>,
         channels: usize,
-    ) -> Result<cpal::Stream, Box<dyn Error>>
+    ) -> Result<cpal::Stream, ClipsterError>
     where
         T: Sample + SizedSample,
         f32: cpal::FromSample<T>,
     {
-        let err_fn = |err| eprintln!("Error in audio stream: {}", err);
+        let error_capture = Arc::clone(&capture);
+        let err_fn = move |err: cpal::StreamError| error_capture.record_error(err);
 
         let stream = device.build_input_stream(
             config,
             move |data: &[T], _: &cpal::InputCallbackInfo| {
-                let mut samples = samples.lock().unwrap();
-
-                // Convert to mono by averaging channels
-                for frame in data.chunks(channels) {
-                    let mono_sample: f32 = frame
-                        .iter()
-                        .map(|&s| f32::from_sample(s))
-                        .sum::<f32>()
-                        / channels as f32;
-                    samples.push(mono_sample);
-                }
+                capture.push_frames(data, channels);
             },
             err_fn,
             None,
-        )?;
+        ).map_err(audio_error)?;
 
         Ok(stream)
     }
 
-    pub fn stop_recording(&mut self) -> Result<Vec<f32>, Box<dyn Error>> {
+    /// Stops the stream and returns the mono samples, or an error if the device failed
+    /// mid-recording since the buffer is then likely truncated or garbled
+    pub fn stop_recording(&mut self) -> Result<Vec<f32>, ClipsterError> {
         if let Some(stream) = self.stream.take() {
             drop(stream);
         }
 
-        let samples = self.samples.lock().unwrap().clone();
+        if let Some(err) = self.capture.error.lock().unwrap().take() {
+            self.last_stats = None;
+            return Err(ClipsterError::Audio(format!("Recording was interrupted by a device error: {}", err)));
+        }
+
+        let samples = self.capture.samples.lock().unwrap().clone();
+        self.last_stats = Some(RecordingStats::from_samples(&samples, self.sample_rate));
 
         Ok(samples)
     }
 
+    /// Remembers the device for this recording, returning the previous one if it differs
+    fn note_device(&mut self, name: &str) -> Option<String> {
+        match self.device_name.replace(name.to_string()) {
+            Some(previous) if previous != name => Some(previous),
+            _ => None,
+        }
+    }
+
+    /// Name of the input device used by the latest recording
+    pub fn device_name(&self) -> Option<&str> {
+        self.device_name.as_deref()
+    }
+
+    /// Stats of the buffer returned by the last `stop_recording`
+    pub fn last_stats(&self) -> Option<RecordingStats> {
+        self.last_stats
+    }
+
+    /// Stops appending captured audio to the buffer while keeping the stream open
+    pub fn pause(&self) {
+        self.capture.paused.store(true, Ordering::Relaxed);
+    }
+
+    pub fn resume(&self) {
+        self.capture.paused.store(false, Ordering::Relaxed);
+    }
+
+    /// Whether the current or last recording hit the maximum duration and lost its end
+    pub fn is_overflowed(&self) -> bool {
+        self.capture.overflowed.load(Ordering::Relaxed)
+    }
+
+    /// True once per recording after it runs past [`with_long_recording_warning`](Self::with_long_recording_warning)
+    pub fn take_long_recording_warning(&self) -> bool {
+        self.capture.warning_due.swap(false, Ordering::Relaxed)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.capture.paused.load(Ordering::Relaxed)
+    }
+
     pub fn get_sample_rate(&self) -> u32 {
         self.sample_rate
     }
 
+    /// Whether the recording so far ends in `window_ms` of silence, see [`ends_in_silence`].
+    /// Always false while paused.
+    pub fn poll_silence(&mut self, window_ms: u32, threshold: f32) -> bool {
+        if self.is_paused() {
+            return false;
+        }
+
+        let samples = self.capture.samples.lock().unwrap();
+        self.silence.ends_in_silence(&samples, self.sample_rate, window_ms, threshold)
+    }
+
     #[allow(dead_code)]
     pub fn is_recording(&self) -> bool {
         self.stream.is_some()
     }
 }
+
+/// Names of the available input devices and of the default one, if any
+pub fn input_devices() -> Result<(Vec<String>, Option<String>), ClipsterError> {
+    let host = cpal::default_host();
+    let default = host.default_input_device().and_then(|device| device.name().ok());
+    let names = host
+        .input_devices()
+        .map_err(audio_error)?
+        .map(|device| device.name().unwrap_or_else(|_| "unknown device".to_string()))
+        .collect();
+
+    Ok((names, default))
+}
+
+/// Names of the available input devices
+pub fn list_input_devices() -> Result<Vec<String>, ClipsterError> {
+    input_devices().map(|(names, _)| names)
+}
+
+/// What `--list-devices` shows about one input device
+#[derive(Debug, Clone, PartialEq)]
+pub struct InputDeviceInfo {
+    pub name: String,
+    pub is_default: bool,
+    /// `None` when the device couldn't be asked for its default config
+    pub default_sample_rate: Option<u32>,
+    /// Formats across the supported configs, empty when they couldn't be queried
+    pub sample_formats: Vec<String>,
+}
+
+impl InputDeviceInfo {
+    fn from_device(device: &cpal::Device, default_name: Option<&str>) -> Self {
+        let name = device.name().unwrap_or_else(|_| "unknown device".to_string());
+        // Some devices (busy or half-unplugged ones, mostly) fail these queries but still list fine
+        let default_sample_rate = device.default_input_config().ok().map(|config| config.sample_rate().0);
+        let mut sample_formats: Vec<String> = Vec::new();
+        for config in device.supported_input_configs().into_iter().flatten() {
+            let format = config.sample_format().to_string();
+            if !sample_formats.contains(&format) {
+                sample_formats.push(format);
+            }
+        }
+
+        Self {
+            is_default: default_name == Some(name.as_str()),
+            name,
+            default_sample_rate,
+            sample_formats,
+        }
+    }
+
+    /// `index: name (default), 48000 Hz, f32, i16`
+    pub fn describe(&self, index: usize) -> String {
+        let mut line = format!("{}: {}", index, self.name);
+        if self.is_default {
+            line.push_str(" (default)");
+        }
+        match self.default_sample_rate {
+            Some(rate) => line.push_str(&format!(", {} Hz", rate)),
+            None => line.push_str(", sample rate unknown"),
+        }
+        if !self.sample_formats.is_empty() {
+            line.push_str(&format!(", {}", self.sample_formats.join(", ")));
+        }
+        line
+    }
+}
+
+/// The input devices of the host recordings use with their formats, in the order `[audio] device`
+/// searches them
+pub fn list_input_device_info() -> Result<Vec<InputDeviceInfo>, ClipsterError> {
+    let host = cpal::default_host();
+    let default = host.default_input_device().and_then(|device| device.name().ok());
+    let devices = host
+        .input_devices()
+        .map_err(audio_error)?
+        .map(|device| InputDeviceInfo::from_device(&device, default.as_deref()))
+        .collect();
+
+    Ok(devices)
+}
+
+fn named_device(host: &cpal::Host, name: &str) -> Result<cpal::Device, ClipsterError> {
+    let devices: Vec<cpal::Device> = host.input_devices().map_err(audio_error)?.collect();
+    let names: Vec<String> = devices
+        .iter()
+        .map(|device| device.name().unwrap_or_else(|_| "unknown device".to_string()))
+        .collect();
+
+    let index = find_device(&names, name).map_err(ClipsterError::Audio)?;
+    Ok(devices.into_iter().nth(index).expect("index from find_device"))
+}
+
+/// Index of the device called `wanted`, or else the only one whose name contains it
+/// (ignoring case). The error lists the devices to choose from.
+pub fn find_device(names: &[String], wanted: &str) -> Result<usize, String> {
+    if let Some(index) = names.iter().position(|name| name == wanted) {
+        return Ok(index);
+    }
+
+    let wanted_lower = wanted.to_lowercase();
+    let matches: Vec<usize> = names
+        .iter()
+        .enumerate()
+        .filter(|(_, name)| name.to_lowercase().contains(&wanted_lower))
+        .map(|(index, _)| index)
+        .collect();
+
+    match matches.as_slice() {
+        [index] => Ok(*index),
+        [] if names.is_empty() => Err(format!("No input device matches \"{}\", no input devices found", wanted)),
+        [] => Err(format!("No input device matches \"{}\", available: {}", wanted, names.join(", "))),
+        _ => {
+            let ambiguous: Vec<&str> = matches.iter().map(|&index| names[index].as_str()).collect();
+            Err(format!("\"{}\" matches several input devices: {}", wanted, ambiguous.join(", ")))
+        }
+    }
+}
+
+fn audio_error(error: impl Display) -> ClipsterError {
+    ClipsterError::Audio(error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recording_stats_from_samples() {
+        // Half a second at 8kHz, alternating +-0.5 with a single 0.8 spike
+        let mut samples: Vec<f32> = (0..4000).map(|i| if i % 2 == 0 { 0.5 } else { -0.5 }).collect();
+        samples[10] = 0.8;
+
+        let stats = RecordingStats::from_samples(&samples, 8000);
+
+        assert_eq!(stats.sample_count, 4000);
+        assert!((stats.duration_secs - 0.5).abs() < 1e-6);
+        assert!((stats.peak - 0.8).abs() < 1e-6);
+        assert!((stats.rms - 0.5).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_recording_stats_empty() {
+        assert_eq!(RecordingStats::from_samples(&[], 48000), RecordingStats::default());
+        assert_eq!(RecordingStats::from_samples(&[0.5], 0), RecordingStats::default());
+    }
+
+    fn tone(len: usize, amplitude: f32) -> Vec<f32> {
+        (0..len).map(|i| if i % 2 == 0 { amplitude } else { -amplitude }).collect()
+    }
+
+    #[test]
+    fn test_ends_in_silence_after_speech() {
+        let mut samples = tone(16000, 0.3);
+        samples.extend(tone(1600, 0.001));
+        assert!(!ends_in_silence(&samples, 16000, 150, 0.01));
+
+        samples.extend(tone(24000, 0.001));
+        assert!(ends_in_silence(&samples, 16000, 1500, 0.01));
+        assert!(!ends_in_silence(&samples, 16000, 1500, 0.0005));
+    }
+
+    #[test]
+    fn test_ends_in_silence_needs_speech_first() {
+        let quiet = tone(48000, 0.001);
+        assert!(!ends_in_silence(&quiet, 16000, 1500, 0.01));
+    }
+
+    #[test]
+    fn test_ends_in_silence_short_buffers() {
+        assert!(!ends_in_silence(&[], 16000, 1500, 0.01));
+        assert!(!ends_in_silence(&tone(100, 0.3), 16000, 1500, 0.01));
+        assert!(!ends_in_silence(&tone(100, 0.3), 0, 1500, 0.01));
+    }
+
+    #[test]
+    fn test_silence_detector_scans_each_frame_once() {
+        let mut detector = SilenceDetector::default();
+        let mut samples = tone(16000, 0.001);
+        assert!(!detector.ends_in_silence(&samples, 16000, 500, 0.01));
+        assert_eq!(detector.scanned, 8000);
+
+        samples.extend(tone(12000, 0.3));
+        assert!(!detector.ends_in_silence(&samples, 16000, 500, 0.01));
+        assert!(detector.heard_speech);
+
+        // Speech was found, later polls only look at the trailing window
+        let scanned = detector.scanned;
+        samples.extend(tone(8000, 0.001));
+        assert!(detector.ends_in_silence(&samples, 16000, 500, 0.01));
+        assert_eq!(detector.scanned, scanned);
+    }
+
+    #[test]
+    fn test_poll_silence_reads_capture_buffer() {
+        let mut recorder = AudioRecorder::new().unwrap();
+        recorder.sample_rate = 16000;
+        {
+            let mut samples = recorder.capture.samples.lock().unwrap();
+            samples.extend(tone(8000, 0.3));
+            samples.extend(tone(16000, 0.0));
+        }
+
+        assert!(recorder.poll_silence(1000, 0.01));
+        recorder.pause();
+        assert!(!recorder.poll_silence(1000, 0.01));
+    }
+
+    #[test]
+    fn test_push_frames_downmixes_to_mono() {
+        let capture = CaptureBuffer::default();
+        capture.push_frames(&[0.2f32, 0.4, -0.5, 0.5], 2);
+
+        let samples = capture.samples.lock().unwrap();
+        assert_eq!(samples.len(), 2);
+        assert!((samples[0] - 0.3).abs() < 1e-6);
+        assert!(samples[1].abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_push_frames_keeps_selected_channel() {
+        let capture = CaptureBuffer::default();
+        *capture.mode.lock().unwrap() = Capture::Channel(1);
+        capture.push_frames(&[0.2f32, 0.4, -0.5, 0.5], 2);
+
+        assert_eq!(*capture.samples.lock().unwrap(), vec![0.4, 0.5]);
+    }
+
+    #[test]
+    fn test_capture_validate_channel_index() {
+        assert!(Capture::MonoAverage.validate(1, "mic").is_ok());
+        assert!(Capture::Channel(1).validate(2, "mic").is_ok());
+        assert_eq!(
+            Capture::Channel(2).validate(2, "USB interface").unwrap_err(),
+            "Capture channel 2 doesn't exist, USB interface has 2 channel(s) (0 to 1)"
+        );
+    }
+
+    #[test]
+    fn test_push_frames_converts_integer_samples() {
+        let capture = CaptureBuffer::default();
+        capture.push_frames(&[i16::MAX, 0], 1);
+
+        let samples = capture.samples.lock().unwrap();
+        assert!((samples[0] - 1.0).abs() < 1e-3);
+        assert_eq!(samples[1], 0.0);
+    }
+
+    #[test]
+    fn test_push_frames_converts_wide_and_unsigned_samples() {
+        let capture = CaptureBuffer::default();
+        capture.push_frames(&[i32::MAX], 1);
+        capture.push_frames(&[128u8, 255], 1);
+        capture.push_frames(&[-0.5f64], 1);
+
+        let samples = capture.samples.lock().unwrap();
+        assert!((samples[0] - 1.0).abs() < 1e-3);
+        assert!(samples[1].abs() < 1e-6);
+        assert!((samples[2] - 1.0).abs() < 1e-2);
+        assert!((samples[3] + 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_note_device_reports_changes_only() {
+        let mut recorder = AudioRecorder::new().unwrap();
+
+        assert_eq!(recorder.note_device("Built-in Microphone"), None);
+        assert_eq!(recorder.note_device("Built-in Microphone"), None);
+        assert_eq!(recorder.note_device("USB Headset"), Some("Built-in Microphone".to_string()));
+        assert_eq!(recorder.device_name(), Some("USB Headset"));
+    }
+
+    #[test]
+    fn test_long_recording_warning_fires_once() {
+        let capture = CaptureBuffer::default();
+        capture.warn_after_samples.store(4, Ordering::Relaxed);
+
+        assert!(!capture.passed_warning_threshold(3));
+        assert!(capture.passed_warning_threshold(4));
+        assert!(!capture.passed_warning_threshold(5));
+
+        capture.push_frames(&[0.0f32; 8], 1);
+        assert!(capture.warned.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_long_recording_warning_is_taken_once() {
+        let recorder = AudioRecorder::new().unwrap();
+        recorder.capture.warn_after_samples.store(4, Ordering::Relaxed);
+
+        assert!(!recorder.take_long_recording_warning());
+        recorder.capture.push_frames(&[0.0f32; 8], 1);
+        recorder.capture.push_frames(&[0.0f32; 8], 1);
+        assert!(recorder.take_long_recording_warning());
+        assert!(!recorder.take_long_recording_warning());
+    }
+
+    #[test]
+    fn test_find_device() {
+        let names: Vec<String> = ["Built-in Microphone", "USB Audio Device", "USB Webcam Mic"]
+            .iter()
+            .map(|name| name.to_string())
+            .collect();
+
+        assert_eq!(find_device(&names, "USB Audio Device"), Ok(1));
+        assert_eq!(find_device(&names, "webcam"), Ok(2));
+
+        let ambiguous = find_device(&names, "usb").unwrap_err();
+        assert!(ambiguous.contains("USB Audio Device, USB Webcam Mic"));
+
+        let missing = find_device(&names, "Yeti").unwrap_err();
+        assert!(missing.ends_with("available: Built-in Microphone, USB Audio Device, USB Webcam Mic"));
+        assert!(find_device(&[], "Yeti").unwrap_err().contains("no input devices found"));
+    }
+
+    #[test]
+    fn test_input_device_info_describe() {
+        let mut info = InputDeviceInfo {
+            name: "USB Mic".to_string(),
+            is_default: true,
+            default_sample_rate: Some(48000),
+            sample_formats: vec!["f32".to_string(), "i16".to_string()],
+        };
+        assert_eq!(info.describe(0), "0: USB Mic (default), 48000 Hz, f32, i16");
+
+        info.is_default = false;
+        info.default_sample_rate = None;
+        info.sample_formats.clear();
+        assert_eq!(info.describe(2), "2: USB Mic, sample rate unknown");
+    }
+
+    #[test]
+    fn test_push_frames_stops_at_max_samples() {
+        let capture = CaptureBuffer::default();
+        capture.max_samples.store(5, Ordering::Relaxed);
+
+        capture.push_frames(&[0.1f32; 4], 1);
+        assert!(!capture.overflowed.load(Ordering::Relaxed));
+
+        capture.push_frames(&[0.2f32, 0.2, 0.3, 0.3, 0.4, 0.4], 2);
+        assert_eq!(*capture.samples.lock().unwrap(), vec![0.1, 0.1, 0.1, 0.1, 0.2]);
+        assert!(capture.overflowed.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_push_frames_without_max_samples_keeps_everything() {
+        let capture = CaptureBuffer::default();
+        capture.push_frames(&[0.0f32; 1000], 1);
+
+        assert_eq!(capture.samples.lock().unwrap().len(), 1000);
+        assert!(!capture.overflowed.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_long_recording_warning_disabled_by_default() {
+        let capture = CaptureBuffer::default();
+        assert!(!capture.passed_warning_threshold(usize::MAX));
+    }
+
+    #[test]
+    fn test_stop_recording_reports_stream_error() {
+        let mut recorder = AudioRecorder::new().unwrap();
+        recorder.capture.push_frames(&[0.1f32, 0.2], 1);
+        recorder.capture.record_error("device unplugged");
+        recorder.capture.record_error("later error");
+
+        let err = recorder.stop_recording().unwrap_err();
+        assert_eq!(err.to_string(), "Recording was interrupted by a device error: device unplugged");
+        assert_eq!(recorder.last_stats(), None);
+
+        // The error is consumed so the next recording starts clean
+        assert_eq!(recorder.stop_recording().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_push_frames_ignored_while_paused() {
+        let recorder = AudioRecorder::new().unwrap();
+        recorder.capture.push_frames(&[0.1f32, 0.2], 1);
+
+        recorder.pause();
+        assert!(recorder.is_paused());
+        recorder.capture.push_frames(&[0.3f32, 0.4, 0.5], 1);
+        assert_eq!(recorder.capture.samples.lock().unwrap().len(), 2);
+
+        recorder.resume();
+        assert!(!recorder.is_paused());
+        recorder.capture.push_frames(&[0.6f32], 1);
+        assert_eq!(*recorder.capture.samples.lock().unwrap(), vec![0.1, 0.2, 0.6]);
+    }
+}
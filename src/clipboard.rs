@@ -0,0 +1,249 @@
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+use crate::ai::local::command::ExternalCommand;
+
+/// Where responses end up, so clipster still works where arboard can't reach a clipboard
+pub trait ClipboardBackend: Send {
+    fn name(&self) -> String;
+    fn set_text(&mut self, text: &str) -> Result<(), String>;
+    fn get_text(&mut self) -> Result<String, String>;
+}
+
+/// Backend as named in the config, e.g. `clipboard = "xclip"` or `clipboard = { file = "/tmp/clipster.txt" }`
+#[derive(Debug, Clone, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClipboardKind {
+    /// arboard, falling back to a clipboard command and finally stdout, see [`detect`]
+    #[default]
+    Auto,
+    Arboard,
+    /// `wl-copy`/`wl-paste` for Wayland compositors arboard doesn't support
+    WlClipboard,
+    Xclip,
+    Xsel,
+    /// Print responses, for headless use
+    Stdout,
+    /// Overwrite a file with each response
+    File(PathBuf),
+}
+
+impl ClipboardKind {
+    pub fn open(&self) -> Result<Box<dyn ClipboardBackend>, String> {
+        let backend: Box<dyn ClipboardBackend> = match self {
+            ClipboardKind::Auto => return Ok(detect()),
+            ClipboardKind::Arboard => Box::new(Arboard::new()?),
+            ClipboardKind::WlClipboard => Box::new(CommandClipboard::wl_clipboard()),
+            ClipboardKind::Xclip => Box::new(CommandClipboard::xclip()),
+            ClipboardKind::Xsel => Box::new(CommandClipboard::xsel()),
+            ClipboardKind::Stdout => Box::new(Stdout::default()),
+            ClipboardKind::File(path) => Box::new(FileSink::new(path)),
+        };
+
+        Ok(backend)
+    }
+}
+
+/// Tries arboard first, then whichever clipboard command fits the session, then stdout
+pub fn detect() -> Box<dyn ClipboardBackend> {
+    let arboard_error = match Arboard::new() {
+        Ok(arboard) => return Box::new(arboard),
+        Err(e) => e,
+    };
+
+    let fallback: Box<dyn ClipboardBackend> = if std::env::var_os("WAYLAND_DISPLAY").is_some() && on_path("wl-copy") {
+        Box::new(CommandClipboard::wl_clipboard())
+    } else if on_path("xclip") {
+        Box::new(CommandClipboard::xclip())
+    } else if on_path("xsel") {
+        Box::new(CommandClipboard::xsel())
+    } else {
+        Box::new(Stdout::default())
+    };
+
+    eprintln!("Clipboard unavailable ({}), using {} instead", arboard_error, fallback.name());
+    fallback
+}
+
+fn on_path(program: &str) -> bool {
+    std::env::var_os("PATH")
+        .is_some_and(|paths| std::env::split_paths(&paths).any(|dir| dir.join(program).is_file()))
+}
+
+pub struct Arboard(arboard::Clipboard);
+
+impl Arboard {
+    pub fn new() -> Result<Self, String> {
+        arboard::Clipboard::new().map(Self).map_err(|e| e.to_string())
+    }
+}
+
+impl ClipboardBackend for Arboard {
+    fn name(&self) -> String {
+        "arboard".to_string()
+    }
+
+    fn set_text(&mut self, text: &str) -> Result<(), String> {
+        self.0.set_text(text).map_err(|e| e.to_string())
+    }
+
+    fn get_text(&mut self) -> Result<String, String> {
+        self.0.get_text().map_err(|e| e.to_string())
+    }
+}
+
+/// Copies by piping to one program and pastes by reading another's stdout
+pub struct CommandClipboard {
+    copy: ExternalCommand,
+    paste: ExternalCommand,
+    name: String,
+}
+
+impl CommandClipboard {
+    pub fn new(name: &str, copy: ExternalCommand, paste: ExternalCommand) -> Self {
+        Self {
+            copy,
+            paste,
+            name: name.to_string(),
+        }
+    }
+
+    pub fn wl_clipboard() -> Self {
+        Self::new(
+            "wl-clipboard",
+            ExternalCommand::new("wl-copy", &[]),
+            ExternalCommand::new("wl-paste", &args(&["--no-newline"])),
+        )
+    }
+
+    pub fn xclip() -> Self {
+        Self::new(
+            "xclip",
+            ExternalCommand::new("xclip", &args(&["-selection", "clipboard"])),
+            ExternalCommand::new("xclip", &args(&["-selection", "clipboard", "-o"])),
+        )
+    }
+
+    pub fn xsel() -> Self {
+        Self::new(
+            "xsel",
+            ExternalCommand::new("xsel", &args(&["--clipboard", "--input"])),
+            ExternalCommand::new("xsel", &args(&["--clipboard", "--output"])),
+        )
+    }
+}
+
+fn args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg| arg.to_string()).collect()
+}
+
+impl ClipboardBackend for CommandClipboard {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn set_text(&mut self, text: &str) -> Result<(), String> {
+        self.copy.feed(text)
+    }
+
+    fn get_text(&mut self) -> Result<String, String> {
+        self.paste.run("")
+    }
+}
+
+/// Prints each response between markers, `get_text` returns the last one
+#[derive(Default)]
+pub struct Stdout {
+    last: String,
+}
+
+impl ClipboardBackend for Stdout {
+    fn name(&self) -> String {
+        "stdout".to_string()
+    }
+
+    fn set_text(&mut self, text: &str) -> Result<(), String> {
+        println!("---- Clipboard ----\n{}\n-------------------", text);
+        self.last = text.to_string();
+        Ok(())
+    }
+
+    fn get_text(&mut self) -> Result<String, String> {
+        Ok(self.last.clone())
+    }
+}
+
+pub struct FileSink {
+    path: PathBuf,
+}
+
+impl FileSink {
+    pub fn new(path: &Path) -> Self {
+        Self { path: path.to_path_buf() }
+    }
+}
+
+impl ClipboardBackend for FileSink {
+    fn name(&self) -> String {
+        format!("file {}", self.path.display())
+    }
+
+    fn set_text(&mut self, text: &str) -> Result<(), String> {
+        std::fs::write(&self.path, text).map_err(|e| format!("Failed to write {}: {}", self.path.display(), e))
+    }
+
+    fn get_text(&mut self) -> Result<String, String> {
+        std::fs::read_to_string(&self.path).map_err(|e| format!("Failed to read {}: {}", self.path.display(), e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_sink_round_trip() {
+        let path = std::env::temp_dir().join(format!("clipster-clipboard-{}.txt", std::process::id()));
+        let mut sink = FileSink::new(&path);
+
+        sink.set_text("ls -la").unwrap();
+        let text = sink.get_text();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(text.unwrap(), "ls -la");
+    }
+
+    #[test]
+    fn test_stdout_remembers_last_text() {
+        let mut stdout = Stdout::default();
+        stdout.set_text("first").unwrap();
+        stdout.set_text("second").unwrap();
+
+        assert_eq!(stdout.get_text().unwrap(), "second");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_command_clipboard_runs_copy_and_paste() {
+        let mut clipboard = CommandClipboard::new(
+            "test",
+            ExternalCommand::new("sh", &args(&["-c", "cat > /dev/null"])),
+            ExternalCommand::new("printf", &args(&["pasted"])),
+        );
+
+        clipboard.set_text("copied").unwrap();
+        assert_eq!(clipboard.get_text().unwrap(), "pasted");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_command_clipboard_reports_missing_program() {
+        let mut clipboard = CommandClipboard::new(
+            "test",
+            ExternalCommand::new("clipster-no-such-copy", &[]),
+            ExternalCommand::new("clipster-no-such-paste", &[]),
+        );
+
+        assert!(clipboard.set_text("x").unwrap_err().contains("clipster-no-such-copy"));
+    }
+}
@@ -0,0 +1,1105 @@
+use clipster::audio_processing::{Normalization, ResampleQuality, DEFAULT_TARGET_RMS, TRIM_PADDING_MS};
+use clipster::ai::remote::traits::Roles;
+use clipster::audio_recorder::{Capture, DEFAULT_MAX_RECORDING_SECS};
+use clipster::clipboard::ClipboardKind;
+use clipster::metrics::Price;
+use clipster::postprocess::PostProcessStep;
+use global_hotkey::hotkey::HotKey;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer};
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+/// Contents of `~/.config/clipster/config.toml`. Every field is optional.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub commands: Vec<CommandConfig>,
+    pub external: Vec<ExternalConfig>,
+    /// Only set up these providers even when other keys are present, overridden by `--providers`
+    pub providers: Option<Vec<String>>,
+    /// Per-provider model overrides, e.g. `mistral = "mistral-small-latest"`
+    pub models: HashMap<String, String>,
+    /// Per-provider hotkey overrides, e.g. `anthropic = "Ctrl+Alt+A"`
+    pub hotkeys: HashMap<String, String>,
+    /// Per-provider extra request headers, e.g. `[headers.openai]` with `X-Tenant = "acme"`.
+    /// `[headers.all]` is sent to every provider that supports custom headers.
+    pub headers: HashMap<String, HashMap<String, String>>,
+    /// Role names for OpenAI-compatible servers that differ, e.g. `[roles.mistral]` with `system = "developer"`
+    pub roles: HashMap<String, Roles>,
+    /// USD per million tokens by `provider:model` or model name, for the session cost estimate
+    pub prices: HashMap<String, Price>,
+    /// What to do with the transcript when the AI request fails
+    pub on_ai_error: AiErrorFallback,
+    /// `User-Agent` for API requests, defaults to `clipster/<version>`
+    pub user_agent: Option<String>,
+    /// Response length cap for every provider. Leaving it out, `0` or `"default"` sends no cap
+    /// (Anthropic requires one and gets the model's maximum).
+    #[serde(deserialize_with = "deserialize_max_tokens")]
+    pub max_tokens: Option<u32>,
+    /// Responses end before the first of these, e.g. `"\n```"` for just the first code block.
+    /// Sent to Anthropic and OpenAI-compatible providers (OpenAI takes at most 4).
+    pub stop_sequences: Vec<String>,
+    /// Seconds an API request may spend connecting or waiting for more of the response before it's
+    /// abandoned, a long answer that keeps arriving isn't cut off. Defaults to 30, 120 for Ollama.
+    pub timeout_secs: Option<u64>,
+    /// Retries for rate limits (429) and server errors (500, 502, 503) with exponential
+    /// backoff, before `[queue]` retries the whole job. Defaults to 2.
+    pub api_retries: Option<u32>,
+    /// Touch each provider's API this often so the first request after a break
+    /// doesn't pay for a new TLS connection. Off by default, at least 30.
+    pub keep_alive_secs: Option<u64>,
+    /// `auto`, `arboard`, `wl_clipboard`, `xclip`, `xsel`, `stdout` or `{ file = "path" }`
+    pub clipboard: ClipboardKind,
+    /// Average all input channels or keep one, e.g. `capture = { channel = 0 }`
+    pub capture: Capture,
+    /// Replaces the built-in system prompt, overridden by `--system-prompt-file`. Relative to the config directory.
+    pub system_prompt_file: Option<String>,
+    /// Named system prompts to switch between with Ctrl+Shift+R, after the `default` one
+    pub profiles: Vec<ProfileConfig>,
+    /// Profile to start with, overridden by `--profile`
+    pub profile: Option<String>,
+    pub audio: AudioConfig,
+    pub anthropic: AnthropicConfig,
+    pub openai: OpenAIConfig,
+    pub azure: AzureConfig,
+    pub whisper: WhisperConfig,
+    pub output: OutputConfig,
+    pub dictation: DictationConfig,
+    pub local_llm: LocalLlmConfig,
+    pub fallback: FallbackConfig,
+    pub redaction: RedactionConfig,
+    pub queue: QueueConfig,
+    pub history: HistoryConfig,
+}
+
+/// Earlier exchanges: sent along with new prompts (per provider), and logged to disk for `--history`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct HistoryConfig {
+    /// Exchanges remembered per provider, 0 (the default) sends every prompt on its own
+    pub max_turns: usize,
+    /// Append every response and its (redacted) transcript to [`Config::history_path`], off by default.
+    /// Responses that fail to copy are appended either way.
+    pub save: bool,
+    /// Entries kept in the log, older ones are dropped. 0 keeps everything.
+    pub max_saved: usize,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            max_turns: 0,
+            save: false,
+            max_saved: 1000,
+        }
+    }
+}
+
+/// Recordings wait here while earlier ones are transcribed and sent
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct QueueConfig {
+    /// Recordings beyond this many waiting are dropped with a warning
+    pub capacity: usize,
+    /// Extra tries for an AI request that failed offline, timed out, was rate-limited or hit a
+    /// server error. Other failures, like a bad key, are reported straight away.
+    pub retry_attempts: u32,
+    pub retry_delay_secs: u64,
+}
+
+impl Default for QueueConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 8,
+            retry_attempts: 2,
+            retry_delay_secs: 5,
+        }
+    }
+}
+
+/// GGUF model run in-process, needs a build with `--features local-llm`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LocalLlmConfig {
+    pub model_path: Option<String>,
+    pub hotkey: Option<String>,
+    pub max_tokens: u32,
+    /// Prompt plus response, in tokens
+    pub context_size: u32,
+}
+
+impl Default for LocalLlmConfig {
+    fn default() -> Self {
+        Self {
+            model_path: None,
+            hotkey: None,
+            max_tokens: 500,
+            context_size: 2048,
+        }
+    }
+}
+
+/// Hotkey that tries providers in order until one answers
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct FallbackConfig {
+    pub hotkey: Option<String>,
+    /// Provider names in the order they're tried, by default every configured provider
+    pub providers: Vec<String>,
+}
+
+/// Replaces matches in the transcript with `[REDACTED]` before it's sent to a provider
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct RedactionConfig {
+    pub api_keys: bool,
+    pub emails: bool,
+    /// Extra regular expressions
+    pub patterns: Vec<String>,
+}
+
+/// Hotkey that copies the transcript itself, without any AI
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct DictationConfig {
+    pub hotkey: Option<String>,
+    /// Capitalize sentences and add a final period, Whisper's punctuation can be inconsistent
+    pub restore_punctuation: bool,
+}
+
+impl Default for DictationConfig {
+    fn default() -> Self {
+        Self {
+            hotkey: None,
+            restore_punctuation: true,
+        }
+    }
+}
+
+/// How responses are delivered
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct OutputConfig {
+    /// Show the transcript in the notification instead of a fixed message
+    pub notify_transcript: bool,
+    /// End clipboard content with a newline, some editors paste it as a new line or re-indent
+    pub trailing_newline: bool,
+    /// Characters of the response printed to the terminal after copying
+    pub preview_chars: usize,
+    /// Processors applied to every response in order, by default only fence stripping
+    pub post_process: Vec<PostProcessStep>,
+    /// Copy this instead of the bare response, with `{transcript}` and `{response}` filled in
+    pub clipboard_template: Option<String>,
+    /// Copy the raw transcript as soon as it's ready, then replace it with the response
+    pub transcript_first: bool,
+    /// Print the response while it's generated, the clipboard still gets it once complete
+    pub stream_response: bool,
+    /// Paste into the focused window after copying, needs a build with `--features auto-paste`
+    pub auto_paste: bool,
+    /// Wait between setting the clipboard and pressing paste
+    pub paste_delay_ms: u64,
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        Self {
+            notify_transcript: false,
+            trailing_newline: false,
+            preview_chars: 100,
+            post_process: vec![PostProcessStep::StripFences],
+            clipboard_template: None,
+            transcript_first: false,
+            stream_response: false,
+            auto_paste: false,
+            paste_delay_ms: 150,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct WhisperConfig {
+    /// Path to the ggml model file (relative to the working directory or config dir, `~` expanded),
+    /// or a model name like `tiny.en` searched for in the usual cache locations
+    pub model_path: String,
+    /// Language to start with, like `en` or `de`, or `auto` to let Whisper detect it
+    pub language: String,
+    /// Decoding threads, one per logical CPU (not physical core) when unset
+    pub threads: Option<i32>,
+    /// Translate speech in other languages to English text before it's sent
+    pub translate: bool,
+    /// Languages cycled through by the language hotkey, `auto` for auto-detection
+    pub language_cycle: Vec<String>,
+    /// Print each segment while transcribing instead of waiting for the whole transcript
+    pub partial_results: bool,
+    /// Mean token probability (0-1) below which a response is only copied after Ctrl+Shift+Y
+    pub min_confidence: Option<f32>,
+    /// Decode each recording as one segment, faster for short commands
+    pub single_segment: bool,
+    /// Drop `[BLANK_AUDIO]` and non-speech tokens like music notes from transcripts
+    pub suppress_blank: bool,
+    pub suppress_non_speech_tokens: bool,
+    /// Context the decoder starts from, e.g. "A programming dictation about Rust and tokio."
+    pub initial_prompt: String,
+    /// Names and jargon to spell as given, added to the initial prompt
+    pub vocabulary: Vec<String>,
+    /// Transcribe long recordings in windows of this many seconds instead of one pass
+    pub chunk_secs: Option<f32>,
+    /// Seconds each window repeats of the previous one, so words at the edges aren't cut
+    pub chunk_overlap_secs: f32,
+}
+
+impl WhisperConfig {
+    /// `initial_prompt` followed by the vocabulary, empty when neither is set
+    pub fn decoder_prompt(&self) -> String {
+        let mut prompt = self.initial_prompt.trim().to_string();
+        let words: Vec<&str> = self.vocabulary.iter().map(|word| word.trim()).filter(|word| !word.is_empty()).collect();
+
+        if !words.is_empty() {
+            if !prompt.is_empty() {
+                prompt.push(' ');
+            }
+            prompt.push_str(&format!("Vocabulary: {}.", words.join(", ")));
+        }
+
+        prompt
+    }
+}
+
+impl Default for WhisperConfig {
+    fn default() -> Self {
+        Self {
+            model_path: "models/ggml-tiny.en.bin".to_string(),
+            language: "en".to_string(),
+            translate: false,
+            threads: None,
+            language_cycle: vec!["en".to_string(), "auto".to_string()],
+            partial_results: true,
+            min_confidence: None,
+            single_segment: false,
+            suppress_blank: true,
+            suppress_non_speech_tokens: true,
+            initial_prompt: String::new(),
+            vocabulary: Vec::new(),
+            chunk_secs: None,
+            chunk_overlap_secs: 2.0,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct OpenAIConfig {
+    /// Use the Responses API instead of chat completions
+    pub responses_api: bool,
+    /// Left out of requests unless set, so OpenAI's defaults apply. The penalties only work with chat completions.
+    pub top_p: Option<f32>,
+    pub presence_penalty: Option<f32>,
+    pub frequency_penalty: Option<f32>,
+    /// Makes responses repeatable (on a best-effort basis) for testing prompts, chat completions only
+    pub seed: Option<i64>,
+    /// Force a JSON object as the response, post-processing is skipped for it. OpenAI rejects the
+    /// request unless a message mentions JSON, which the built-in system prompt doesn't, so use a
+    /// profile or `system_prompt_file` that asks for JSON.
+    pub json_mode: bool,
+}
+
+/// Where the `azure` provider sends requests, its key is `AZURE_OPENAI_API_KEY`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AzureConfig {
+    /// The resource's endpoint, e.g. `https://acme.openai.azure.com`
+    pub endpoint: Option<String>,
+    /// Name of the model deployment, `[models] azure` takes precedence
+    pub deployment: Option<String>,
+    pub api_version: String,
+}
+
+impl Default for AzureConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: None,
+            deployment: None,
+            api_version: "2024-10-21".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct AnthropicConfig {
+    /// `anthropic-version` header, defaults to the current stable version
+    pub api_version: Option<String>,
+    /// `anthropic-beta` header for opting into beta features
+    pub beta: Option<String>,
+}
+
+/// Recording guards and preprocessing applied before transcription
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AudioConfig {
+    /// `hold` records while the hotkey is held, `toggle` starts on one press and stops on the next,
+    /// `vad` also stops by itself after `vad_silence_ms` below `vad_threshold` RMS following speech
+    pub mode: RecordingMode,
+    pub vad_silence_ms: u32,
+    pub vad_threshold: f32,
+    /// Presses shorter than this are treated as accidental taps and ignored
+    pub min_duration_ms: u64,
+    /// Warn once when a recording runs this long, in case it was left running by mistake. 0 disables it.
+    pub warn_after_secs: u32,
+    /// Recordings are cut off and processed at this length. 0 removes the limit.
+    pub max_duration_secs: u32,
+    /// Input device name, or part of one, to use instead of the system default
+    pub device: Option<String>,
+    /// Cut leading/trailing silence, which Whisper tends to hallucinate on. On by default.
+    pub trim_edges: bool,
+    pub trim_threshold: f32,
+    pub trim_margin_ms: u32,
+    /// `fast`, `balanced` or `high`
+    pub resample_quality: ResampleQuality,
+    /// `peak`, `rms` or `none`
+    pub normalization: Normalization,
+    /// Level `normalization = "rms"` scales to, 0.1 by default
+    pub rms_target: f32,
+    /// Gate steady background noise, measured from the start of the recording
+    pub noise_reduction: bool,
+    pub noise_window_ms: u32,
+    pub noise_floor_gain: f32,
+    /// Keep each raw recording at [`Config::last_recording_path`] for `--replay-last`
+    pub save_last_recording: bool,
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        Self {
+            mode: RecordingMode::default(),
+            vad_silence_ms: 1500,
+            vad_threshold: 0.01,
+            min_duration_ms: 300,
+            warn_after_secs: 60,
+            max_duration_secs: DEFAULT_MAX_RECORDING_SECS,
+            device: None,
+            trim_edges: true,
+            trim_threshold: 0.02,
+            trim_margin_ms: TRIM_PADDING_MS,
+            resample_quality: ResampleQuality::default(),
+            normalization: Normalization::default(),
+            rms_target: DEFAULT_TARGET_RMS,
+            noise_reduction: false,
+            noise_window_ms: 200,
+            noise_floor_gain: 0.1,
+            save_last_recording: false,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RecordingMode {
+    #[default]
+    Hold,
+    Toggle,
+    Vad,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AiErrorFallback {
+    /// Print the transcript so it can be recovered from the terminal
+    #[default]
+    Log,
+    /// Copy the raw transcript to the clipboard instead of the response
+    Clipboard,
+}
+
+/// A system prompt that can be switched to, e.g. `name = "shell"` with a prompt asking for one command
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProfileConfig {
+    pub name: String,
+    pub system_prompt: String,
+}
+
+/// A "prompt macro" hotkey that sends the transcript to a provider through a template
+#[derive(Debug, Clone, Deserialize)]
+pub struct CommandConfig {
+    pub name: String,
+    pub provider: String,
+    pub hotkey: String,
+    /// Model for this command only, e.g. a cheaper one than the provider default
+    pub model: Option<String>,
+    /// Prompt sent instead of the raw transcript, with `{transcript}` replaced by what was said
+    pub user_template: Option<String>,
+    /// Prepended to the user message to nudge tone, e.g. `As a senior Rust reviewer, `
+    pub persona: Option<String>,
+}
+
+/// An external program used like a provider: the transcript goes to its stdin
+/// and its stdout is copied to the clipboard
+#[derive(Debug, Clone, Deserialize)]
+pub struct ExternalConfig {
+    pub name: String,
+    pub hotkey: String,
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub user_template: Option<String>,
+}
+
+impl Config {
+    /// `$XDG_CONFIG_HOME/clipster`, or `~/.config/clipster`
+    pub fn dir() -> Option<PathBuf> {
+        let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| home_dir().map(|home| home.join(".config")))?;
+
+        Some(config_dir.join("clipster"))
+    }
+
+    pub fn path() -> Option<PathBuf> {
+        Some(Self::dir()?.join("config.toml"))
+    }
+
+    /// API keys written by `--setup`, see [`load_env_file`]
+    pub fn env_path() -> Option<PathBuf> {
+        Some(Self::dir()?.join(".env"))
+    }
+
+    /// `system_prompt_file`, a relative path is taken from the config directory like the config itself
+    pub fn system_prompt_path(&self) -> Option<PathBuf> {
+        let path = self.system_prompt_file.as_deref()?;
+        Some(relative_to(Path::new(path), Self::dir().as_deref()))
+    }
+
+    /// `$XDG_DATA_HOME/clipster`, or `~/.local/share/clipster`
+    pub fn data_dir() -> Option<PathBuf> {
+        let data_dir = std::env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| home_dir().map(|home| home.join(".local").join("share")))?;
+
+        Some(data_dir.join("clipster"))
+    }
+
+    /// Log of past responses, see `[history] save`
+    pub fn history_path() -> Option<PathBuf> {
+        Some(Self::data_dir()?.join("history.jsonl"))
+    }
+
+    /// Where the most recent recording is kept when `save_last_recording` is on
+    pub fn last_recording_path() -> Option<PathBuf> {
+        Some(Self::dir()?.join("last_recording.wav"))
+    }
+
+    /// Loads the config file, falling back to defaults when it doesn't exist
+    pub fn load() -> Result<Self, Box<dyn Error>> {
+        let Some(path) = Self::path() else {
+            return Ok(Self::default());
+        };
+
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = std::fs::read_to_string(&path)?;
+        Self::from_toml(&contents)
+            .map_err(|e| format!("Invalid config {}: {}", path.display(), e).into())
+    }
+
+    pub fn from_toml(contents: &str) -> Result<Self, Box<dyn Error>> {
+        Ok(toml::from_str(contents)?)
+    }
+}
+
+/// `KEY=value` lines from a `.env` file, skipping blanks and `#` comments.
+/// An `export ` prefix and quotes around the value are dropped.
+pub fn parse_env(contents: &str) -> Vec<(String, String)> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.strip_prefix("export ").unwrap_or(line).split_once('='))
+        .map(|(key, value)| {
+            let value = value.trim();
+            let unquoted = ['"', '\'']
+                .iter()
+                .find_map(|quote| value.strip_prefix(*quote)?.strip_suffix(*quote))
+                .unwrap_or(value);
+            (key.trim().to_string(), unquoted.to_string())
+        })
+        .collect()
+}
+
+/// Sets the variables in [`Config::env_path`] that aren't already in the environment
+pub fn load_env_file() {
+    let Some(contents) = Config::env_path().and_then(|path| std::fs::read_to_string(path).ok()) else {
+        return;
+    };
+
+    for (key, value) in parse_env(&contents) {
+        if std::env::var_os(&key).is_none() {
+            std::env::set_var(key, value);
+        }
+    }
+}
+
+pub fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(PathBuf::from)
+}
+
+/// `path` as it is when absolute, otherwise inside `dir` (or the working directory without one)
+fn relative_to(path: &Path, dir: Option<&Path>) -> PathBuf {
+    match dir {
+        Some(dir) if path.is_relative() => dir.join(path),
+        _ => path.to_path_buf(),
+    }
+}
+
+fn deserialize_max_tokens<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u32>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum MaxTokens {
+        Limit(u32),
+        Named(String),
+    }
+
+    match MaxTokens::deserialize(deserializer)? {
+        MaxTokens::Limit(0) => Ok(None),
+        MaxTokens::Limit(limit) => Ok(Some(limit)),
+        MaxTokens::Named(name) if name == "default" || name == "unlimited" => Ok(None),
+        MaxTokens::Named(name) => Err(D::Error::custom(format!(
+            "invalid max_tokens \"{}\", expected a number or \"default\"",
+            name
+        ))),
+    }
+}
+
+/// Parses a human-readable hotkey such as `Ctrl+Alt+T`
+pub fn parse_hotkey(hotkey: &str) -> Result<HotKey, String> {
+    hotkey
+        .parse::<HotKey>()
+        .map_err(|e| format!("Invalid hotkey \"{}\": {}", hotkey, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use global_hotkey::hotkey::{Code, Modifiers};
+
+    #[test]
+    fn test_config_empty_uses_defaults() {
+        let config = Config::from_toml("").unwrap();
+        assert!(config.commands.is_empty());
+        assert_eq!(config.on_ai_error, AiErrorFallback::Log);
+    }
+
+    #[test]
+    fn test_config_parses_ai_error_fallback() {
+        let config = Config::from_toml(r#"on_ai_error = "clipboard""#).unwrap();
+        assert_eq!(config.on_ai_error, AiErrorFallback::Clipboard);
+
+        assert!(Config::from_toml(r#"on_ai_error = "discard""#).is_err());
+    }
+
+    #[test]
+    fn test_config_parses_commands() {
+        let config = Config::from_toml(r#"
+            [[commands]]
+            name = "Translate"
+            provider = "openai"
+            hotkey = "Ctrl+Alt+J"
+            user_template = "Translate the following to Japanese:\n{transcript}"
+
+            [[commands]]
+            name = "Quick"
+            provider = "openai"
+            hotkey = "Ctrl+Alt+Q"
+            model = "gpt-4o-mini"
+            persona = "As a senior Rust reviewer, "
+        "#).unwrap();
+
+        assert_eq!(config.commands.len(), 2);
+        assert_eq!(config.commands[0].model, None);
+        assert_eq!(config.commands[1].model.as_deref(), Some("gpt-4o-mini"));
+        assert_eq!(config.commands[1].persona.as_deref(), Some("As a senior Rust reviewer, "));
+        assert_eq!(config.commands[0].name, "Translate");
+        assert_eq!(config.commands[0].provider, "openai");
+        assert_eq!(
+            config.commands[0].user_template.as_deref(),
+            Some("Translate the following to Japanese:\n{transcript}")
+        );
+    }
+
+    #[test]
+    fn test_config_parses_model_overrides() {
+        let config = Config::from_toml(r#"
+            [models]
+            mistral = "mistral-small-latest"
+        "#).unwrap();
+
+        assert_eq!(config.models.get("mistral").map(String::as_str), Some("mistral-small-latest"));
+        assert!(!config.models.contains_key("openai"));
+    }
+
+    #[test]
+    fn test_config_parses_audio_section() {
+        let config = Config::from_toml(r#"
+            [audio]
+            trim_edges = false
+            trim_threshold = 0.05
+            resample_quality = "fast"
+            noise_reduction = true
+            normalization = "none"
+        "#).unwrap();
+
+        assert!(!config.audio.trim_edges);
+        assert!(Config::default().audio.trim_edges);
+        assert_eq!(config.audio.trim_threshold, 0.05);
+        assert_eq!(config.audio.trim_margin_ms, 200);
+        assert_eq!(config.audio.min_duration_ms, 300);
+        assert_eq!(config.audio.warn_after_secs, 60);
+        assert_eq!(config.audio.resample_quality, ResampleQuality::Fast);
+        assert!(config.audio.noise_reduction);
+        assert_eq!(config.audio.normalization, Normalization::None);
+        assert_eq!(config.audio.rms_target, DEFAULT_TARGET_RMS);
+        assert_eq!(config.audio.noise_window_ms, 200);
+    }
+
+    #[test]
+    fn test_config_parses_headers() {
+        let config = Config::from_toml(r#"
+            [headers.openai]
+            X-Tenant = "acme"
+            X-Route = "eu"
+        "#).unwrap();
+
+        assert_eq!(config.headers["openai"]["X-Tenant"], "acme");
+        assert_eq!(config.headers["openai"].len(), 2);
+        assert!(!config.headers.contains_key("anthropic"));
+    }
+
+    #[test]
+    fn test_config_parses_user_agent() {
+        assert_eq!(Config::default().user_agent, None);
+
+        let config = Config::from_toml(r#"
+            user_agent = "acme-dictation/2"
+
+            [headers.all]
+            X-Client = "clipster-laptop"
+        "#).unwrap();
+
+        assert_eq!(config.user_agent.as_deref(), Some("acme-dictation/2"));
+        assert_eq!(config.headers["all"]["X-Client"], "clipster-laptop");
+    }
+
+    #[test]
+    fn test_config_parses_keep_alive() {
+        assert_eq!(Config::default().keep_alive_secs, None);
+        assert_eq!(Config::from_toml("keep_alive_secs = 60").unwrap().keep_alive_secs, Some(60));
+    }
+
+    #[test]
+    fn test_config_parses_capture() {
+        assert_eq!(Config::default().capture, Capture::MonoAverage);
+        assert_eq!(Config::from_toml(r#"capture = "mono_average""#).unwrap().capture, Capture::MonoAverage);
+        assert_eq!(Config::from_toml("capture = { channel = 1 }").unwrap().capture, Capture::Channel(1));
+        assert!(Config::from_toml(r#"capture = "left""#).is_err());
+    }
+
+    #[test]
+    fn test_relative_to_config_dir() {
+        let dir = Path::new("/home/me/.config/clipster");
+
+        assert_eq!(relative_to(Path::new("prompts/shell.txt"), Some(dir)), dir.join("prompts/shell.txt"));
+        assert_eq!(relative_to(Path::new("/etc/prompt.txt"), Some(dir)), PathBuf::from("/etc/prompt.txt"));
+        assert_eq!(relative_to(Path::new("prompt.txt"), None), PathBuf::from("prompt.txt"));
+    }
+
+    #[test]
+    fn test_parse_env() {
+        let contents = "# keys\nOPENAI_API_KEY=sk-1\n\nexport XAI_API_KEY = \"xai 2\"\nMISTRAL_API_KEY='m=3'\nnot a pair\n";
+
+        assert_eq!(parse_env(contents), vec![
+            ("OPENAI_API_KEY".to_string(), "sk-1".to_string()),
+            ("XAI_API_KEY".to_string(), "xai 2".to_string()),
+            ("MISTRAL_API_KEY".to_string(), "m=3".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_config_parses_timeout() {
+        assert_eq!(Config::default().timeout_secs, None);
+        assert_eq!(Config::from_toml("timeout_secs = 90").unwrap().timeout_secs, Some(90));
+    }
+
+    #[test]
+    fn test_config_parses_api_retries() {
+        assert_eq!(Config::default().api_retries, None);
+        assert_eq!(Config::from_toml("api_retries = 0").unwrap().api_retries, Some(0));
+    }
+
+    #[test]
+    fn test_config_parses_max_tokens() {
+        assert_eq!(Config::default().max_tokens, None);
+        assert_eq!(Config::from_toml("max_tokens = 2000").unwrap().max_tokens, Some(2000));
+        assert_eq!(Config::from_toml("max_tokens = 0").unwrap().max_tokens, None);
+        assert_eq!(Config::from_toml(r#"max_tokens = "default""#).unwrap().max_tokens, None);
+        assert!(Config::from_toml(r#"max_tokens = "lots""#).is_err());
+    }
+
+    #[test]
+    fn test_config_parses_stop_sequences() {
+        assert!(Config::default().stop_sequences.is_empty());
+
+        let config = Config::from_toml(r#"stop_sequences = ["\n```", "END"]"#).unwrap();
+        assert_eq!(config.stop_sequences, vec!["\n```", "END"]);
+    }
+
+    #[test]
+    fn test_config_parses_clipboard() {
+        assert_eq!(Config::default().clipboard, ClipboardKind::Auto);
+        assert_eq!(Config::from_toml(r#"clipboard = "wl_clipboard""#).unwrap().clipboard, ClipboardKind::WlClipboard);
+        assert_eq!(
+            Config::from_toml(r#"clipboard = { file = "/tmp/out.txt" }"#).unwrap().clipboard,
+            ClipboardKind::File(PathBuf::from("/tmp/out.txt"))
+        );
+    }
+
+    #[test]
+    fn test_config_parses_roles() {
+        let config = Config::from_toml(r#"
+            [roles.mistral]
+            system = "developer"
+        "#).unwrap();
+
+        let roles = &config.roles["mistral"];
+        assert_eq!(roles.system, "developer");
+        assert_eq!(roles.assistant, "assistant");
+    }
+
+    #[test]
+    fn test_config_parses_prices() {
+        let config = Config::from_toml(r#"
+            [prices]
+            "gpt-5" = { input = 1.25, output = 10.0 }
+        "#).unwrap();
+
+        assert_eq!(config.prices["gpt-5"], Price { input: 1.25, output: 10.0 });
+    }
+
+    #[test]
+    fn test_config_parses_queue_section() {
+        assert_eq!(Config::default().queue.capacity, 8);
+
+        let config = Config::from_toml(r#"
+            [queue]
+            capacity = 3
+            retry_attempts = 0
+        "#).unwrap();
+
+        assert_eq!(config.queue.capacity, 3);
+        assert_eq!(config.queue.retry_attempts, 0);
+        assert_eq!(config.queue.retry_delay_secs, 5);
+    }
+
+    #[test]
+    fn test_config_parses_profiles() {
+        assert!(Config::default().profiles.is_empty());
+
+        let config = Config::from_toml(r#"
+            profile = "shell"
+
+            [[profiles]]
+            name = "shell"
+            system_prompt = "Reply with a single shell command."
+        "#).unwrap();
+
+        assert_eq!(config.profile.as_deref(), Some("shell"));
+        assert_eq!(config.profiles.len(), 1);
+        assert_eq!(config.profiles[0].name, "shell");
+        assert_eq!(config.profiles[0].system_prompt, "Reply with a single shell command.");
+    }
+
+    #[test]
+    fn test_config_parses_history_section() {
+        assert_eq!(Config::default().history.max_turns, 0);
+        assert!(!Config::default().history.save);
+        assert_eq!(Config::default().history.max_saved, 1000);
+
+        let config = Config::from_toml("[history]\nmax_turns = 5\nsave = true").unwrap();
+        assert_eq!(config.history.max_turns, 5);
+        assert!(config.history.save);
+    }
+
+    #[test]
+    fn test_config_parses_local_llm_section() {
+        assert_eq!(Config::default().local_llm.model_path, None);
+        assert_eq!(Config::default().local_llm.context_size, 2048);
+
+        let config = Config::from_toml(r#"
+            [local_llm]
+            model_path = "models/qwen2.5-coder-1.5b-instruct-q4_k_m.gguf"
+            hotkey = "Ctrl+Alt+L"
+            max_tokens = 200
+        "#).unwrap();
+
+        assert_eq!(config.local_llm.hotkey.as_deref(), Some("Ctrl+Alt+L"));
+        assert_eq!(config.local_llm.max_tokens, 200);
+    }
+
+    #[test]
+    fn test_config_parses_redaction_section() {
+        let default = Config::default().redaction;
+        assert!(!default.api_keys && !default.emails && default.patterns.is_empty());
+
+        let config = Config::from_toml(r#"
+            [redaction]
+            api_keys = true
+            patterns = ["(?i)password is \\S+"]
+        "#).unwrap();
+
+        assert!(config.redaction.api_keys);
+        assert!(!config.redaction.emails);
+        assert_eq!(config.redaction.patterns, vec![r"(?i)password is \S+"]);
+    }
+
+    #[test]
+    fn test_config_parses_dictation_section() {
+        assert_eq!(Config::default().dictation.hotkey, None);
+        assert!(Config::default().dictation.restore_punctuation);
+
+        let config = Config::from_toml(r#"
+            [dictation]
+            hotkey = "Ctrl+Alt+D"
+            restore_punctuation = false
+        "#).unwrap();
+
+        assert_eq!(config.dictation.hotkey.as_deref(), Some("Ctrl+Alt+D"));
+        assert!(!config.dictation.restore_punctuation);
+    }
+
+    #[test]
+    fn test_config_parses_fallback_section() {
+        assert_eq!(Config::default().fallback.hotkey, None);
+
+        let config = Config::from_toml(r#"
+            [fallback]
+            hotkey = "Ctrl+Alt+F"
+            providers = ["anthropic", "openai"]
+        "#).unwrap();
+
+        assert_eq!(config.fallback.hotkey.as_deref(), Some("Ctrl+Alt+F"));
+        assert_eq!(config.fallback.providers, vec!["anthropic", "openai"]);
+    }
+
+    #[test]
+    fn test_config_parses_openai_section() {
+        assert!(!Config::default().openai.responses_api);
+
+        let config = Config::from_toml(r#"
+            [openai]
+            responses_api = true
+            top_p = 0.9
+            frequency_penalty = 0.5
+            seed = 42
+            json_mode = true
+        "#).unwrap();
+
+        assert!(config.openai.responses_api);
+        assert_eq!(config.openai.top_p, Some(0.9));
+        assert_eq!(config.openai.presence_penalty, None);
+        assert_eq!(config.openai.frequency_penalty, Some(0.5));
+        assert_eq!(config.openai.seed, Some(42));
+        assert!(config.openai.json_mode);
+    }
+
+    #[test]
+    fn test_config_parses_azure_section() {
+        assert_eq!(Config::default().azure.api_version, "2024-10-21");
+
+        let config = Config::from_toml(r#"
+            [azure]
+            endpoint = "https://acme.openai.azure.com"
+            deployment = "team-gpt4o"
+        "#).unwrap();
+
+        assert_eq!(config.azure.endpoint.as_deref(), Some("https://acme.openai.azure.com"));
+        assert_eq!(config.azure.deployment.as_deref(), Some("team-gpt4o"));
+        assert_eq!(config.azure.api_version, "2024-10-21");
+    }
+
+    #[test]
+    fn test_config_parses_anthropic_section() {
+        let config = Config::from_toml(r#"
+            [anthropic]
+            beta = "prompt-caching-2024-07-31"
+        "#).unwrap();
+
+        assert_eq!(config.anthropic.api_version, None);
+        assert_eq!(config.anthropic.beta.as_deref(), Some("prompt-caching-2024-07-31"));
+    }
+
+    #[test]
+    fn test_whisper_decoder_prompt() {
+        assert_eq!(Config::default().whisper.decoder_prompt(), "");
+
+        let config = Config::from_toml(r#"
+            [whisper]
+            vocabulary = ["tokio", " serde ", ""]
+        "#).unwrap();
+        assert_eq!(config.whisper.decoder_prompt(), "Vocabulary: tokio, serde.");
+
+        let config = Config::from_toml(r#"
+            [whisper]
+            initial_prompt = "A programming dictation about Rust."
+            vocabulary = ["clipster"]
+        "#).unwrap();
+        assert_eq!(config.whisper.decoder_prompt(), "A programming dictation about Rust. Vocabulary: clipster.");
+    }
+
+    #[test]
+    fn test_config_parses_language_cycle() {
+        assert_eq!(Config::default().whisper.language_cycle, vec!["en", "auto"]);
+        assert_eq!(Config::default().whisper.language, "en");
+        assert!(!Config::default().whisper.translate);
+        assert_eq!(Config::default().whisper.threads, None);
+        assert_eq!(Config::default().whisper.model_path, "models/ggml-tiny.en.bin");
+        assert!(Config::default().whisper.partial_results);
+        assert_eq!(Config::default().whisper.min_confidence, None);
+        assert!(!Config::default().whisper.single_segment);
+        assert!(Config::default().whisper.suppress_blank);
+        assert!(Config::default().whisper.suppress_non_speech_tokens);
+        assert_eq!(Config::default().whisper.chunk_secs, None);
+        assert_eq!(Config::default().whisper.chunk_overlap_secs, 2.0);
+
+        let config = Config::from_toml(r#"
+            [whisper]
+            language = "auto"
+            translate = true
+            threads = 8
+            language_cycle = ["en", "es", "auto"]
+            min_confidence = 0.6
+            single_segment = true
+            suppress_non_speech_tokens = false
+            chunk_secs = 30
+        "#).unwrap();
+
+        assert_eq!(config.whisper.language_cycle, vec!["en", "es", "auto"]);
+        assert_eq!(config.whisper.language, "auto");
+        assert!(config.whisper.translate);
+        assert_eq!(config.whisper.threads, Some(8));
+        assert_eq!(config.whisper.min_confidence, Some(0.6));
+        assert!(config.whisper.single_segment);
+        assert!(config.whisper.suppress_blank);
+        assert!(!config.whisper.suppress_non_speech_tokens);
+        assert_eq!(config.whisper.chunk_secs, Some(30.0));
+    }
+
+    #[test]
+    fn test_config_parses_output_section() {
+        assert!(!Config::default().output.notify_transcript);
+        assert!(!Config::default().output.trailing_newline);
+        assert_eq!(Config::default().output.preview_chars, 100);
+        assert_eq!(Config::default().output.post_process, vec![PostProcessStep::StripFences]);
+        assert_eq!(Config::default().output.clipboard_template, None);
+        assert!(!Config::default().output.transcript_first);
+        assert!(!Config::default().output.stream_response);
+        assert!(!Config::default().output.auto_paste);
+        assert_eq!(Config::default().output.paste_delay_ms, 150);
+
+        let config = Config::from_toml(r#"
+            [output]
+            notify_transcript = true
+            trailing_newline = true
+            preview_chars = 40
+            post_process = ["strip_fences", { max_length = 200 }, "trim"]
+            clipboard_template = "Q: {transcript}\n\nA: {response}"
+            transcript_first = true
+            stream_response = true
+            auto_paste = true
+        "#).unwrap();
+
+        assert!(config.output.notify_transcript);
+        assert!(config.output.trailing_newline);
+        assert_eq!(config.output.preview_chars, 40);
+        assert_eq!(config.output.post_process, vec![
+            PostProcessStep::StripFences,
+            PostProcessStep::MaxLength(200),
+            PostProcessStep::Trim,
+        ]);
+        assert_eq!(config.output.clipboard_template.as_deref(), Some("Q: {transcript}\n\nA: {response}"));
+        assert!(config.output.transcript_first);
+        assert!(config.output.stream_response);
+        assert!(config.output.auto_paste);
+    }
+
+    #[test]
+    fn test_config_parses_external_commands() {
+        let config = Config::from_toml(r#"
+            [[external]]
+            name = "Summarize"
+            hotkey = "Ctrl+Alt+S"
+            command = "llm"
+            args = ["-m", "local"]
+
+            [[external]]
+            name = "Upper"
+            hotkey = "Ctrl+Alt+U"
+            command = "tr"
+        "#).unwrap();
+
+        assert_eq!(config.external.len(), 2);
+        assert_eq!(config.external[0].command, "llm");
+        assert_eq!(config.external[0].args, vec!["-m", "local"]);
+        assert!(config.external[1].args.is_empty());
+    }
+
+    #[test]
+    fn test_config_rejects_command_without_hotkey() {
+        let result = Config::from_toml(r#"
+            [[commands]]
+            name = "Broken"
+            provider = "openai"
+        "#);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_config_parses_recording_mode() {
+        assert_eq!(Config::default().audio.mode, RecordingMode::Hold);
+
+        let config = Config::from_toml("[audio]\nmode = \"toggle\"").unwrap();
+        assert_eq!(config.audio.mode, RecordingMode::Toggle);
+
+        let config = Config::from_toml("[audio]\nmode = \"vad\"\nvad_silence_ms = 2000").unwrap();
+        assert_eq!(config.audio.mode, RecordingMode::Vad);
+        assert_eq!(config.audio.vad_silence_ms, 2000);
+        assert_eq!(config.audio.vad_threshold, 0.01);
+    }
+
+    #[test]
+    fn test_config_parses_hotkey_overrides() {
+        let config = Config::from_toml(r#"
+            [hotkeys]
+            anthropic = "Ctrl+Alt+A"
+        "#).unwrap();
+
+        assert_eq!(config.hotkeys.get("anthropic").map(String::as_str), Some("Ctrl+Alt+A"));
+        assert!(Config::default().hotkeys.is_empty());
+    }
+
+    #[test]
+    fn test_parse_hotkey() {
+        let hotkey = parse_hotkey("Ctrl+Alt+T").unwrap();
+        assert_eq!(hotkey, HotKey::new(Some(Modifiers::CONTROL | Modifiers::ALT), Code::KeyT));
+    }
+
+    #[test]
+    fn test_parse_hotkey_invalid() {
+        assert!(parse_hotkey("Ctrl+Nope").is_err());
+        assert!(parse_hotkey("Ctrl++").is_err());
+    }
+}
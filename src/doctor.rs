@@ -0,0 +1,139 @@
+use std::time::Instant;
+
+use clipster::ai::local::whisper::{resolve_model_path, WhisperModel};
+use clipster::audio_recorder::input_devices;
+
+use crate::config::{self, Config};
+use crate::{build_ai, describe_error, AIProvider};
+
+/// Outcome of a single `--doctor` stage
+#[derive(Debug)]
+pub struct Check {
+    pub stage: String,
+    pub result: Result<String, String>,
+}
+
+impl Check {
+    fn new(stage: &str, result: Result<String, String>) -> Self {
+        Self {
+            stage: stage.to_string(),
+            result,
+        }
+    }
+}
+
+/// Runs every check, printing each as it finishes, then the summary.
+/// Returns whether all of them passed.
+pub async fn run(config: &Config) -> bool {
+    let mut checks = vec![check_microphone(), check_whisper(config)];
+    println!("{}", format_check(&checks[0]));
+    println!("{}", format_check(&checks[1]));
+
+    for provider in AIProvider::ALL {
+        let named = config
+            .providers
+            .as_ref()
+            .is_some_and(|names| names.iter().any(|name| AIProvider::from_name(name) == Some(provider)));
+        if !provider.is_configured(named) {
+            continue;
+        }
+
+        let check = check_provider(provider, config).await;
+        println!("{}", format_check(&check));
+        checks.push(check);
+    }
+
+    let (summary, healthy) = summarize(&checks);
+    println!("\n{}", summary);
+    healthy
+}
+
+fn check_microphone() -> Check {
+    let result = match input_devices() {
+        Ok((_, None)) => Err("no default input device".to_string()),
+        Ok((names, Some(default))) => Ok(format!("default {} ({} input devices: {})", default, names.len(), names.join(", "))),
+        Err(e) => Err(e.to_string()),
+    };
+
+    Check::new("Microphone", result)
+}
+
+fn check_whisper(config: &Config) -> Check {
+    let model = &config.whisper.model_path;
+    let result = resolve_model_path(model, Config::dir().as_deref(), config::home_dir().as_deref())
+        .map_err(|e| e.to_string())
+        .and_then(|path| {
+            WhisperModel::new(&path.to_string_lossy())
+                .map(|_| format!("loaded {}", path.display()))
+                .map_err(|e| e.to_string())
+        });
+
+    Check::new("Whisper model", result)
+}
+
+/// Sends a trivial prompt, so a bad key shows up as an auth error from the API
+async fn check_provider(provider: AIProvider, config: &Config) -> Check {
+    let model = config.models.get(provider.key()).map(String::as_str);
+    let result = match build_ai(provider, model, config) {
+        Ok(ai) => {
+            let started = Instant::now();
+            match ai.generate("Reply with OK").await {
+                Ok(_) => Ok(format!("{} responded in {} ms", ai.name(), started.elapsed().as_millis())),
+                Err(e) => Err(describe_error(e.as_ref())),
+            }
+        }
+        Err(e) => Err(describe_error(e.as_ref())),
+    };
+
+    Check::new(provider.name(), result)
+}
+
+fn format_check(check: &Check) -> String {
+    match &check.result {
+        Ok(detail) => format!("\x1b[32m✓\x1b[0m {}: {}", check.stage, detail),
+        Err(e) => format!("\x1b[31m✗\x1b[0m {}: {}", check.stage, e),
+    }
+}
+
+/// One line listing the failed stages, and whether there weren't any
+fn summarize(checks: &[Check]) -> (String, bool) {
+    let failed: Vec<&str> = checks
+        .iter()
+        .filter(|check| check.result.is_err())
+        .map(|check| check.stage.as_str())
+        .collect();
+
+    if failed.is_empty() {
+        (format!("\x1b[32mAll {} checks passed\x1b[0m", checks.len()), true)
+    } else {
+        (format!("\x1b[31m{} of {} checks failed: {}\x1b[0m", failed.len(), checks.len(), failed.join(", ")), false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summarize_names_failed_stages() {
+        let checks = vec![
+            Check::new("Microphone", Ok("default mic".to_string())),
+            Check::new("Whisper model", Err("missing".to_string())),
+            Check::new("OpenAI (GPT)", Err("401 Unauthorized".to_string())),
+        ];
+
+        let (summary, healthy) = summarize(&checks);
+
+        assert!(!healthy);
+        assert!(summary.contains("2 of 3 checks failed: Whisper model, OpenAI (GPT)"));
+        assert!(format_check(&checks[2]).ends_with("OpenAI (GPT): 401 Unauthorized"));
+    }
+
+    #[test]
+    fn test_summarize_all_passed() {
+        let (summary, healthy) = summarize(&[Check::new("Microphone", Ok("default mic".to_string()))]);
+
+        assert!(healthy);
+        assert!(summary.contains("All 1 checks passed"));
+    }
+}
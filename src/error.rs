@@ -0,0 +1,158 @@
+use reqwest::StatusCode;
+use std::fmt;
+use std::time::Duration;
+
+/// Failures callers may want to tell apart, e.g. to point at the missing key instead of the network.
+/// It converts into `Box<dyn Error>` with `?`, where `downcast_ref::<ClipsterError>` gets it back.
+#[derive(Debug)]
+pub enum ClipsterError {
+    /// The environment variable holding a provider's key isn't set
+    MissingApiKey(String),
+    /// The provider answered with a non-success status
+    ApiError { provider: String, status: u16, body: String },
+    /// The request couldn't be sent or its response couldn't be read
+    Network(reqwest::Error),
+    /// Connecting, or waiting for more of the response, took longer than the client's timeout
+    Timeout(Duration),
+    /// A successful response without the expected content, or a stream that broke off
+    InvalidResponse(String),
+    /// Settings the request can't be built with, like an invalid header
+    InvalidConfig(String),
+    Transcription(String),
+    Audio(String),
+    Clipboard(String),
+}
+
+impl ClipsterError {
+    /// Names the limit when a request timed out, reqwest's own message doesn't
+    pub fn from_reqwest(error: reqwest::Error, timeout: Duration) -> Self {
+        if error.is_timeout() {
+            ClipsterError::Timeout(timeout)
+        } else {
+            ClipsterError::Network(error)
+        }
+    }
+
+    /// Reads the body of a failed response from `provider` into a [`ClipsterError::ApiError`]
+    pub async fn from_response(provider: &str, response: reqwest::Response) -> Self {
+        let status = response.status().as_u16();
+        match response.text().await {
+            Ok(body) => ClipsterError::ApiError { provider: provider.to_string(), status, body },
+            Err(e) => ClipsterError::Network(e),
+        }
+    }
+
+    /// Whether the same request may succeed later: network failures, timeouts, rate limits
+    /// and server errors. Bad keys and rejected requests fail the same way every time.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ClipsterError::Network(_) | ClipsterError::Timeout(_) => true,
+            ClipsterError::ApiError { status, .. } => *status == 429 || *status >= 500,
+            _ => false,
+        }
+    }
+
+    /// Gets a [`ClipsterError`] back out of a boxed error, anything else becomes
+    /// [`ClipsterError::InvalidResponse`] with its message
+    pub fn from_boxed(error: Box<dyn std::error::Error>) -> Self {
+        match error.downcast::<ClipsterError>() {
+            Ok(error) => *error,
+            Err(error) => ClipsterError::InvalidResponse(error.to_string()),
+        }
+    }
+}
+
+/// The key in `var`, or [`ClipsterError::MissingApiKey`] naming it
+pub fn api_key_from_env(var: &str) -> Result<String, ClipsterError> {
+    std::env::var(var).map_err(|_| ClipsterError::MissingApiKey(var.to_string()))
+}
+
+impl fmt::Display for ClipsterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClipsterError::MissingApiKey(var) => write!(f, "{} is not set", var),
+            ClipsterError::ApiError { provider, status, body } => {
+                let reason = StatusCode::from_u16(*status).ok().and_then(|status| status.canonical_reason());
+                match reason {
+                    Some(reason) => write!(f, "{} API error {} {}: {}", provider, status, reason, body),
+                    None => write!(f, "{} API error {}: {}", provider, status, body),
+                }
+            }
+            ClipsterError::Network(e) => write!(f, "Network error: {}", e),
+            ClipsterError::Timeout(timeout) => write!(f, "request timed out after {}s", timeout.as_secs_f32()),
+            ClipsterError::InvalidResponse(message)
+            | ClipsterError::InvalidConfig(message)
+            | ClipsterError::Transcription(message)
+            | ClipsterError::Audio(message)
+            | ClipsterError::Clipboard(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for ClipsterError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ClipsterError::Network(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for ClipsterError {
+    fn from(error: reqwest::Error) -> Self {
+        ClipsterError::Network(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error;
+
+    #[test]
+    fn test_display() {
+        let api_error = ClipsterError::ApiError { provider: "Anthropic".to_string(), status: 429, body: "slow down".to_string() };
+        assert_eq!(api_error.to_string(), "Anthropic API error 429 Too Many Requests: slow down");
+        assert_eq!(ClipsterError::MissingApiKey("OPENAI_API_KEY".to_string()).to_string(), "OPENAI_API_KEY is not set");
+        assert_eq!(ClipsterError::Timeout(Duration::from_millis(1500)).to_string(), "request timed out after 1.5s");
+    }
+
+    #[test]
+    fn test_downcast_from_boxed_error() {
+        let boxed: Box<dyn Error> = ClipsterError::Audio("No input device available".to_string()).into();
+
+        assert!(matches!(boxed.downcast_ref::<ClipsterError>(), Some(ClipsterError::Audio(_))));
+        assert_eq!(boxed.to_string(), "No input device available");
+    }
+
+    #[test]
+    fn test_is_retryable() {
+        let api_error = |status| ClipsterError::ApiError { provider: "openai".to_string(), status, body: String::new() };
+
+        assert!(ClipsterError::Timeout(Duration::from_secs(30)).is_retryable());
+        assert!(api_error(429).is_retryable());
+        assert!(api_error(503).is_retryable());
+        assert!(!api_error(400).is_retryable());
+        assert!(!api_error(401).is_retryable());
+        assert!(!ClipsterError::MissingApiKey("OPENAI_API_KEY".to_string()).is_retryable());
+        assert!(!ClipsterError::InvalidResponse("No response from API".to_string()).is_retryable());
+    }
+
+    #[test]
+    fn test_from_boxed_keeps_the_variant() {
+        let boxed: Box<dyn Error> = ClipsterError::Timeout(Duration::from_secs(30)).into();
+        assert!(matches!(ClipsterError::from_boxed(boxed), ClipsterError::Timeout(_)));
+
+        let other: Box<dyn Error> = "unexpected end of JSON".into();
+        assert!(matches!(
+            ClipsterError::from_boxed(other),
+            ClipsterError::InvalidResponse(message) if message == "unexpected end of JSON"
+        ));
+    }
+
+    #[test]
+    fn test_api_key_from_env_names_missing_variable() {
+        let err = api_key_from_env("CLIPSTER_TEST_NO_SUCH_KEY").unwrap_err();
+        assert!(matches!(err, ClipsterError::MissingApiKey(var) if var == "CLIPSTER_TEST_NO_SUCH_KEY"));
+    }
+}
@@ -0,0 +1,174 @@
+use serde::{Deserialize, Serialize};
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// One response as written to the history log, a JSON object per line
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    pub provider: String,
+    pub transcript: String,
+    pub response: String,
+}
+
+impl HistoryEntry {
+    /// An entry stamped with the current time
+    pub fn now(provider: &str, transcript: &str, response: &str) -> Self {
+        Self {
+            timestamp: unix_now(),
+            provider: provider.to_string(),
+            transcript: transcript.to_string(),
+            response: response.to_string(),
+        }
+    }
+
+    /// `[5 min ago] Anthropic` with the transcript and response below, for `--history`
+    pub fn describe(&self, now: u64) -> String {
+        format!(
+            "[{}] {}\n  You said: {}\n  Response: {}",
+            format_age(now.saturating_sub(self.timestamp)),
+            self.provider,
+            self.transcript,
+            self.response.replace('\n', "\n            ")
+        )
+    }
+}
+
+/// Appends `entry` to the log at `path`, creating it and its directory as needed.
+/// Once the log holds more than `max_entries`, only the newest `max_entries` are kept.
+pub fn append(path: &Path, entry: &HistoryEntry, max_entries: usize) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let line = serde_json::to_string(entry)?;
+    let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", line)?;
+    drop(file);
+
+    if max_entries > 0 {
+        truncate_to_last(path, max_entries)?;
+    }
+
+    Ok(())
+}
+
+/// Rewrites the log with only its last `max_entries` lines, if it has more
+fn truncate_to_last(path: &Path, max_entries: usize) -> io::Result<()> {
+    let contents = std::fs::read_to_string(path)?;
+    let lines: Vec<&str> = contents.lines().filter(|line| !line.trim().is_empty()).collect();
+    if lines.len() <= max_entries {
+        return Ok(());
+    }
+
+    // Write next to the log and rename over it, so a crash midway doesn't lose the history
+    let kept = lines[lines.len() - max_entries..].join("\n") + "\n";
+    let temp = path.with_extension("jsonl.tmp");
+    std::fs::write(&temp, kept)?;
+    std::fs::rename(&temp, path)
+}
+
+/// The newest `count` entries, oldest first. Lines that don't parse are skipped and a missing log is empty.
+pub fn read_last(path: &Path, count: usize) -> io::Result<Vec<HistoryEntry>> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let entries: Vec<HistoryEntry> = contents.lines().filter_map(|line| serde_json::from_str(line).ok()).collect();
+    let skip = entries.len().saturating_sub(count);
+
+    Ok(entries.into_iter().skip(skip).collect())
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// Rough age like `just now`, `5 min ago`, `3 h ago` or `2 days ago`
+fn format_age(secs: u64) -> String {
+    match secs {
+        0..=59 => "just now".to_string(),
+        60..=3599 => format!("{} min ago", secs / 60),
+        3600..=86_399 => format!("{} h ago", secs / 3600),
+        86_400..=172_799 => "1 day ago".to_string(),
+        _ => format!("{} days ago", secs / 86_400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_log(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir()
+            .join(format!("clipster-history-{}-{}", name, std::process::id()))
+            .join("history.jsonl")
+    }
+
+    fn entry(timestamp: u64, response: &str) -> HistoryEntry {
+        HistoryEntry {
+            timestamp,
+            provider: "OpenAI".to_string(),
+            transcript: "list files".to_string(),
+            response: response.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_append_and_read_last() {
+        let path = temp_log("read");
+        append(&path, &entry(1, "ls"), 0).unwrap();
+        append(&path, &entry(2, "ls -la"), 0).unwrap();
+        append(&path, &entry(3, "ls -laS"), 0).unwrap();
+
+        let last = read_last(&path, 2).unwrap();
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+
+        assert_eq!(last, vec![entry(2, "ls -la"), entry(3, "ls -laS")]);
+    }
+
+    #[test]
+    fn test_append_keeps_newest_entries() {
+        let path = temp_log("cap");
+        for timestamp in 0..5 {
+            append(&path, &entry(timestamp, "ls"), 3).unwrap();
+        }
+
+        let all = read_last(&path, 10).unwrap();
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+
+        let timestamps: Vec<u64> = all.iter().map(|entry| entry.timestamp).collect();
+        assert_eq!(timestamps, vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn test_read_last_skips_bad_lines_and_missing_log() {
+        let path = temp_log("bad");
+        assert!(read_last(&path, 5).unwrap().is_empty());
+
+        append(&path, &entry(1, "ls"), 0).unwrap();
+        let mut file = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+        writeln!(file, "not json").unwrap();
+
+        let last = read_last(&path, 5).unwrap();
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+
+        assert_eq!(last, vec![entry(1, "ls")]);
+    }
+
+    #[test]
+    fn test_describe_entry() {
+        let described = entry(1000, "ls -la\nls -laS").describe(1000 + 300);
+        assert_eq!(
+            described,
+            "[5 min ago] OpenAI\n  You said: list files\n  Response: ls -la\n            ls -laS"
+        );
+        assert_eq!(format_age(30), "just now");
+        assert_eq!(format_age(7200), "2 h ago");
+        assert_eq!(format_age(3 * 86_400), "3 days ago");
+    }
+}
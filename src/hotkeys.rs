@@ -0,0 +1,523 @@
+use global_hotkey::hotkey::{Code, HotKey, Modifiers};
+use global_hotkey::HotKeyState;
+
+use crate::config::{parse_hotkey, Config, RecordingMode};
+use crate::AIProvider;
+
+/// What happens when a registered hotkey fires
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HotkeyAction {
+    Provider(AIProvider),
+    /// Index into `config.commands`
+    Command(usize),
+    /// Index into `config.external`
+    External(usize),
+    /// Transcript only, no AI
+    Dictation,
+    /// In-process GGUF model from `[local_llm]`
+    LocalLlm,
+    /// Providers from `[fallback]`, each tried until one answers
+    Fallback,
+    Pause,
+    Language,
+    /// Stops the transcription in progress
+    Cancel,
+    /// Copies a response held back for low transcription confidence
+    Confirm,
+    /// Forgets the `[history]` of every provider
+    ClearHistory,
+    /// Switches to the next of `[[profiles]]`
+    Profile,
+}
+
+#[derive(Debug, Clone)]
+pub struct HotkeyBinding {
+    pub hotkey: HotKey,
+    /// Combo as shown to the user, e.g. `Ctrl+Shift+Space`
+    pub label: String,
+    pub action: HotkeyAction,
+}
+
+pub fn pause_hotkey() -> HotKey {
+    HotKey::new(Some(Modifiers::CONTROL | Modifiers::SHIFT), Code::KeyP)
+}
+
+pub fn language_hotkey() -> HotKey {
+    HotKey::new(Some(Modifiers::CONTROL | Modifiers::SHIFT), Code::KeyL)
+}
+
+pub fn cancel_hotkey() -> HotKey {
+    HotKey::new(Some(Modifiers::CONTROL | Modifiers::SHIFT), Code::KeyK)
+}
+
+pub fn confirm_hotkey() -> HotKey {
+    HotKey::new(Some(Modifiers::CONTROL | Modifiers::SHIFT), Code::KeyY)
+}
+
+pub fn clear_history_hotkey() -> HotKey {
+    HotKey::new(Some(Modifiers::CONTROL | Modifiers::SHIFT), Code::KeyH)
+}
+
+pub fn profile_hotkey() -> HotKey {
+    HotKey::new(Some(Modifiers::CONTROL | Modifiers::SHIFT), Code::KeyR)
+}
+
+/// Works out every hotkey to register without needing a display server.
+/// Pause, language, cancel, (with a minimum confidence) confirm, (with `[history]`) clear history and
+/// (with `[[profiles]]`) profile switching are reserved first, then providers in `configured`, then commands,
+/// external commands, dictation, the local LLM and the fallback chain.
+/// Commands with an unknown or unconfigured provider, an invalid combo or a combo that's
+/// already taken are left out, with the reason returned alongside the bindings.
+pub fn plan_hotkeys(config: &Config, configured: &[AIProvider]) -> (Vec<HotkeyBinding>, Vec<String>) {
+    let mut bindings = vec![
+        HotkeyBinding {
+            hotkey: pause_hotkey(),
+            label: "Ctrl+Shift+P".to_string(),
+            action: HotkeyAction::Pause,
+        },
+        HotkeyBinding {
+            hotkey: language_hotkey(),
+            label: "Ctrl+Shift+L".to_string(),
+            action: HotkeyAction::Language,
+        },
+        HotkeyBinding {
+            hotkey: cancel_hotkey(),
+            label: "Ctrl+Shift+K".to_string(),
+            action: HotkeyAction::Cancel,
+        },
+    ];
+    if config.whisper.min_confidence.is_some() {
+        bindings.push(HotkeyBinding {
+            hotkey: confirm_hotkey(),
+            label: "Ctrl+Shift+Y".to_string(),
+            action: HotkeyAction::Confirm,
+        });
+    }
+    if config.history.max_turns > 0 {
+        bindings.push(HotkeyBinding {
+            hotkey: clear_history_hotkey(),
+            label: "Ctrl+Shift+H".to_string(),
+            action: HotkeyAction::ClearHistory,
+        });
+    }
+    if !config.profiles.is_empty() {
+        bindings.push(HotkeyBinding {
+            hotkey: profile_hotkey(),
+            label: "Ctrl+Shift+R".to_string(),
+            action: HotkeyAction::Profile,
+        });
+    }
+    let mut skipped = Vec::new();
+
+    for name in config.hotkeys.keys() {
+        if !AIProvider::ALL.iter().any(|provider| provider.key() == name) {
+            skipped.push(format!("Ignoring [hotkeys] {}: unknown provider", name));
+        }
+    }
+
+    for &provider in configured {
+        let binding = provider_binding(provider, config, &mut skipped);
+
+        if let Some(existing) = bindings.iter().find(|existing| existing.hotkey == binding.hotkey) {
+            skipped.push(format!(
+                "Skipping {}: {} is already bound to {}",
+                provider.name(),
+                binding.label,
+                describe(&existing.action, config)
+            ));
+            continue;
+        }
+
+        bindings.push(binding);
+    }
+
+    for (index, command) in config.commands.iter().enumerate() {
+        let Some(provider) = AIProvider::from_name(&command.provider) else {
+            skipped.push(format!("Skipping command \"{}\": unknown provider \"{}\"", command.name, command.provider));
+            continue;
+        };
+
+        if !configured.contains(&provider) {
+            skipped.push(format!("Skipping command \"{}\": {} is not configured", command.name, provider.name()));
+            continue;
+        }
+
+        bind_custom(&mut bindings, &mut skipped, config, &command.name, &command.hotkey, HotkeyAction::Command(index));
+    }
+
+    for (index, external) in config.external.iter().enumerate() {
+        bind_custom(&mut bindings, &mut skipped, config, &external.name, &external.hotkey, HotkeyAction::External(index));
+    }
+
+    if let Some(hotkey) = &config.dictation.hotkey {
+        bind_custom(&mut bindings, &mut skipped, config, "Dictation", hotkey, HotkeyAction::Dictation);
+    }
+
+    if let Some(hotkey) = &config.local_llm.hotkey {
+        bind_custom(&mut bindings, &mut skipped, config, "Local LLM", hotkey, HotkeyAction::LocalLlm);
+    }
+
+    if let Some(hotkey) = &config.fallback.hotkey {
+        bind_custom(&mut bindings, &mut skipped, config, "Fallback", hotkey, HotkeyAction::Fallback);
+    }
+
+    (bindings, skipped)
+}
+
+/// The provider's combo from `[hotkeys]`, or its default when there's none or it doesn't parse
+fn provider_binding(provider: AIProvider, config: &Config, skipped: &mut Vec<String>) -> HotkeyBinding {
+    let (mods, code) = provider.hotkey_combo();
+    let default = HotkeyBinding {
+        hotkey: HotKey::new(mods, code),
+        label: provider.hotkey_display().to_string(),
+        action: HotkeyAction::Provider(provider),
+    };
+
+    let Some(combo) = config.hotkeys.get(provider.key()) else {
+        return default;
+    };
+
+    match parse_hotkey(combo) {
+        Ok(hotkey) => HotkeyBinding {
+            hotkey,
+            label: combo.to_string(),
+            ..default
+        },
+        Err(e) => {
+            skipped.push(format!(
+                "[hotkeys] {} = \"{}\": {}, using {}",
+                provider.key(),
+                combo,
+                e,
+                default.label
+            ));
+            default
+        }
+    }
+}
+
+/// What a recording hotkey event does to the recording
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordingStep {
+    Start,
+    /// Stop and send to the recording's own target
+    Stop,
+    /// Keep recording but send it to the hotkey just pressed
+    Switch,
+    Ignore,
+}
+
+/// Hold mode starts on press and stops on release. Toggle and VAD modes ignore releases, a press
+/// starts a recording, the same hotkey again stops it and another recording hotkey switches its target.
+pub fn recording_step(mode: RecordingMode, state: HotKeyState, active: Option<u32>, pressed: u32) -> RecordingStep {
+    match (mode, state, active) {
+        (RecordingMode::Hold, HotKeyState::Pressed, _) => RecordingStep::Start,
+        (RecordingMode::Hold, HotKeyState::Released, Some(_)) => RecordingStep::Stop,
+        (RecordingMode::Toggle | RecordingMode::Vad, HotKeyState::Pressed, None) => RecordingStep::Start,
+        (RecordingMode::Toggle | RecordingMode::Vad, HotKeyState::Pressed, Some(active)) if active == pressed => {
+            RecordingStep::Stop
+        }
+        (RecordingMode::Toggle | RecordingMode::Vad, HotKeyState::Pressed, Some(_)) => RecordingStep::Switch,
+        _ => RecordingStep::Ignore,
+    }
+}
+
+/// Adds a user-configured combo unless it's invalid or already taken
+fn bind_custom(
+    bindings: &mut Vec<HotkeyBinding>,
+    skipped: &mut Vec<String>,
+    config: &Config,
+    name: &str,
+    combo: &str,
+    action: HotkeyAction,
+) {
+    let hotkey = match parse_hotkey(combo) {
+        Ok(hotkey) => hotkey,
+        Err(e) => {
+            skipped.push(format!("Skipping command \"{}\": {}", name, e));
+            return;
+        }
+    };
+
+    if let Some(existing) = bindings.iter().find(|binding| binding.hotkey == hotkey) {
+        skipped.push(format!(
+            "Skipping command \"{}\": {} is already bound to {}",
+            name,
+            combo,
+            describe(&existing.action, config)
+        ));
+        return;
+    }
+
+    bindings.push(HotkeyBinding {
+        hotkey,
+        label: combo.to_string(),
+        action,
+    });
+}
+
+fn describe(action: &HotkeyAction, config: &Config) -> String {
+    match action {
+        HotkeyAction::Provider(provider) => provider.name().to_string(),
+        HotkeyAction::Command(index) => format!("command \"{}\"", config.commands[*index].name),
+        HotkeyAction::External(index) => format!("command \"{}\"", config.external[*index].name),
+        HotkeyAction::Dictation => "dictation".to_string(),
+        HotkeyAction::LocalLlm => "the local LLM".to_string(),
+        HotkeyAction::Fallback => "the fallback chain".to_string(),
+        HotkeyAction::Pause => "pause/resume".to_string(),
+        HotkeyAction::Language => "language switching".to_string(),
+        HotkeyAction::Cancel => "cancelling transcriptions".to_string(),
+        HotkeyAction::Confirm => "confirming low-confidence copies".to_string(),
+        HotkeyAction::ClearHistory => "clearing the conversation history".to_string(),
+        HotkeyAction::Profile => "switching profiles".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{CommandConfig, ExternalConfig};
+
+    fn command(name: &str, provider: &str, hotkey: &str) -> CommandConfig {
+        CommandConfig {
+            name: name.to_string(),
+            provider: provider.to_string(),
+            hotkey: hotkey.to_string(),
+            model: None,
+            user_template: None,
+            persona: None,
+        }
+    }
+
+    #[test]
+    fn test_plan_hotkeys_defaults_have_no_duplicates() {
+        let (bindings, skipped) = plan_hotkeys(&Config::default(), &AIProvider::ALL);
+
+        assert!(skipped.is_empty());
+        assert_eq!(bindings.len(), AIProvider::ALL.len() + 3);
+        assert_eq!(bindings[2].action, HotkeyAction::Cancel);
+
+        for (i, binding) in bindings.iter().enumerate() {
+            assert!(
+                bindings[i + 1..].iter().all(|other| other.hotkey != binding.hotkey),
+                "{} is bound twice",
+                binding.label
+            );
+        }
+    }
+
+    #[test]
+    fn test_plan_hotkeys_provider_display_matches_combo() {
+        for provider in AIProvider::ALL {
+            let (mods, code) = provider.hotkey_combo();
+            assert_eq!(parse_hotkey(provider.hotkey_display()).unwrap(), HotKey::new(mods, code));
+        }
+    }
+
+    #[test]
+    fn test_plan_hotkeys_adds_commands() {
+        let config = Config {
+            commands: vec![command("Translate", "claude", "Ctrl+Alt+T")],
+            ..Config::default()
+        };
+
+        let (bindings, skipped) = plan_hotkeys(&config, &[AIProvider::Anthropic]);
+
+        assert!(skipped.is_empty());
+        let last = bindings.last().unwrap();
+        assert_eq!(last.action, HotkeyAction::Command(0));
+        assert_eq!(last.hotkey, parse_hotkey("Ctrl+Alt+T").unwrap());
+    }
+
+    #[test]
+    fn test_plan_hotkeys_skips_duplicate_bindings() {
+        let config = Config {
+            commands: vec![
+                command("Translate", "openai", "Ctrl+Alt+T"),
+                command("Shadow", "openai", "Shift+Ctrl+Space"),
+                command("Pause clash", "openai", "Ctrl+Shift+P"),
+                command("Again", "openai", "Alt+Ctrl+T"),
+            ],
+            ..Config::default()
+        };
+
+        let (bindings, skipped) = plan_hotkeys(&config, &[AIProvider::Anthropic, AIProvider::OpenAI]);
+
+        assert_eq!(bindings.iter().filter(|b| matches!(b.action, HotkeyAction::Command(_))).count(), 1);
+        assert_eq!(skipped, vec![
+            "Skipping command \"Shadow\": Shift+Ctrl+Space is already bound to Anthropic (Claude)",
+            "Skipping command \"Pause clash\": Ctrl+Shift+P is already bound to pause/resume",
+            "Skipping command \"Again\": Alt+Ctrl+T is already bound to command \"Translate\"",
+        ]);
+    }
+
+    #[test]
+    fn test_plan_hotkeys_adds_external_commands_without_providers() {
+        let config = Config {
+            commands: vec![command("Translate", "openai", "Ctrl+Alt+T")],
+            external: vec![
+                ExternalConfig {
+                    name: "Script".to_string(),
+                    hotkey: "Ctrl+Alt+S".to_string(),
+                    command: "./script.sh".to_string(),
+                    args: Vec::new(),
+                    user_template: None,
+                },
+                ExternalConfig {
+                    name: "Clash".to_string(),
+                    hotkey: "Ctrl+Alt+S".to_string(),
+                    command: "other".to_string(),
+                    args: Vec::new(),
+                    user_template: None,
+                },
+            ],
+            ..Config::default()
+        };
+
+        let (bindings, skipped) = plan_hotkeys(&config, &[]);
+
+        assert_eq!(bindings.last().unwrap().action, HotkeyAction::External(0));
+        assert_eq!(skipped, vec![
+            "Skipping command \"Translate\": OpenAI (GPT) is not configured",
+            "Skipping command \"Clash\": Ctrl+Alt+S is already bound to command \"Script\"",
+        ]);
+    }
+
+    #[test]
+    fn test_plan_hotkeys_adds_dictation() {
+        let mut config = Config::default();
+        config.dictation.hotkey = Some("Ctrl+Alt+D".to_string());
+
+        let (bindings, skipped) = plan_hotkeys(&config, &[]);
+
+        assert!(skipped.is_empty());
+        assert_eq!(bindings.last().unwrap().action, HotkeyAction::Dictation);
+    }
+
+    #[test]
+    fn test_plan_hotkeys_adds_local_llm_after_dictation() {
+        let mut config = Config::default();
+        config.dictation.hotkey = Some("Ctrl+Alt+D".to_string());
+        config.local_llm.hotkey = Some("Ctrl+Alt+D".to_string());
+
+        let (_, skipped) = plan_hotkeys(&config, &[]);
+
+        assert_eq!(skipped, vec!["Skipping command \"Local LLM\": Ctrl+Alt+D is already bound to dictation"]);
+    }
+
+    #[test]
+    fn test_plan_hotkeys_adds_fallback_last() {
+        let mut config = Config::default();
+        config.fallback.hotkey = Some("Ctrl+Alt+F".to_string());
+
+        let (bindings, skipped) = plan_hotkeys(&config, &[AIProvider::OpenAI]);
+
+        assert!(skipped.is_empty());
+        assert_eq!(bindings.last().unwrap().action, HotkeyAction::Fallback);
+    }
+
+    #[test]
+    fn test_plan_hotkeys_reserves_confirm_with_min_confidence() {
+        let mut config = Config {
+            commands: vec![command("Clash", "openai", "Ctrl+Shift+Y")],
+            ..Config::default()
+        };
+        let (bindings, skipped) = plan_hotkeys(&config, &[AIProvider::OpenAI]);
+        assert!(skipped.is_empty());
+        assert!(bindings.iter().all(|binding| binding.action != HotkeyAction::Confirm));
+
+        config.whisper.min_confidence = Some(0.6);
+        let (bindings, skipped) = plan_hotkeys(&config, &[AIProvider::OpenAI]);
+        assert_eq!(bindings[3].action, HotkeyAction::Confirm);
+        assert_eq!(skipped, vec![
+            "Skipping command \"Clash\": Ctrl+Shift+Y is already bound to confirming low-confidence copies",
+        ]);
+    }
+
+    #[test]
+    fn test_plan_hotkeys_reserves_clear_history_with_history() {
+        let mut config = Config::default();
+        let (bindings, _) = plan_hotkeys(&config, &[AIProvider::OpenAI]);
+        assert!(bindings.iter().all(|binding| binding.action != HotkeyAction::ClearHistory));
+
+        config.history.max_turns = 3;
+        let (bindings, _) = plan_hotkeys(&config, &[AIProvider::OpenAI]);
+        assert_eq!(bindings[3].action, HotkeyAction::ClearHistory);
+        assert_eq!(bindings[3].label, "Ctrl+Shift+H");
+    }
+
+    #[test]
+    fn test_plan_hotkeys_applies_provider_overrides() {
+        let mut config = Config::default();
+        config.hotkeys.insert("anthropic".to_string(), "Ctrl+Alt+A".to_string());
+
+        let (bindings, skipped) = plan_hotkeys(&config, &[AIProvider::Anthropic, AIProvider::OpenAI]);
+        let anthropic = bindings
+            .iter()
+            .find(|binding| binding.action == HotkeyAction::Provider(AIProvider::Anthropic))
+            .unwrap();
+
+        assert!(skipped.is_empty());
+        assert_eq!(anthropic.hotkey, parse_hotkey("Ctrl+Alt+A").unwrap());
+        assert_eq!(anthropic.label, "Ctrl+Alt+A");
+    }
+
+    #[test]
+    fn test_plan_hotkeys_reports_bad_provider_overrides() {
+        let mut config = Config::default();
+        config.hotkeys.insert("anthropic".to_string(), "Ctrl+Nope".to_string());
+        config.hotkeys.insert("openai".to_string(), "Ctrl+Shift+P".to_string());
+        config.hotkeys.insert("bard".to_string(), "Ctrl+Alt+B".to_string());
+
+        let (bindings, mut skipped) = plan_hotkeys(&config, &[AIProvider::Anthropic, AIProvider::OpenAI]);
+        skipped.sort();
+
+        assert_eq!(skipped.len(), 3);
+        assert!(skipped[0].starts_with("Ignoring [hotkeys] bard"));
+        assert_eq!(skipped[1], "Skipping OpenAI (GPT): Ctrl+Shift+P is already bound to pause/resume");
+        assert!(skipped[2].starts_with("[hotkeys] anthropic = \"Ctrl+Nope\": Invalid hotkey"));
+        assert!(skipped[2].ends_with("using Ctrl+Shift+Space"));
+
+        let anthropic = bindings.iter().find(|binding| binding.action == HotkeyAction::Provider(AIProvider::Anthropic));
+        assert_eq!(anthropic.unwrap().label, "Ctrl+Shift+Space");
+    }
+
+    #[test]
+    fn test_recording_step_hold() {
+        let hold = RecordingMode::Hold;
+
+        assert_eq!(recording_step(hold, HotKeyState::Pressed, None, 1), RecordingStep::Start);
+        assert_eq!(recording_step(hold, HotKeyState::Released, Some(1), 1), RecordingStep::Stop);
+        assert_eq!(recording_step(hold, HotKeyState::Released, None, 1), RecordingStep::Ignore);
+    }
+
+    #[test]
+    fn test_recording_step_toggle() {
+        let toggle = RecordingMode::Toggle;
+
+        assert_eq!(recording_step(toggle, HotKeyState::Pressed, None, 1), RecordingStep::Start);
+        assert_eq!(recording_step(toggle, HotKeyState::Released, Some(1), 1), RecordingStep::Ignore);
+        assert_eq!(recording_step(toggle, HotKeyState::Pressed, Some(1), 1), RecordingStep::Stop);
+        assert_eq!(recording_step(toggle, HotKeyState::Pressed, Some(1), 2), RecordingStep::Switch);
+    }
+
+    #[test]
+    fn test_plan_hotkeys_skips_invalid_commands() {
+        let config = Config {
+            commands: vec![
+                command("Unknown", "bard", "Ctrl+Alt+B"),
+                command("Unconfigured", "cohere", "Ctrl+Alt+O"),
+                command("Bad combo", "openai", "Ctrl+Nope"),
+            ],
+            ..Config::default()
+        };
+
+        let (bindings, skipped) = plan_hotkeys(&config, &[AIProvider::OpenAI]);
+
+        assert_eq!(bindings.len(), 4);
+        assert_eq!(skipped.len(), 3);
+        assert!(skipped[0].contains("unknown provider \"bard\""));
+        assert!(skipped[1].contains("Cohere (Command) is not configured"));
+        assert!(skipped[2].contains("Invalid hotkey \"Ctrl+Nope\""));
+    }
+}
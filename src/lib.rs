@@ -0,0 +1,12 @@
+pub mod ai;
+pub mod assistant;
+pub mod audio_processing;
+pub mod audio_recorder;
+pub mod clipboard;
+pub mod error;
+pub mod history;
+pub mod metrics;
+pub mod paste;
+pub mod postprocess;
+pub mod redact;
+pub mod test_support;
@@ -1,67 +1,141 @@
-mod ai;
-mod audio_processing;
-mod audio_recorder;
+mod config;
+mod doctor;
+mod hotkeys;
+mod profiles;
+mod replay;
+mod setup;
+mod worker;
 
-use arboard::Clipboard;
 use global_hotkey::{
     GlobalHotKeyManager,
     GlobalHotKeyEvent,
-    hotkey::{Code, Modifiers, HotKey}
+    hotkey::{Code, Modifiers}
 };
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use std::collections::HashMap;
+use std::time::{Duration, Instant};
 
-use crate::ai::local::whisper::WhisperModel;
-use crate::ai::remote::anthropic::AnthropicAI;
-use crate::ai::remote::openai::OpenAI;
-use crate::ai::remote::traits::{Message, AI};
-use crate::audio_recorder::AudioRecorder;
+use clipster::ai::fallback::FallbackAI;
+use clipster::ai::local::command::ExternalCommand;
+use clipster::ai::local::dictation::Dictation;
+use clipster::ai::local::whisper::{default_threads, language_setting, resolve_model_path, WhisperModel};
+use clipster::ai::remote::anthropic::AnthropicAI;
+use clipster::ai::remote::cohere::CohereAI;
+use clipster::ai::remote::gemini::GeminiAI;
+use clipster::ai::remote::headers::{DEFAULT_TIMEOUT, DEFAULT_USER_AGENT};
+use clipster::ai::remote::ollama::{Ollama, DEFAULT_OLLAMA_TIMEOUT};
+use clipster::ai::remote::retry::DEFAULT_API_RETRIES;
+use clipster::ai::remote::openai::{OpenAI, MAX_STOP_SEQUENCES};
+use clipster::ai::remote::traits::AI;
+use clipster::audio_recorder::{list_input_device_info, AudioRecorder};
+use clipster::clipboard::ClipboardBackend;
+use clipster::error::ClipsterError;
+use clipster::assistant::SYSTEM_PROMPT;
+use clipster::history;
+use clipster::metrics::SessionStats;
+use clipster::paste;
+use clipster::postprocess::Chain;
+use clipster::redact::{Redactor, API_KEY_PATTERN, EMAIL_PATTERN};
 
-const BANNER: &'static str = "\
+use crate::config::{AiErrorFallback, Config, RecordingMode, RedactionConfig};
+use crate::hotkeys::{HotkeyAction, RecordingStep};
+use crate::profiles::{ActiveProfile, Profiles};
+use crate::worker::{Conversations, Job, PendingCopy, SharedLanguage, Worker};
+
+const BANNER: &str = "\
 ▄▖▜ ▘    ▗
 ▌ ▐ ▌▛▌▛▘▜▘█▌▛▘
 ▙▖▐▖▌▙▌▄▌▐▖▙▖▌
      ▌         ";
 
-const SYSTEM_PROMPT: &str = "\
-You are a code assistant.
-ONLY respond with the requested code, command, or snippet.
-NO explanations.
-NO markdown (unless it was specifically asked for).
-NO unnecessary quotes around response.
-BE CONCISE and immediately usable.
-
-Correct example:
-User: \"Regex for email\"
-Response: \"[a-zA-Z0-9._%+-]+@[a-zA-Z0-9.-]+\\.[a-zA-Z]{2,}\"
-
-Incorrect example:
-User: \"Regex for email\"\
-Response: \"```text
-`^[^\\s@]+@[^\\s@]+\\.[^\\s@]+$
-```
-
-Or more comprehensive:
-
-```text
-^[a-zA-Z0-9.!#$%&'*+/=?^_`{|}~-]+@[a-zA-Z0-9](?:[a-zA-Z0-9-]{0,61}[a-zA-Z0-9])?(?: ...\"
-```
-";
-
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 enum AIProvider {
     Anthropic,
     OpenAI,
-    XAI,
+    Xai,
+    Cohere,
+    Mistral,
+    Gemini,
+    Ollama,
+    Azure,
 }
 
 impl AIProvider {
+    const ALL: [AIProvider; 8] = [
+        AIProvider::Anthropic,
+        AIProvider::OpenAI,
+        AIProvider::Xai,
+        AIProvider::Cohere,
+        AIProvider::Mistral,
+        AIProvider::Gemini,
+        AIProvider::Ollama,
+        AIProvider::Azure,
+    ];
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "anthropic" | "claude" => Some(AIProvider::Anthropic),
+            "openai" | "gpt" => Some(AIProvider::OpenAI),
+            "xai" | "grok" => Some(AIProvider::Xai),
+            "cohere" => Some(AIProvider::Cohere),
+            "mistral" => Some(AIProvider::Mistral),
+            "gemini" | "google" => Some(AIProvider::Gemini),
+            "ollama" => Some(AIProvider::Ollama),
+            "azure" => Some(AIProvider::Azure),
+            _ => None,
+        }
+    }
+
+    /// Identifier used in the config file
+    fn key(&self) -> &str {
+        match self {
+            AIProvider::Anthropic => "anthropic",
+            AIProvider::OpenAI => "openai",
+            AIProvider::Xai => "xai",
+            AIProvider::Cohere => "cohere",
+            AIProvider::Mistral => "mistral",
+            AIProvider::Gemini => "gemini",
+            AIProvider::Ollama => "ollama",
+            AIProvider::Azure => "azure",
+        }
+    }
+
     fn name(&self) -> &str {
         match self {
             AIProvider::Anthropic => "Anthropic (Claude)",
             AIProvider::OpenAI => "OpenAI (GPT)",
-            AIProvider::XAI => "xAI (Grok)",
+            AIProvider::Xai => "xAI (Grok)",
+            AIProvider::Cohere => "Cohere (Command)",
+            AIProvider::Mistral => "Mistral",
+            AIProvider::Gemini => "Google (Gemini)",
+            AIProvider::Ollama => "Ollama (local)",
+            AIProvider::Azure => "Azure OpenAI",
+        }
+    }
+
+    /// Variable holding the API key, Ollama needs none
+    fn env_var(&self) -> Option<&str> {
+        match self {
+            AIProvider::Anthropic => Some("ANTHROPIC_API_KEY"),
+            AIProvider::OpenAI => Some("OPENAI_API_KEY"),
+            AIProvider::Xai => Some("XAI_API_KEY"),
+            AIProvider::Cohere => Some("COHERE_API_KEY"),
+            AIProvider::Mistral => Some("MISTRAL_API_KEY"),
+            AIProvider::Gemini => Some("GEMINI_API_KEY"),
+            AIProvider::Ollama => None,
+            AIProvider::Azure => Some("AZURE_OPENAI_API_KEY"),
+        }
+    }
+
+    /// Whether to set the provider up: its key is set, or for Ollama `OLLAMA_HOST` is set or
+    /// it's `named` in `providers`
+    fn is_configured(&self, named: bool) -> bool {
+        match self.env_var() {
+            Some(var) => std::env::var_os(var).is_some(),
+            None => named || std::env::var_os("OLLAMA_HOST").is_some(),
         }
     }
 
@@ -69,7 +143,12 @@ impl AIProvider {
         match self {
             AIProvider::Anthropic => (Some(Modifiers::CONTROL | Modifiers::SHIFT), Code::Space),
             AIProvider::OpenAI => (Some(Modifiers::CONTROL | Modifiers::ALT), Code::Space),
-            AIProvider::XAI => (Some(Modifiers::CONTROL | Modifiers::SHIFT), Code::KeyX),
+            AIProvider::Xai => (Some(Modifiers::CONTROL | Modifiers::SHIFT), Code::KeyX),
+            AIProvider::Cohere => (Some(Modifiers::CONTROL | Modifiers::ALT), Code::KeyC),
+            AIProvider::Mistral => (Some(Modifiers::CONTROL | Modifiers::SHIFT), Code::KeyM),
+            AIProvider::Gemini => (Some(Modifiers::CONTROL | Modifiers::SHIFT), Code::KeyG),
+            AIProvider::Ollama => (Some(Modifiers::CONTROL | Modifiers::SHIFT), Code::KeyO),
+            AIProvider::Azure => (Some(Modifiers::CONTROL | Modifiers::ALT), Code::KeyZ),
         }
     }
 
@@ -77,74 +156,402 @@ impl AIProvider {
         match self {
             AIProvider::Anthropic => "Ctrl+Shift+Space",
             AIProvider::OpenAI => "Ctrl+Alt+Space",
-            AIProvider::XAI => "Ctrl+Shift+X",
+            AIProvider::Xai => "Ctrl+Shift+X",
+            AIProvider::Cohere => "Ctrl+Alt+C",
+            AIProvider::Mistral => "Ctrl+Shift+M",
+            AIProvider::Gemini => "Ctrl+Shift+G",
+            AIProvider::Ollama => "Ctrl+Shift+O",
+            AIProvider::Azure => "Ctrl+Alt+Z",
         }
     }
 }
 
+type SharedAI = Arc<Box<dyn AI>>;
+
+#[derive(Clone)]
 struct AIConfig {
-    provider: AIProvider,
-    ai: Arc<Box<dyn AI>>,
+    /// Shown in messages and notifications
+    name: String,
+    ai: SharedAI,
+    user_template: Option<String>,
+    persona: Option<String>,
 }
 
-fn setup_ais() -> Result<Vec<AIConfig>, Box<dyn std::error::Error>> {
-    let mut configs = Vec::new();
+/// Builds a provider client from its env key, optionally overriding the default model
+fn build_ai(
+    provider: AIProvider,
+    model: Option<&str>,
+    config: &Config,
+) -> Result<Box<dyn AI>, Box<dyn std::error::Error>> {
+    let empty = HashMap::new();
+    // `[headers.all]` goes to every provider, the provider's own section wins on conflicts
+    let all_headers = config.headers.get("all").unwrap_or(&empty);
+    let headers = config.headers.get(provider.key()).unwrap_or(&empty);
+    let user_agent = config.user_agent.as_deref().unwrap_or(DEFAULT_USER_AGENT);
+    let timeout = config.timeout_secs.map_or(DEFAULT_TIMEOUT, Duration::from_secs);
+    let retries = config.api_retries.unwrap_or(DEFAULT_API_RETRIES);
+    let roles = config.roles.get(provider.key()).cloned().unwrap_or_default();
+    if config.roles.contains_key(provider.key()) && matches!(provider, AIProvider::Anthropic | AIProvider::Cohere | AIProvider::Gemini | AIProvider::Ollama) {
+        eprintln!("Custom roles are only supported for OpenAI-compatible providers, ignoring [roles.{}]", provider.key());
+    }
 
-    // Check Anthropic
-    if std::env::var("ANTHROPIC_API_KEY").is_ok() {
-        match AnthropicAI::from_env() {
-            Ok(ai) => {
-                configs.push(AIConfig {
-                    provider: AIProvider::Anthropic,
-                    ai: Arc::new(Box::new(ai.with_temperature(0.8).with_max_tokens(500))),
-                });
-            }
-            Err(e) => eprintln!("Anthropic key found but failed to initialize: {}", e),
-        }
+    if !config.stop_sequences.is_empty() && matches!(provider, AIProvider::Cohere | AIProvider::Gemini | AIProvider::Ollama) {
+        eprintln!("Stop sequences are only supported for Anthropic and OpenAI-compatible providers, {} ignores them", provider.name());
+    }
+    if !config.stop_sequences.is_empty() && provider == AIProvider::OpenAI && config.openai.responses_api {
+        eprintln!("The Responses API has no stop sequences, OpenAI ignores them while [openai] responses_api is on");
+    }
+    if config.stop_sequences.len() > MAX_STOP_SEQUENCES && matches!(provider, AIProvider::OpenAI | AIProvider::Azure) {
+        eprintln!(
+            "{} takes at most {} stop sequences and rejects requests with {}",
+            provider.name(),
+            MAX_STOP_SEQUENCES,
+            config.stop_sequences.len()
+        );
     }
 
-    // Check OpenAI
-    if std::env::var("OPENAI_API_KEY").is_ok() {
-        match OpenAI::openai_5() {
-            Ok(ai) => {
-                configs.push(AIConfig {
-                    provider: AIProvider::OpenAI,
-                    ai: Arc::new(Box::new(ai.with_temperature(0.8).with_max_tokens(500))),
-                });
+    let ai: Box<dyn AI> = match provider {
+        AIProvider::Anthropic => {
+            let mut ai = AnthropicAI::from_env()?
+                .with_temperature(0.8)
+                .with_max_tokens(config.max_tokens)
+                .with_stop(config.stop_sequences.clone())
+                .with_user_agent(user_agent)
+                .with_timeout(timeout)
+                .with_retries(retries)
+                .with_headers(all_headers)
+                .with_headers(headers);
+            if let Some(model) = model {
+                ai = ai.with_model(model);
+            }
+            if let Some(api_version) = &config.anthropic.api_version {
+                ai = ai.with_api_version(api_version);
+            }
+            if let Some(beta) = &config.anthropic.beta {
+                ai = ai.with_beta(beta);
+            }
+            Box::new(ai)
+        }
+        AIProvider::OpenAI => {
+            let mut ai = OpenAI::openai_5()?
+                .with_temperature(0.8)
+                .with_max_tokens(config.max_tokens)
+                .with_stop(config.stop_sequences.clone())
+                .with_responses_api(config.openai.responses_api)
+                .with_top_p(config.openai.top_p)
+                .with_presence_penalty(config.openai.presence_penalty)
+                .with_frequency_penalty(config.openai.frequency_penalty)
+                .with_json_mode(config.openai.json_mode)
+                .with_user_agent(user_agent)
+                .with_timeout(timeout)
+                .with_retries(retries)
+                .with_headers(all_headers)
+                .with_headers(headers)
+                .with_roles(roles);
+            if let Some(model) = model {
+                ai = ai.with_model(model);
+            }
+            if let Some(seed) = config.openai.seed {
+                ai = ai.with_seed(seed);
+            }
+            Box::new(ai)
+        }
+        AIProvider::Xai => {
+            let mut ai = OpenAI::grok()?
+                .with_temperature(0.8)
+                .with_max_tokens(config.max_tokens)
+                .with_stop(config.stop_sequences.clone())
+                .with_user_agent(user_agent)
+                .with_timeout(timeout)
+                .with_retries(retries)
+                .with_headers(all_headers)
+                .with_headers(headers)
+                .with_roles(roles);
+            if let Some(model) = model {
+                ai = ai.with_model(model);
             }
-            Err(e) => eprintln!("OpenAI key found but failed to initialize: {}", e),
+            Box::new(ai)
+        }
+        AIProvider::Cohere => {
+            if !headers.is_empty() {
+                eprintln!("Custom headers aren't supported for Cohere, ignoring [headers.cohere]");
+            }
+            let mut ai = CohereAI::from_env()?
+                .with_temperature(0.8)
+                .with_max_tokens(config.max_tokens)
+                .with_user_agent(user_agent)
+                .with_timeout(timeout)
+                .with_retries(retries);
+            if let Some(model) = model {
+                ai = ai.with_model(model);
+            }
+            Box::new(ai)
+        }
+        AIProvider::Mistral => {
+            let mut ai = OpenAI::mistral()?
+                .with_temperature(0.8)
+                .with_max_tokens(config.max_tokens)
+                .with_stop(config.stop_sequences.clone())
+                .with_user_agent(user_agent)
+                .with_timeout(timeout)
+                .with_retries(retries)
+                .with_headers(all_headers)
+                .with_headers(headers)
+                .with_roles(roles);
+            if let Some(model) = model {
+                ai = ai.with_model(model);
+            }
+            Box::new(ai)
+        }
+        AIProvider::Gemini => {
+            let mut ai = GeminiAI::from_env()?
+                .with_temperature(0.8)
+                .with_max_tokens(config.max_tokens)
+                .with_user_agent(user_agent)
+                .with_timeout(timeout)
+                .with_retries(retries)
+                .with_headers(all_headers)
+                .with_headers(headers);
+            if let Some(model) = model {
+                ai = ai.with_model(model);
+            }
+            Box::new(ai)
+        }
+        AIProvider::Ollama => {
+            if !headers.is_empty() {
+                eprintln!("Custom headers aren't supported for Ollama, ignoring [headers.ollama]");
+            }
+            let mut ai = Ollama::from_env()?
+                .with_temperature(0.8)
+                .with_max_tokens(config.max_tokens)
+                .with_user_agent(user_agent)
+                .with_timeout(config.timeout_secs.map_or(DEFAULT_OLLAMA_TIMEOUT, Duration::from_secs))
+                .with_retries(retries);
+            if let Some(model) = model {
+                ai = ai.with_model(model);
+            }
+            Box::new(ai)
+        }
+        AIProvider::Azure => {
+            let endpoint = config
+                .azure
+                .endpoint
+                .as_deref()
+                .ok_or("set endpoint under [azure], e.g. https://acme.openai.azure.com")?;
+            let deployment = model
+                .or(config.azure.deployment.as_deref())
+                .ok_or("set deployment under [azure] to the name of your model deployment")?;
+            let ai = OpenAI::azure(endpoint, deployment, &config.azure.api_version)?
+                .with_temperature(0.8)
+                .with_max_tokens(config.max_tokens)
+                .with_stop(config.stop_sequences.clone())
+                .with_user_agent(user_agent)
+                .with_timeout(timeout)
+                .with_retries(retries)
+                .with_headers(all_headers)
+                .with_headers(headers)
+                .with_roles(roles);
+            Box::new(ai)
+        }
+    };
+
+    Ok(ai)
+}
+
+/// Providers named by `--providers` or `providers` in the config, every provider when neither is set.
+/// Unknown names are left out with a warning.
+fn enabled_providers(names: Option<&[String]>) -> Vec<AIProvider> {
+    let Some(names) = names else {
+        return AIProvider::ALL.to_vec();
+    };
+
+    for name in names {
+        if AIProvider::from_name(name).is_none() {
+            eprintln!("Ignoring unknown provider \"{}\"", name);
         }
     }
 
-    // Check xAI
-    if std::env::var("XAI_API_KEY").is_ok() {
-        match OpenAI::grok() {
-            Ok(ai) => {
-                configs.push(AIConfig {
-                    provider: AIProvider::XAI,
-                    ai: Arc::new(Box::new(ai.with_temperature(0.8).with_max_tokens(500))),
-                });
-            }
-            Err(e) => eprintln!("xAI key found but failed to initialize: {}", e),
+    AIProvider::ALL
+        .into_iter()
+        .filter(|provider| names.iter().any(|name| AIProvider::from_name(name) == Some(*provider)))
+        .collect()
+}
+
+/// `named` says `enabled` was picked by name, which is enough to set up Ollama
+fn setup_ais(
+    config: &Config,
+    enabled: &[AIProvider],
+    named: bool,
+) -> Result<Vec<(AIProvider, SharedAI)>, Box<dyn std::error::Error>> {
+    if enabled.is_empty() {
+        return Err("None of the selected providers are known, see --providers or providers in the config".into());
+    }
+
+    let mut providers = Vec::new();
+
+    for &provider in enabled {
+        if !provider.is_configured(named) {
+            continue;
         }
+
+        let model = config.models.get(provider.key()).map(String::as_str);
+        match build_ai(provider, model, config) {
+            Ok(ai) => providers.push((provider, Arc::new(ai))),
+            Err(e) => eprintln!("{} key found but failed to initialize: {}", provider.name(), describe_error(e.as_ref())),
+        }
+    }
+
+    // External commands, dictation and the local LLM work without any API key
+    if providers.is_empty()
+        && config.external.is_empty()
+        && config.dictation.hotkey.is_none()
+        && config.local_llm.hotkey.is_none()
+    {
+        let env_vars: Vec<&str> = enabled.iter().filter_map(|provider| provider.env_var()).collect();
+        return Err(format!("No AI API keys found. Please set one of {}", env_vars.join(", ")).into());
     }
 
-    if configs.is_empty() {
-        return Err("No AI API keys found. Please set ANTHROPIC_API_KEY, OPENAI_API_KEY, or XAI_API_KEY".into());
+    Ok(providers)
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    // Runs before loading the config, which it may be about to replace
+    let setup = args.iter().any(|arg| arg == "--setup");
+    // Setting environment variables isn't thread-safe, so this happens before the runtime starts its threads
+    if !setup {
+        config::load_env_file();
     }
 
-    Ok(configs)
+    let runtime = tokio::runtime::Runtime::new()?;
+    if setup {
+        return runtime.block_on(setup::run());
+    }
+    runtime.block_on(run(args))
 }
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let ai_configs = setup_ais()?;
+async fn run(args: Vec<String>) -> Result<(), Box<dyn std::error::Error>> {
+    // Also answers when the config is broken, which is when the version matters most
+    if args.iter().any(|arg| arg == "--version" || arg == "-V") {
+        println!("{}", version_info(Config::load().ok().as_ref()));
+        return Ok(());
+    }
 
-    let recorder = Arc::new(Mutex::new(AudioRecorder::new()?));
-    let whisper = Arc::new(Mutex::new(
-        WhisperModel::new("models/ggml-tiny.en.bin")?
-    ));
-    let clipboard = Arc::new(Mutex::new(Clipboard::new()?));
+    let mut config = Config::load()?;
+
+    // --model beats WHISPER_MODEL_PATH, which beats [whisper] model_path
+    let model_override = match args.iter().position(|arg| arg == "--model") {
+        Some(index) => Some(args.get(index + 1).ok_or("--model needs a path or model name")?.clone()),
+        None => std::env::var("WHISPER_MODEL_PATH").ok().filter(|path| !path.is_empty()),
+    };
+    if let Some(model_path) = model_override {
+        config.whisper.model_path = model_path;
+    }
+    if let Some(index) = args.iter().position(|arg| arg == "--language") {
+        config.whisper.language = args.get(index + 1).ok_or("--language needs a code like de, or auto")?.clone();
+    }
+    if args.iter().any(|arg| arg == "--translate") {
+        config.whisper.translate = true;
+    }
+    if args.iter().any(|arg| arg == "--auto-paste") {
+        config.output.auto_paste = true;
+    }
+    if let Some(index) = args.iter().position(|arg| arg == "--threads") {
+        let threads = args.get(index + 1).ok_or("--threads needs a number")?;
+        config.whisper.threads = Some(threads.parse().map_err(|_| format!("--threads needs a number, got \"{}\"", threads))?);
+    }
+    let config = Arc::new(config);
+
+    let verbose = args.iter().any(|arg| arg == "--verbose" || arg == "-v");
+    let recording_mode = if args.iter().any(|arg| arg == "--toggle") {
+        RecordingMode::Toggle
+    } else {
+        config.audio.mode
+    };
+
+    if args.iter().any(|arg| arg == "--list-devices") {
+        return list_devices();
+    }
+
+    if let Some(index) = args.iter().position(|arg| arg == "--history") {
+        let count = match args.get(index + 1).filter(|arg| !arg.starts_with("--")) {
+            Some(count) => count.parse().map_err(|_| format!("--history takes a number of entries, got \"{}\"", count))?,
+            None => DEFAULT_HISTORY_ENTRIES,
+        };
+        return print_history(count);
+    }
+
+    if let Some(index) = args.iter().position(|arg| arg == "--list-models") {
+        let name = args.get(index + 1).ok_or("--list-models needs a provider, e.g. --list-models openai")?;
+        return list_models(name, &config).await;
+    }
+
+    if args.iter().any(|arg| arg == "--doctor") {
+        if !doctor::run(&config).await {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    let system_prompt_file = match args.iter().position(|arg| arg == "--system-prompt-file") {
+        Some(index) => Some(PathBuf::from(args.get(index + 1).ok_or("--system-prompt-file needs a path")?)),
+        None => config.system_prompt_path(),
+    };
+    let mut profiles = Profiles::new(&load_system_prompt(system_prompt_file.as_deref()), &config.profiles);
+    let profile = match args.iter().position(|arg| arg == "--profile") {
+        Some(index) => Some(args.get(index + 1).ok_or("--profile needs a profile name")?.as_str()),
+        None => config.profile.as_deref(),
+    };
+    if let Some(name) = profile {
+        profiles.select(name)?;
+    }
+    if config.openai.json_mode && !profiles.active().system_prompt.to_lowercase().contains("json") {
+        eprintln!("Warning: [openai] json_mode is on but the system prompt doesn't mention JSON, OpenAI rejects requests unless it (or what you say) asks for JSON");
+    }
+
+    if let Some(index) = args.iter().position(|arg| arg == "--replay-last") {
+        let name = args.get(index + 1).ok_or("--replay-last needs a provider, e.g. --replay-last openai")?;
+        return replay::run(name, &config, &profiles.active().system_prompt).await;
+    }
+    let profiles: ActiveProfile = Arc::new(std::sync::Mutex::new(profiles));
+
+    let save_audio_dir = match args.iter().position(|arg| arg == "--save-audio") {
+        Some(index) => Some(PathBuf::from(args.get(index + 1).ok_or("--save-audio needs a directory")?)),
+        None => None,
+    };
+    let dry_run = args.iter().any(|arg| arg == "--dry-run");
+    if dry_run {
+        println!("Dry run: responses are printed, the clipboard is left alone");
+    }
+
+    let provider_names: Option<Vec<String>> = match args.iter().position(|arg| arg == "--providers") {
+        Some(index) => {
+            let list = args.get(index + 1).ok_or("--providers needs a list, e.g. --providers openai,anthropic")?;
+            Some(list.split(',').map(|name| name.trim().to_string()).filter(|name| !name.is_empty()).collect())
+        }
+        None => config.providers.clone(),
+    };
+    let providers = setup_ais(&config, &enabled_providers(provider_names.as_deref()), provider_names.is_some())?;
+
+    let mut recorder = AudioRecorder::new()?
+        .with_long_recording_warning(config.audio.warn_after_secs)
+        .with_max_duration(config.audio.max_duration_secs)
+        .with_capture(config.capture)
+        .with_verbose(verbose);
+    if let Some(device) = &config.audio.device {
+        recorder = recorder.with_device_name(device);
+    }
+    let recorder = Arc::new(Mutex::new(recorder));
+    let whisper = load_whisper(&config);
+    let multilingual = whisper.is_multilingual();
+    let language = SharedLanguage::new(std::sync::Mutex::new(whisper.settings().language.clone()));
+    let whisper = Arc::new(std::sync::Mutex::new(whisper));
+    let cancel = Arc::new(AtomicBool::new(false));
+    let clipboard = Arc::new(Mutex::new(config.clipboard.open()?));
+    let pending_copy = PendingCopy::default();
+    let conversations = Conversations::default();
+    let redactor = build_redactor(&config.redaction)?;
 
     let hotkey_manager = GlobalHotKeyManager::new()?;
     let mut hotkey_map: HashMap<u32, AIConfig> = HashMap::new();
@@ -153,122 +560,715 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("Clipster AI Assistant Ready!\n");
     println!("Available AI providers:");
 
-    for config in ai_configs {
-        let (mods, code) = config.provider.hotkey_combo();
-        let hotkey = HotKey::new(mods, code);
+    let configured: Vec<AIProvider> = providers.iter().map(|(provider, _)| *provider).collect();
+    let (bindings, skipped) = hotkeys::plan_hotkeys(&config, &configured);
+
+    for reason in &skipped {
+        eprintln!("{}", reason);
+    }
+
+    let provider_ai = |provider: AIProvider| {
+        providers
+            .iter()
+            .find(|(configured, _)| *configured == provider)
+            .map(|(_, ai)| ai.clone())
+            .expect("plan_hotkeys only binds configured providers")
+    };
+
+    for binding in &bindings {
+        let ai_config = match binding.action {
+            HotkeyAction::Provider(provider) => {
+                println!("  {} - {}", binding.label, provider.name());
+                AIConfig {
+                    name: provider.name().to_string(),
+                    ai: provider_ai(provider),
+                    user_template: None,
+                    persona: None,
+                }
+            }
+            HotkeyAction::Command(index) => {
+                let command = &config.commands[index];
+                let provider = AIProvider::from_name(&command.provider).expect("validated by plan_hotkeys");
 
-        hotkey_manager.register(hotkey)?;
-        let hotkey_id = hotkey.id();
+                // A command with its own model gets a separate client instead of sharing the provider's
+                let ai = match &command.model {
+                    Some(model) => match build_ai(provider, Some(model), &config) {
+                        Ok(ai) => Arc::new(ai),
+                        Err(e) => {
+                            eprintln!("Skipping command \"{}\": {}", command.name, e);
+                            continue;
+                        }
+                    },
+                    None => provider_ai(provider),
+                };
 
-        println!("  {} - {}", config.provider.hotkey_display(), config.provider.name());
+                println!("  {} - {} via {}", binding.label, command.name, ai.name());
+                AIConfig {
+                    name: provider.name().to_string(),
+                    ai,
+                    user_template: command.user_template.clone(),
+                    persona: command.persona.clone(),
+                }
+            }
+            HotkeyAction::External(index) => {
+                let external = &config.external[index];
+                println!("  {} - {} via `{}`", binding.label, external.name, external.command);
+                AIConfig {
+                    name: external.name.clone(),
+                    ai: Arc::new(Box::new(ExternalCommand::new(&external.command, &external.args))),
+                    user_template: external.user_template.clone(),
+                    persona: None,
+                }
+            }
+            HotkeyAction::Dictation => {
+                println!("  {} - Dictation (transcript only)", binding.label);
+                let dictation = Dictation::new().with_restore_punctuation(config.dictation.restore_punctuation);
+                AIConfig {
+                    name: "Dictation".to_string(),
+                    ai: Arc::new(Box::new(dictation)),
+                    user_template: None,
+                    persona: None,
+                }
+            }
+            HotkeyAction::LocalLlm => {
+                let Some(ai) = load_local_llm(&config) else {
+                    continue;
+                };
+                println!("  {} - Local LLM ({})", binding.label, ai.name());
+                AIConfig {
+                    name: "Local LLM".to_string(),
+                    ai,
+                    user_template: None,
+                    persona: None,
+                }
+            }
+            HotkeyAction::Fallback => {
+                let chain = FallbackAI::new(fallback_chain(&config.fallback.providers, &providers));
+                if chain.is_empty() {
+                    eprintln!("Skipping fallback: none of its providers are configured");
+                    continue;
+                }
+                println!("  {} - Fallback ({})", binding.label, chain.name());
+                AIConfig {
+                    name: "Fallback".to_string(),
+                    ai: Arc::new(Box::new(chain)),
+                    user_template: None,
+                    persona: None,
+                }
+            }
+            HotkeyAction::Pause
+            | HotkeyAction::Language
+            | HotkeyAction::Cancel
+            | HotkeyAction::Confirm
+            | HotkeyAction::ClearHistory
+            | HotkeyAction::Profile => {
+                if let Err(e) = hotkey_manager.register(binding.hotkey) {
+                    eprintln!("Skipping {}: {}", binding.label, e);
+                }
+                continue;
+            }
+        };
 
-        hotkey_map.insert(hotkey_id, AIConfig {
-            provider: config.provider,
-            ai: config.ai.clone(),
-        });
+        // Another application may hold the combo already, that shouldn't stop the others from working
+        if let Err(e) = hotkey_manager.register(binding.hotkey) {
+            eprintln!("Skipping {}: {}", binding.label, e);
+            continue;
+        }
+        hotkey_map.insert(binding.hotkey.id(), ai_config);
     }
 
-    println!("\nHold hotkey to record, release to process");
+    let pause_hotkey = hotkeys::pause_hotkey();
+    let language_hotkey = hotkeys::language_hotkey();
+    let cancel_hotkey = hotkeys::cancel_hotkey();
+    let confirm_hotkey = hotkeys::confirm_hotkey();
+    let clear_history_hotkey = hotkeys::clear_history_hotkey();
+    let profile_hotkey = hotkeys::profile_hotkey();
+
+    match recording_mode {
+        RecordingMode::Hold => println!("\nHold hotkey to record, release to process"),
+        RecordingMode::Toggle => println!("\nPress hotkey to start recording, press again to process"),
+        RecordingMode::Vad => println!("\nPress hotkey to start recording, it stops and processes when you pause"),
+    }
+    println!("Press Ctrl+Shift+P while recording to pause/resume");
+    println!("Press Ctrl+Shift+L to switch transcription language ({})", config.whisper.language_cycle.join(" -> "));
+    println!("Press Ctrl+Shift+K to cancel the transcription in progress");
+    if let Some(min_confidence) = config.whisper.min_confidence {
+        println!("Press Ctrl+Shift+Y to copy a response held back for confidence below {:.2}", min_confidence);
+    }
+    if config.output.auto_paste {
+        if paste::AVAILABLE {
+            println!("Responses are pasted into the focused window after copying");
+        } else {
+            eprintln!("Warning: auto_paste is on but clipster was built without it, rebuild with `cargo build --release --features auto-paste`");
+        }
+    }
+    if !config.profiles.is_empty() {
+        let profiles = profiles.lock().unwrap();
+        println!(
+            "Press Ctrl+Shift+R to switch profiles ({}), using {}",
+            profiles.names().join(" -> "),
+            profiles.active().name
+        );
+    }
+    if config.history.max_turns > 0 {
+        println!("Follow-ups see the last {} exchange(s) per provider, press Ctrl+Shift+H to start over", config.history.max_turns);
+    }
     println!("Press Ctrl+C to exit\n");
 
+    if let Some(secs) = config.keep_alive_secs {
+        spawn_keep_alive(&providers, secs, verbose);
+    }
+
+    let stats = Arc::new(std::sync::Mutex::new(SessionStats::new()));
+
+    // Print the session's usage on Ctrl+C instead of just dying
+    let shutdown_stats = stats.clone();
+    let shutdown_config = config.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            let stats = shutdown_stats.lock().unwrap();
+            if !stats.is_empty() {
+                println!("\n{}", stats.summary(&shutdown_config.prices));
+            }
+            std::process::exit(0);
+        }
+    });
+
+    // Transcription and AI calls run on the worker so recordings can queue up behind them
+    let jobs = Worker {
+        config: config.clone(),
+        whisper,
+        language: language.clone(),
+        cancel: cancel.clone(),
+        clipboard: clipboard.clone(),
+        post_processor: Chain::from_steps(&config.output.post_process),
+        profiles: profiles.clone(),
+        redactor,
+        pending_copy: pending_copy.clone(),
+        conversations: conversations.clone(),
+        stats,
+        save_audio_dir,
+        dry_run,
+    }
+    .spawn();
+
     let receiver = GlobalHotKeyEvent::receiver();
-    let mut active_recording: Option<(AIProvider, Arc<Box<dyn AI>>)> = None;
+    // Hotkey id and target of the recording in progress
+    let mut active_recording: Option<(u32, AIConfig)> = None;
+    let mut recording_started = Instant::now();
 
     loop {
-        if let Ok(event) = receiver.recv() {
-            if let Some(config) = hotkey_map.get(&event.id) {
-                match event.state {
-                    global_hotkey::HotKeyState::Pressed => {
-                        println!("Recording for {}...", config.provider.name());
-                        recorder.lock().await.start_recording()?;
-                        active_recording = Some((config.provider, config.ai.clone()));
+        let event = match receiver.recv_timeout(SILENCE_POLL_INTERVAL) {
+            Ok(event) => event,
+            Err(e) if e.is_timeout() => {
+                if active_recording.is_none() {
+                    continue;
+                }
+
+                let stop_reason = {
+                    let mut recorder = recorder.lock().await;
+                    if recorder.take_long_recording_warning() {
+                        eprintln!("Still recording after {}s", config.audio.warn_after_secs);
+                    }
+                    if recorder.is_overflowed() {
+                        Some("Maximum recording length reached")
+                    } else if recording_mode == RecordingMode::Vad
+                        && recorder.poll_silence(config.audio.vad_silence_ms, config.audio.vad_threshold)
+                    {
+                        Some("Silence detected")
+                    } else {
+                        None
+                    }
+                };
+
+                if let Some(reason) = stop_reason {
+                    if let Some((_, ai_config)) = active_recording.take() {
+                        println!("{}, stopping", reason);
+                        finish_recording(&recorder, &config, recording_started, ai_config, &jobs).await;
+                    }
+                }
+                continue;
+            }
+            Err(_) => return Ok(()),
+        };
+
+        if event.id == pause_hotkey.id() {
+            if event.state == global_hotkey::HotKeyState::Pressed && active_recording.is_some() {
+                let recorder = recorder.lock().await;
+                if recorder.is_paused() {
+                    recorder.resume();
+                    println!("Recording resumed");
+                } else {
+                    recorder.pause();
+                    println!("Recording paused");
+                }
+            }
+            continue;
+        }
+
+        if event.id == cancel_hotkey.id() {
+            if event.state == global_hotkey::HotKeyState::Pressed {
+                cancel.store(true, Ordering::Relaxed);
+                println!("Cancelling transcription");
+            }
+            continue;
+        }
+
+        if event.id == language_hotkey.id() {
+            if event.state == global_hotkey::HotKeyState::Pressed {
+                let mut language = language.lock().unwrap();
+
+                if let Some(next) = next_language(&config.whisper.language_cycle, language.as_deref()) {
+                    *language = language_setting(next).map(str::to_string);
+                    println!("Transcription language: {}", next);
+
+                    if !multilingual {
+                        eprintln!("Warning: the loaded Whisper model is English-only, language changes have no effect");
                     }
-                    global_hotkey::HotKeyState::Released => {
-                        if let Some((provider, ai)) = active_recording.take() {
-                            println!("Processing with {}...", provider.name());
-
-                            let samples = recorder.lock().await.stop_recording()?;
-                            let sample_rate = recorder.lock().await.get_sample_rate();
-
-                            if !samples.is_empty() {
-                                // Resample and normalize
-                                let resampled = audio_processing::resample_to_16khz(&samples, sample_rate)?;
-                                let normalized = audio_processing::normalize_audio(&resampled);
-
-                                // Transcribe
-                                match whisper.lock().await.transcribe(&normalized) {
-                                    Ok(text) => {
-                                        println!("You said: {}", text);
-
-                                        // Get AI response
-                                        match get_ai_response(&ai, &text).await {
-                                            Ok(response) => {
-                                                // Copy to clipboard
-                                                clipboard.lock().await.set_text(&response)?;
-                                                println!("Copied to clipboard via {}!", provider.name());
-
-                                                #[cfg(not(target_os = "windows"))]
-                                                let _ = notify_rust::Notification::new()
-                                                    .summary(&format!("AI Assistant ({})", provider.name()))
-                                                    .body("Response copied! Ready to paste.")
-                                                    .show();
-
-                                                let preview = if response.len() > 100 {
-                                                    format!("{}...", &response[..100])
-                                                } else {
-                                                    response.clone()
-                                                };
-                                                println!("Preview: {}\n", preview);
-                                            }
-                                            Err(e) => eprintln!("AI Error: {}", e),
-                                        }
-                                    }
-                                    Err(e) => eprintln!("Transcription Error: {}", e),
-                                }
-                            }
+                }
+            }
+            continue;
+        }
+
+        if event.id == confirm_hotkey.id() {
+            if event.state == global_hotkey::HotKeyState::Pressed {
+                let pending = pending_copy.lock().await.take();
+                match pending {
+                    Some((name, clipboard_text)) => match copy_to_clipboard(&clipboard, &clipboard_text).await {
+                        Ok(()) => {
+                            println!("Confirmed, copied to clipboard via {}!\n", name);
+                            auto_paste(&config).await;
                         }
+                        Err(e) => eprintln!("Clipboard Error: {}", e),
+                    },
+                    None => println!("No response waiting for confirmation"),
+                }
+            }
+            continue;
+        }
+
+        if event.id == profile_hotkey.id() {
+            if event.state == global_hotkey::HotKeyState::Pressed {
+                let name = profiles.lock().unwrap().next().name.clone();
+                println!("Profile: {}", name);
+                notify("Profile switched", &format!("Using the {} profile.", name));
+            }
+            continue;
+        }
+
+        if event.id == clear_history_hotkey.id() {
+            if event.state == global_hotkey::HotKeyState::Pressed {
+                conversations.lock().unwrap().clear();
+                println!("Conversation history cleared");
+            }
+            continue;
+        }
+
+        if let Some(ai_config) = hotkey_map.get(&event.id) {
+            let active_id = active_recording.as_ref().map(|(id, _)| *id);
+            match hotkeys::recording_step(recording_mode, event.state, active_id, event.id) {
+                RecordingStep::Start => {
+                    if pending_copy.lock().await.take().is_some() {
+                        println!("Unconfirmed response discarded");
+                    }
+                    match recording_mode {
+                        RecordingMode::Hold => println!("Recording for {}...", ai_config.name),
+                        RecordingMode::Toggle => println!("Recording for {}, press again to stop...", ai_config.name),
+                        RecordingMode::Vad => println!("Recording for {}, stops when you pause...", ai_config.name),
                     }
+                    if let Err(e) = recorder.lock().await.start_recording() {
+                        eprintln!("Can't start recording: {}\n", e);
+                        notify("Recording failed", &e.to_string());
+                        continue;
+                    }
+                    active_recording = Some((event.id, ai_config.clone()));
+                    recording_started = Instant::now();
+                }
+                RecordingStep::Switch => {
+                    println!("Switched to {}, press again to stop...", ai_config.name);
+                    active_recording = Some((event.id, ai_config.clone()));
                 }
+                RecordingStep::Stop => {
+                    if let Some((_, ai_config)) = active_recording.take() {
+                        finish_recording(&recorder, &config, recording_started, ai_config, &jobs).await;
+                    }
+                }
+                RecordingStep::Ignore => {}
             }
         }
     }
 }
 
-async fn get_ai_response(
-    ai: &Arc<Box<dyn AI>>,
-    user_prompt: &str
-) -> Result<String, Box<dyn std::error::Error>> {
-    let messages = vec![
-        Message {
-            role: "system".to_string(),
-            content: SYSTEM_PROMPT.to_string(),
-        },
-        Message {
-            role: "user".to_string(),
-            content: user_prompt.to_string(),
-        },
-    ];
+/// How often a recording is checked for a pause (`vad` mode) or for reaching its maximum length
+/// while no hotkey events arrive
+const SILENCE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Stops the recording and queues it for the worker, unless the device failed or it was too short
+async fn finish_recording(
+    recorder: &Mutex<AudioRecorder>,
+    config: &Config,
+    started: Instant,
+    ai_config: AIConfig,
+    jobs: &tokio::sync::mpsc::Sender<Job>,
+) {
+    let samples = match recorder.lock().await.stop_recording() {
+        Ok(samples) => samples,
+        Err(e) => {
+            eprintln!("{}, skipping\n", e);
+            notify("Recording failed", &e.to_string());
+            return;
+        }
+    };
+
+    let held = started.elapsed();
+    if held < Duration::from_millis(config.audio.min_duration_ms) {
+        println!("Too short ({}ms), ignoring\n", held.as_millis());
+        return;
+    }
 
-    let response = ai.chat(messages).await?;
+    if recorder.lock().await.is_overflowed() {
+        eprintln!(
+            "Recording truncated to {}s, raise max_duration_secs under [audio] for longer ones",
+            config.audio.max_duration_secs
+        );
+    }
 
-    let cleaned = clean_response(&response);
+    let sample_rate = recorder.lock().await.get_sample_rate();
+    if let Some(stats) = recorder.lock().await.last_stats() {
+        println!(
+            "Recorded {:.1}s (peak {:.2}, rms {:.3})",
+            stats.duration_secs, stats.peak, stats.rms
+        );
+    }
 
-    Ok(cleaned)
+    worker::enqueue(jobs, Job { samples, sample_rate, ai_config });
 }
 
-fn clean_response(response: &str) -> String {
-    let mut cleaned = response.trim();
+/// Loads the `[local_llm]` model, or explains why it's skipped
+#[cfg(feature = "local-llm")]
+fn load_local_llm(config: &Config) -> Option<SharedAI> {
+    use clipster::ai::local::llama::LocalLlama;
+
+    let Some(model_path) = &config.local_llm.model_path else {
+        eprintln!("Skipping local LLM: set model_path under [local_llm]");
+        return None;
+    };
 
-    // Remove markdown code fences with language specifiers
-    if cleaned.starts_with("```") {
-        if let Some(newline_pos) = cleaned.find('\n') {
-            cleaned = &cleaned[newline_pos + 1..];
+    match LocalLlama::new(model_path) {
+        Ok(llama) => {
+            let llama = llama
+                .with_max_tokens(config.local_llm.max_tokens)
+                .with_context_size(config.local_llm.context_size);
+            Some(Arc::new(Box::new(llama)))
         }
+        Err(e) => {
+            eprintln!("Skipping local LLM: {}", e);
+            None
+        }
+    }
+}
+
+#[cfg(not(feature = "local-llm"))]
+fn load_local_llm(_config: &Config) -> Option<SharedAI> {
+    eprintln!("Skipping local LLM: clipster was built without it, rebuild with `cargo build --release --features local-llm`");
+    None
+}
+
+/// The `[fallback]` providers in order, or every configured one when none are listed.
+/// Unknown and unconfigured names are skipped with a warning.
+fn fallback_chain(names: &[String], providers: &[(AIProvider, SharedAI)]) -> Vec<SharedAI> {
+    if names.is_empty() {
+        return providers.iter().map(|(_, ai)| ai.clone()).collect();
     }
 
-    cleaned = cleaned.trim_end_matches("```").trim();
+    names
+        .iter()
+        .filter_map(|name| {
+            let Some(provider) = AIProvider::from_name(name) else {
+                eprintln!("Ignoring [fallback] provider \"{}\": unknown provider", name);
+                return None;
+            };
+            let ai = providers.iter().find(|(configured, _)| *configured == provider).map(|(_, ai)| ai.clone());
+            if ai.is_none() {
+                eprintln!("Ignoring [fallback] provider \"{}\": {} is not configured", name, provider.name());
+            }
+            ai
+        })
+        .collect()
+}
+
+/// Shortest keep-alive interval, anything more frequent just wastes requests
+const MIN_KEEP_ALIVE_SECS: u64 = 30;
+
+/// Periodically warms up every provider's connection in the background
+fn spawn_keep_alive(providers: &[(AIProvider, SharedAI)], secs: u64, verbose: bool) {
+    let ais: Vec<SharedAI> = providers.iter().map(|(_, ai)| ai.clone()).collect();
+    let period = Duration::from_secs(secs.max(MIN_KEEP_ALIVE_SECS));
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(period);
+        loop {
+            interval.tick().await;
+            for ai in &ais {
+                let result = ai.warm_up().await.map_err(|e| e.to_string());
+                if let (Err(e), true) = (result, verbose) {
+                    eprintln!("Keep-alive for {} failed: {}", ai.name(), e);
+                }
+            }
+        }
+    });
+}
+
+/// Reads the system prompt from `path`, falling back to the built-in one with a warning
+/// when the file can't be read or is empty
+fn load_system_prompt(path: Option<&Path>) -> String {
+    let Some(path) = path else {
+        return SYSTEM_PROMPT.to_string();
+    };
+
+    match std::fs::read_to_string(path) {
+        Ok(prompt) if !prompt.trim().is_empty() => {
+            println!("Using system prompt from {}", path.display());
+            prompt
+        }
+        Ok(_) => {
+            eprintln!("Warning: system prompt file {} is empty, using the built-in prompt", path.display());
+            SYSTEM_PROMPT.to_string()
+        }
+        Err(e) => {
+            eprintln!("Warning: can't read system prompt file {} ({}), using the built-in prompt", path.display(), e);
+            SYSTEM_PROMPT.to_string()
+        }
+    }
+}
+
+/// Redactor with the enabled built-in patterns followed by the configured ones
+fn build_redactor(config: &RedactionConfig) -> Result<Redactor, Box<dyn std::error::Error>> {
+    let mut redactor = Redactor::new();
+
+    if config.api_keys {
+        redactor = redactor.with_pattern(API_KEY_PATTERN)?;
+    }
+    if config.emails {
+        redactor = redactor.with_pattern(EMAIL_PATTERN)?;
+    }
+    for pattern in &config.patterns {
+        redactor = redactor
+            .with_pattern(pattern)
+            .map_err(|e| format!("Invalid pattern under [redaction]: {}", e))?;
+    }
+
+    Ok(redactor)
+}
+
+/// Copies to the clipboard, retrying once since clipboard managers can fail transiently
+async fn copy_to_clipboard(clipboard: &Mutex<Box<dyn ClipboardBackend>>, text: &str) -> Result<(), String> {
+    if clipboard.lock().await.set_text(text).is_ok() {
+        return Ok(());
+    }
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    clipboard.lock().await.set_text(text)
+}
+
+/// Pastes what was just copied into the focused window when `[output] auto_paste` is on.
+/// Waits `paste_delay_ms` first so the clipboard owner serves the new text, not the old.
+async fn auto_paste(config: &Config) {
+    if !config.output.auto_paste {
+        return;
+    }
+
+    tokio::time::sleep(Duration::from_millis(config.output.paste_delay_ms)).await;
+    if let Err(e) = paste::paste() {
+        eprintln!("Auto-paste Error: {}", e);
+    }
+}
+
+/// Keeps the transcript recoverable after a failed AI request so it doesn't have to be re-dictated
+async fn save_transcript(clipboard: &Mutex<Box<dyn ClipboardBackend>>, transcript: &str, fallback: AiErrorFallback) {
+    if fallback == AiErrorFallback::Clipboard {
+        match copy_to_clipboard(clipboard, transcript).await {
+            Ok(()) => {
+                println!("Transcript copied to clipboard instead\n");
+                notify("AI Assistant", "AI request failed, transcript copied instead.");
+                return;
+            }
+            Err(e) => eprintln!("Clipboard Error: {}", e),
+        }
+    }
 
-    cleaned.to_string()
+    println!("---- Transcript (not sent) ----\n{}\n-------------------------------\n", transcript);
+}
+
+fn notify(summary: &str, body: &str) {
+    #[cfg(not(target_os = "windows"))]
+    let _ = notify_rust::Notification::new()
+        .summary(summary)
+        .body(body)
+        .show();
+
+    #[cfg(target_os = "windows")]
+    let _ = (summary, body);
+}
+
+/// Exit code when no model file was found
+const EXIT_MODEL_MISSING: i32 = 2;
+/// Exit code when the model file exists but whisper.cpp can't load it
+const EXIT_MODEL_INVALID: i32 = 3;
+
+/// Loads the configured Whisper model, exiting with instructions if it's missing or unreadable
+fn load_whisper(config: &Config) -> WhisperModel {
+    let model = &config.whisper.model_path;
+
+    let model_path = match resolve_model_path(model, Config::dir().as_deref(), config::home_dir().as_deref()) {
+        Ok(path) => path,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, model_missing_help(model));
+            std::process::exit(EXIT_MODEL_MISSING);
+        }
+    };
+
+    match WhisperModel::new(&model_path.to_string_lossy()) {
+        Ok(whisper) => {
+            if config.whisper.translate && !whisper.is_multilingual() {
+                eprintln!("Warning: the loaded Whisper model is English-only, translate has no effect");
+            }
+
+            let whisper = whisper
+                .with_threads(config.whisper.threads.unwrap_or_else(default_threads))
+                .with_language(language_setting(&config.whisper.language))
+                .with_translate(config.whisper.translate)
+                .with_initial_prompt(&config.whisper.decoder_prompt())
+                .with_single_segment(config.whisper.single_segment)
+                .with_token_suppression(config.whisper.suppress_blank, config.whisper.suppress_non_speech_tokens);
+
+            match config.whisper.chunk_secs {
+                Some(window_secs) => whisper.with_chunking(window_secs, config.whisper.chunk_overlap_secs),
+                None => whisper,
+            }
+        }
+        Err(e) => {
+            eprintln!(
+                "{}\n\nThe file at {} exists but isn't a valid ggml Whisper model, it may be corrupt or \
+                 only partially downloaded. Delete it and download it again.\n\n{}",
+                e,
+                model_path.display(),
+                model_missing_help(model)
+            );
+            std::process::exit(EXIT_MODEL_INVALID);
+        }
+    }
+}
+
+/// Download instructions for a configured model path or name
+fn model_missing_help(model: &str) -> String {
+    let file_name = std::path::Path::new(model)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .filter(|name| name.ends_with(".bin"))
+        .unwrap_or_else(|| format!("ggml-{}.bin", model));
+
+    format!(
+        "Download it with:\n  mkdir -p models && wget https://huggingface.co/ggerganov/whisper.cpp/resolve/main/{0} -O models/{0}\n\
+         or set model_path under [whisper] in {1} (WHISPER_MODEL_PATH and --model override it)",
+        file_name,
+        Config::path().map_or("the config file".to_string(), |path| path.display().to_string()),
+    )
+}
+
+/// Prints the models a provider offers, using the same API key as normal operation
+async fn list_models(name: &str, config: &Config) -> Result<(), Box<dyn std::error::Error>> {
+    let provider = AIProvider::from_name(name).ok_or_else(|| format!("Unknown provider \"{}\"", name))?;
+    let ai = build_ai(provider, None, config)
+        .map_err(|e| format!("{} is not configured: {}", provider.name(), describe_error(e.as_ref())))?;
+
+    let models = ai.list_models().await?;
+    if models.is_empty() {
+        println!("{} doesn't support listing models", provider.name());
+    }
+    for model in models {
+        println!("{}", model);
+    }
+
+    Ok(())
+}
+
+/// Prints the input devices with their index, default sample rate and sample formats
+fn list_devices() -> Result<(), Box<dyn std::error::Error>> {
+    let devices = list_input_device_info()?;
+    if devices.is_empty() {
+        println!("No input devices found");
+    }
+    for (index, device) in devices.iter().enumerate() {
+        println!("{}", device.describe(index));
+    }
+
+    Ok(())
+}
+
+/// Entries `--history` prints without a count
+const DEFAULT_HISTORY_ENTRIES: usize = 10;
+
+/// Prints the newest `count` logged responses, oldest first so the latest ends up next to the prompt
+fn print_history(count: usize) -> Result<(), Box<dyn std::error::Error>> {
+    let path = Config::history_path().ok_or("Can't find the data directory, set HOME or XDG_DATA_HOME")?;
+    let entries = history::read_last(&path, count)?;
+    if entries.is_empty() {
+        println!("No history yet in {}", path.display());
+    }
+
+    let now = history::unix_now();
+    for entry in entries {
+        println!("{}\n", entry.describe(now));
+    }
+
+    Ok(())
+}
+
+/// Version, build commit, compiled-in providers and features and the configured model, for bug reports
+fn version_info(config: Option<&Config>) -> String {
+    let mut providers: Vec<&str> = AIProvider::ALL.iter().map(|provider| provider.key()).collect();
+    if cfg!(feature = "local-llm") {
+        providers.push("local-llm");
+    }
+
+    format!(
+        "clipster {} ({})\nproviders: {}\nauto-paste: {}\nwhisper model: {}",
+        env!("CARGO_PKG_VERSION"),
+        option_env!("CLIPSTER_GIT_COMMIT").unwrap_or("unknown commit"),
+        providers.join(", "),
+        if paste::AVAILABLE { "yes" } else { "no, built without the auto-paste feature" },
+        config.map_or("unknown, the config didn't load", |config| config.whisper.model_path.as_str()),
+    )
+}
+
+/// Error message with a hint at the fix for the failures users can fix themselves
+fn describe_error(error: &(dyn std::error::Error + 'static)) -> String {
+    match error.downcast_ref::<ClipsterError>() {
+        Some(ClipsterError::MissingApiKey(var)) => format!("{} is not set, export it or run clipster --setup", var),
+        Some(e @ ClipsterError::ApiError { status: 401 | 403, .. }) => format!("{} (check the API key)", e),
+        Some(e @ ClipsterError::Timeout(_)) => format!("{} (raise timeout_secs for slow providers)", e),
+        _ => error.to_string(),
+    }
+}
+
+/// Whether a transcript's confidence falls below the configured minimum, if there is one
+fn is_low_confidence(min_confidence: Option<f32>, confidence: Option<f32>) -> bool {
+    match (min_confidence, confidence) {
+        (Some(min_confidence), Some(confidence)) => confidence < min_confidence,
+        _ => false,
+    }
+}
+
+/// Entry after `current` in the language cycle, wrapping around; `None` for current means `auto`
+fn next_language<'a>(cycle: &'a [String], current: Option<&str>) -> Option<&'a str> {
+    let current = current.unwrap_or("auto");
+    let next = match cycle.iter().position(|language| language == current) {
+        Some(i) => (i + 1) % cycle.len(),
+        None => 0,
+    };
+
+    cycle.get(next).map(String::as_str)
 }
 
 #[cfg(test)]
@@ -276,51 +1276,111 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_clean_response_removes_code_fences() {
-        let response = "```rust\nfn main() {}\n```";
-        let cleaned = clean_response(response);
-        assert_eq!(cleaned, "fn main() {}");
+    fn test_next_language_cycles_and_wraps() {
+        let cycle = vec!["en".to_string(), "es".to_string(), "auto".to_string()];
+
+        assert_eq!(next_language(&cycle, Some("en")), Some("es"));
+        assert_eq!(next_language(&cycle, Some("es")), Some("auto"));
+        assert_eq!(next_language(&cycle, None), Some("en"));
+        assert_eq!(next_language(&cycle, Some("fr")), Some("en"));
+        assert_eq!(next_language(&[], Some("en")), None);
+    }
+
+    #[test]
+    fn test_load_system_prompt_falls_back_to_built_in() {
+        assert_eq!(load_system_prompt(None), SYSTEM_PROMPT);
+        assert_eq!(load_system_prompt(Some(Path::new("/nonexistent/clipster-prompt.txt"))), SYSTEM_PROMPT);
+
+        let path = std::env::temp_dir().join(format!("clipster-prompt-{}.txt", std::process::id()));
+        std::fs::write(&path, "Answer in French.").unwrap();
+        assert_eq!(load_system_prompt(Some(&path)), "Answer in French.");
+
+        std::fs::write(&path, "  \n").unwrap();
+        assert_eq!(load_system_prompt(Some(&path)), SYSTEM_PROMPT);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_describe_error_adds_hints() {
+        let missing: Box<dyn std::error::Error> = ClipsterError::MissingApiKey("XAI_API_KEY".to_string()).into();
+        assert_eq!(describe_error(missing.as_ref()), "XAI_API_KEY is not set, export it or run clipster --setup");
+
+        let unauthorized: Box<dyn std::error::Error> =
+            ClipsterError::ApiError { provider: "xai".to_string(), status: 401, body: "bad key".to_string() }.into();
+        assert!(describe_error(unauthorized.as_ref()).ends_with("(check the API key)"));
+
+        let other: Box<dyn std::error::Error> = "plain failure".into();
+        assert_eq!(describe_error(other.as_ref()), "plain failure");
+    }
+
+    #[test]
+    fn test_is_low_confidence() {
+        assert!(is_low_confidence(Some(0.6), Some(0.45)));
+        assert!(!is_low_confidence(Some(0.6), Some(0.8)));
+        assert!(!is_low_confidence(None, Some(0.1)));
+        assert!(!is_low_confidence(Some(0.6), None));
     }
 
     #[test]
-    fn test_clean_response_removes_code_fences_with_language() {
-        let response = "```python\nprint('hello')\n```";
-        let cleaned = clean_response(response);
-        assert_eq!(cleaned, "print('hello')");
+    fn test_ai_provider_from_name() {
+        assert_eq!(AIProvider::from_name("OpenAI"), Some(AIProvider::OpenAI));
+        assert_eq!(AIProvider::from_name("claude"), Some(AIProvider::Anthropic));
+        assert_eq!(AIProvider::from_name("grok"), Some(AIProvider::Xai));
+        assert_eq!(AIProvider::from_name("Azure"), Some(AIProvider::Azure));
+        assert_eq!(AIProvider::from_name("bard"), None);
     }
 
     #[test]
-    fn test_clean_response_handles_no_code_fences() {
-        let response = "Just plain text";
-        let cleaned = clean_response(response);
-        assert_eq!(cleaned, "Just plain text");
+    fn test_fallback_chain_keeps_configured_order() {
+        use clipster::test_support::MockAI;
+
+        let providers: Vec<(AIProvider, SharedAI)> = vec![
+            (AIProvider::OpenAI, Arc::new(Box::new(MockAI::canned("openai")))),
+            (AIProvider::Anthropic, Arc::new(Box::new(MockAI::echo()))),
+        ];
+        let names = |list: &[&str]| list.iter().map(|name| name.to_string()).collect::<Vec<_>>();
+
+        let chain = fallback_chain(&names(&["claude", "bard", "cohere", "openai"]), &providers);
+        let chain: Vec<String> = chain.iter().map(|ai| ai.name()).collect();
+        assert_eq!(chain, vec!["mock:echo", "mock:canned"]);
+
+        assert_eq!(fallback_chain(&[], &providers).len(), 2);
     }
 
     #[test]
-    fn test_clean_response_trims_whitespace() {
-        let response = "   some text   ";
-        let cleaned = clean_response(response);
-        assert_eq!(cleaned, "some text");
+    fn test_enabled_providers_filters_in_order() {
+        assert_eq!(enabled_providers(None), AIProvider::ALL.to_vec());
+
+        let names = vec!["mistral".to_string(), "bard".to_string(), "Claude".to_string()];
+        assert_eq!(enabled_providers(Some(&names)), vec![AIProvider::Anthropic, AIProvider::Mistral]);
     }
 
     #[test]
-    fn test_clean_response_handles_empty_string() {
-        let response = "";
-        let cleaned = clean_response(response);
-        assert_eq!(cleaned, "");
+    fn test_model_missing_help_names_download() {
+        let help = model_missing_help("models/ggml-tiny.en.bin");
+        assert!(help.contains("resolve/main/ggml-tiny.en.bin -O models/ggml-tiny.en.bin"));
+
+        let help = model_missing_help("base");
+        assert!(help.contains("resolve/main/ggml-base.bin -O models/ggml-base.bin"));
+        assert!(help.contains("set model_path under [whisper]"));
     }
 
     #[test]
-    fn test_clean_response_handles_only_code_fences() {
-        let response = "```\ncode here\n```";
-        let cleaned = clean_response(response);
-        assert_eq!(cleaned, "code here");
+    fn test_version_info_lists_version_providers_and_model() {
+        let info = version_info(Some(&Config::default()));
+
+        assert!(info.starts_with(&format!("clipster {}", env!("CARGO_PKG_VERSION"))));
+        assert!(info.contains("providers: anthropic, openai, xai, cohere, mistral"));
+        assert!(info.contains("whisper model: models/ggml-tiny.en.bin"));
+
+        assert!(version_info(None).ends_with("whisper model: unknown, the config didn't load"));
     }
 
     #[test]
-    fn test_clean_response_handles_multiline_code() {
-        let response = "```javascript\nconst x = 1;\nconst y = 2;\nconsole.log(x + y);\n```";
-        let cleaned = clean_response(response);
-        assert_eq!(cleaned, "const x = 1;\nconst y = 2;\nconsole.log(x + y);");
+    fn test_version_info_reports_compiled_features() {
+        let info = version_info(None);
+
+        assert_eq!(info.contains(", local-llm\n"), cfg!(feature = "local-llm"));
+        assert_eq!(info.contains("auto-paste: yes\n"), cfg!(feature = "auto-paste"));
     }
 }
@@ -0,0 +1,166 @@
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::ai::remote::traits::Usage;
+
+/// USD per million tokens
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct Price {
+    pub input: f64,
+    pub output: f64,
+}
+
+impl Price {
+    pub fn cost(&self, input_tokens: u64, output_tokens: u64) -> f64 {
+        (input_tokens as f64 * self.input + output_tokens as f64 * self.output) / 1_000_000.0
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ModelStats {
+    pub requests: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+}
+
+/// Requests and tokens per AI (`provider:model`) since startup
+#[derive(Debug, Default)]
+pub struct SessionStats {
+    models: BTreeMap<String, ModelStats>,
+}
+
+impl SessionStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counts a request, tokens are only added when the provider reported usage
+    pub fn record(&mut self, name: &str, usage: Option<Usage>) {
+        let stats = self.models.entry(name.to_string()).or_default();
+        stats.requests += 1;
+
+        if let Some(usage) = usage {
+            stats.input_tokens += usage.input_tokens;
+            stats.output_tokens += usage.output_tokens;
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.models.is_empty()
+    }
+
+    /// Everything since startup and its estimated cost, AIs without a price count as free
+    pub fn total(&self, prices: &HashMap<String, Price>) -> (ModelStats, f64) {
+        let mut total = ModelStats::default();
+        let mut total_cost = 0.0;
+
+        for (name, stats) in &self.models {
+            total.requests += stats.requests;
+            total.input_tokens += stats.input_tokens;
+            total.output_tokens += stats.output_tokens;
+            if let Some(price) = price_for(prices, name) {
+                total_cost += price.cost(stats.input_tokens, stats.output_tokens);
+            }
+        }
+
+        (total, total_cost)
+    }
+
+    /// One line for a request just recorded: its tokens and cost, then the running session total
+    pub fn request_line(&self, name: &str, usage: Usage, prices: &HashMap<String, Price>) -> String {
+        let cost = match price_for(prices, name) {
+            Some(price) => format!(", ~${:.4}", price.cost(usage.input_tokens, usage.output_tokens)),
+            None => String::new(),
+        };
+        let (total, total_cost) = self.total(prices);
+
+        format!(
+            "Tokens: {} input / {} output{} (session: {} input / {} output, ~${:.4})",
+            usage.input_tokens, usage.output_tokens, cost, total.input_tokens, total.output_tokens, total_cost
+        )
+    }
+
+    /// Totals followed by a line per AI, see [`SessionStats::total`]
+    pub fn summary(&self, prices: &HashMap<String, Price>) -> String {
+        let mut lines = Vec::new();
+
+        for (name, stats) in &self.models {
+            let cost = match price_for(prices, name) {
+                Some(price) => format!("~${:.4}", price.cost(stats.input_tokens, stats.output_tokens)),
+                None => "no price".to_string(),
+            };
+
+            lines.push(format!(
+                "  {}: {} requests, {} input / {} output tokens, {}",
+                name, stats.requests, stats.input_tokens, stats.output_tokens, cost
+            ));
+        }
+
+        let (total, total_cost) = self.total(prices);
+        format!(
+            "Session: {} requests, {} input / {} output tokens, ~${:.4}\n{}",
+            total.requests,
+            total.input_tokens,
+            total.output_tokens,
+            total_cost,
+            lines.join("\n")
+        )
+    }
+}
+
+/// Looked up by `provider:model`, then by the bare model name
+fn price_for<'a>(prices: &'a HashMap<String, Price>, name: &str) -> Option<&'a Price> {
+    let model = name.split_once(':').map_or(name, |(_, model)| model);
+    prices.get(name).or_else(|| prices.get(model))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_price_cost_per_million() {
+        let price = Price { input: 2.0, output: 10.0 };
+        assert!((price.cost(500_000, 100_000) - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_session_stats_summary() {
+        let mut stats = SessionStats::new();
+        assert!(stats.is_empty());
+
+        stats.record("openai:gpt-5", Some(Usage { input_tokens: 1_000, output_tokens: 200 }));
+        stats.record("openai:gpt-5", Some(Usage { input_tokens: 1_000, output_tokens: 300 }));
+        stats.record("cohere:command-a", None);
+
+        let prices = HashMap::from([("gpt-5".to_string(), Price { input: 1.0, output: 10.0 })]);
+        let summary = stats.summary(&prices);
+
+        assert_eq!(
+            summary,
+            "Session: 3 requests, 2000 input / 500 output tokens, ~$0.0070\n  \
+             cohere:command-a: 1 requests, 0 input / 0 output tokens, no price\n  \
+             openai:gpt-5: 2 requests, 2000 input / 500 output tokens, ~$0.0070"
+        );
+    }
+
+    #[test]
+    fn test_request_line_with_running_total() {
+        let prices = HashMap::from([("anthropic:claude-sonnet-4-5".to_string(), Price { input: 3.0, output: 15.0 })]);
+        let mut stats = SessionStats::new();
+        let usage = Usage { input_tokens: 1_000, output_tokens: 100 };
+
+        stats.record("anthropic:claude-sonnet-4-5", Some(usage));
+        stats.record("anthropic:claude-sonnet-4-5", Some(usage));
+        assert_eq!(
+            stats.request_line("anthropic:claude-sonnet-4-5", usage, &prices),
+            "Tokens: 1000 input / 100 output, ~$0.0045 (session: 2000 input / 200 output, ~$0.0090)"
+        );
+
+        stats.record("mistral:mistral-small-latest", Some(usage));
+        assert_eq!(
+            stats.request_line("mistral:mistral-small-latest", usage, &prices),
+            "Tokens: 1000 input / 100 output (session: 3000 input / 300 output, ~$0.0090)"
+        );
+    }
+}
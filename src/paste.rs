@@ -0,0 +1,44 @@
+use crate::error::ClipsterError;
+
+/// Whether this build can paste, it needs `--features auto-paste`
+pub const AVAILABLE: bool = cfg!(feature = "auto-paste");
+
+/// Presses the platform's paste shortcut in the focused window: Cmd+V on macOS, Ctrl+V elsewhere.
+/// The clipboard has to be set first, and some clipboard owners need a moment before they serve it.
+#[cfg(feature = "auto-paste")]
+pub fn paste() -> Result<(), ClipsterError> {
+    use enigo::{Direction, Enigo, Key, Keyboard, Settings};
+
+    #[cfg(target_os = "macos")]
+    let modifier = Key::Meta;
+    #[cfg(not(target_os = "macos"))]
+    let modifier = Key::Control;
+
+    let input_error = |e: enigo::InputError| ClipsterError::Clipboard(format!("Can't simulate paste: {}", e));
+    let mut enigo = Enigo::new(&Settings::default())
+        .map_err(|e| ClipsterError::Clipboard(format!("Can't simulate paste: {}", e)))?;
+
+    enigo.key(modifier, Direction::Press).map_err(input_error)?;
+    let pressed = enigo.key(Key::Unicode('v'), Direction::Click).map_err(input_error);
+    // Release the modifier even when the V failed, a stuck Ctrl is worse than a missed paste
+    enigo.key(modifier, Direction::Release).map_err(input_error)?;
+
+    pressed
+}
+
+#[cfg(not(feature = "auto-paste"))]
+pub fn paste() -> Result<(), ClipsterError> {
+    Err(ClipsterError::Clipboard(
+        "clipster was built without auto-paste, rebuild with `cargo build --release --features auto-paste`".to_string(),
+    ))
+}
+
+#[cfg(all(test, not(feature = "auto-paste")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paste_explains_missing_feature() {
+        assert!(paste().unwrap_err().to_string().contains("--features auto-paste"));
+    }
+}
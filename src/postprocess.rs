@@ -0,0 +1,188 @@
+use serde::Deserialize;
+
+use crate::assistant::clean_response;
+
+/// Transforms an AI response before it's copied to the clipboard
+pub trait ResponsePostProcessor: Send + Sync {
+    fn process(&self, text: String) -> String;
+}
+
+impl<F> ResponsePostProcessor for F
+where
+    F: Fn(String) -> String + Send + Sync,
+{
+    fn process(&self, text: String) -> String {
+        self(text)
+    }
+}
+
+/// Strips markdown code fences, see [`clean_response`]
+pub struct StripFences;
+
+impl ResponsePostProcessor for StripFences {
+    fn process(&self, text: String) -> String {
+        clean_response(&text)
+    }
+}
+
+/// Trims leading and trailing whitespace
+pub struct Trim;
+
+impl ResponsePostProcessor for Trim {
+    fn process(&self, text: String) -> String {
+        text.trim().to_string()
+    }
+}
+
+/// Cuts the response to at most this many characters
+pub struct MaxLength(pub usize);
+
+impl ResponsePostProcessor for MaxLength {
+    fn process(&self, mut text: String) -> String {
+        if let Some((end, _)) = text.char_indices().nth(self.0) {
+            text.truncate(end);
+        }
+        text
+    }
+}
+
+/// Heuristic cleanup for plain dictation: capitalizes sentence starts and a standalone `i`,
+/// and ends the text with a period when it has no terminal punctuation
+pub struct RestorePunctuation;
+
+impl ResponsePostProcessor for RestorePunctuation {
+    fn process(&self, text: String) -> String {
+        restore_punctuation(&text)
+    }
+}
+
+pub fn restore_punctuation(text: &str) -> String {
+    let text = text.trim();
+    let mut restored = String::with_capacity(text.len() + 1);
+    let mut sentence_start = true;
+
+    for word in text.split_inclusive(char::is_whitespace) {
+        let bare = word.trim_end();
+        let is_i = bare == "i" || bare.starts_with("i'");
+
+        let mut chars = word.chars();
+        match chars.next() {
+            Some(first) if sentence_start || is_i => {
+                restored.extend(first.to_uppercase());
+                restored.push_str(chars.as_str());
+            }
+            _ => restored.push_str(word),
+        }
+
+        if !bare.is_empty() {
+            sentence_start = bare.ends_with(['.', '!', '?']);
+        }
+    }
+
+    if restored.ends_with(char::is_alphanumeric) {
+        restored.push('.');
+    }
+
+    restored
+}
+
+/// Runs processors in order, each one getting the previous one's output
+#[derive(Default)]
+pub struct Chain {
+    processors: Vec<Box<dyn ResponsePostProcessor>>,
+}
+
+impl Chain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn then(mut self, processor: impl ResponsePostProcessor + 'static) -> Self {
+        self.processors.push(Box::new(processor));
+        self
+    }
+
+    pub fn from_steps(steps: &[PostProcessStep]) -> Self {
+        Self {
+            processors: steps.iter().map(PostProcessStep::build).collect(),
+        }
+    }
+}
+
+impl ResponsePostProcessor for Chain {
+    fn process(&self, text: String) -> String {
+        self.processors
+            .iter()
+            .fold(text, |text, processor| processor.process(text))
+    }
+}
+
+/// Built-in processor as named in the config, e.g. `["strip_fences", { max_length = 200 }]`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PostProcessStep {
+    StripFences,
+    Trim,
+    MaxLength(usize),
+    RestorePunctuation,
+}
+
+impl PostProcessStep {
+    fn build(&self) -> Box<dyn ResponsePostProcessor> {
+        match self {
+            PostProcessStep::StripFences => Box::new(StripFences),
+            PostProcessStep::Trim => Box::new(Trim),
+            PostProcessStep::MaxLength(max) => Box::new(MaxLength(*max)),
+            PostProcessStep::RestorePunctuation => Box::new(RestorePunctuation),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chain_applies_in_order() {
+        let chain = Chain::new()
+            .then(StripFences)
+            .then(|text: String| text.trim_end_matches(';').to_string())
+            .then(MaxLength(8));
+
+        assert_eq!(chain.process("```js\nconsole.log(1);\n```".to_string()), "console.");
+    }
+
+    #[test]
+    fn test_empty_chain_is_identity() {
+        assert_eq!(Chain::new().process("  as is  ".to_string()), "  as is  ");
+    }
+
+    #[test]
+    fn test_max_length_counts_characters() {
+        assert_eq!(MaxLength(3).process("日本語テキスト".to_string()), "日本語");
+        assert_eq!(MaxLength(10).process("short".to_string()), "short");
+    }
+
+    #[test]
+    fn test_restore_punctuation() {
+        assert_eq!(
+            restore_punctuation(" hello there. how are you? i'm fine and i agree "),
+            "Hello there. How are you? I'm fine and I agree."
+        );
+        assert_eq!(restore_punctuation("Already done!"), "Already done!");
+        assert_eq!(restore_punctuation("version 2"), "Version 2.");
+        assert_eq!(restore_punctuation("ice is cold, it is"), "Ice is cold, it is.");
+        assert_eq!(restore_punctuation(""), "");
+    }
+
+    #[test]
+    fn test_chain_from_steps() {
+        let chain = Chain::from_steps(&[
+            PostProcessStep::StripFences,
+            PostProcessStep::MaxLength(5),
+            PostProcessStep::Trim,
+        ]);
+
+        assert_eq!(chain.process("```\nls -la /tmp\n```".to_string()), "ls -l");
+    }
+}
@@ -0,0 +1,113 @@
+use std::sync::{Arc, Mutex};
+
+use crate::config::ProfileConfig;
+
+/// Name of the profile holding the built-in (or `--system-prompt-file`) prompt
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Profiles shared between the hotkey loop, which switches them, and the worker, which reads the prompt
+pub type ActiveProfile = Arc<Mutex<Profiles>>;
+
+/// A named system prompt, e.g. a shell command writer or a formal English rewriter
+#[derive(Debug, Clone, PartialEq)]
+pub struct Profile {
+    pub name: String,
+    pub system_prompt: String,
+}
+
+/// The profiles Ctrl+Shift+R cycles through and the one in use
+#[derive(Debug, Clone)]
+pub struct Profiles {
+    profiles: Vec<Profile>,
+    active: usize,
+}
+
+impl Profiles {
+    /// `default` with `default_prompt`, then the configured profiles in order.
+    /// A configured profile named `default` replaces the built-in one, later duplicates are ignored.
+    pub fn new(default_prompt: &str, configured: &[ProfileConfig]) -> Self {
+        let mut profiles = vec![Profile {
+            name: DEFAULT_PROFILE.to_string(),
+            system_prompt: default_prompt.to_string(),
+        }];
+
+        for profile in configured {
+            let profile = Profile {
+                name: profile.name.clone(),
+                system_prompt: profile.system_prompt.clone(),
+            };
+            match profiles.iter_mut().position(|existing| existing.name == profile.name) {
+                Some(0) => profiles[0] = profile,
+                Some(_) => eprintln!("Warning: profile \"{}\" is defined twice, using the first", profile.name),
+                None => profiles.push(profile),
+            }
+        }
+
+        Self { profiles, active: 0 }
+    }
+
+    pub fn active(&self) -> &Profile {
+        &self.profiles[self.active]
+    }
+
+    /// Makes the profile called `name` active, the error lists the ones there are
+    pub fn select(&mut self, name: &str) -> Result<&Profile, String> {
+        let Some(index) = self.profiles.iter().position(|profile| profile.name == name) else {
+            let names: Vec<&str> = self.profiles.iter().map(|profile| profile.name.as_str()).collect();
+            return Err(format!("Unknown profile \"{}\", available: {}", name, names.join(", ")));
+        };
+
+        self.active = index;
+        Ok(self.active())
+    }
+
+    /// Moves to the next profile, wrapping around
+    pub fn next(&mut self) -> &Profile {
+        self.active = (self.active + 1) % self.profiles.len();
+        self.active()
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.profiles.iter().map(|profile| profile.name.as_str()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(name: &str, system_prompt: &str) -> ProfileConfig {
+        ProfileConfig {
+            name: name.to_string(),
+            system_prompt: system_prompt.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_profiles_start_with_default_and_cycle() {
+        let mut profiles = Profiles::new("Code only", &[profile("shell", "One shell command"), profile("formal", "Rewrite formally")]);
+
+        assert_eq!(profiles.names(), vec!["default", "shell", "formal"]);
+        assert_eq!(profiles.active().system_prompt, "Code only");
+        assert_eq!(profiles.next().name, "shell");
+        assert_eq!(profiles.next().name, "formal");
+        assert_eq!(profiles.next().name, "default");
+    }
+
+    #[test]
+    fn test_profiles_configured_default_replaces_built_in() {
+        let profiles = Profiles::new("Code only", &[profile("default", "Be terse"), profile("shell", "One shell command")]);
+
+        assert_eq!(profiles.names(), vec!["default", "shell"]);
+        assert_eq!(profiles.active().system_prompt, "Be terse");
+    }
+
+    #[test]
+    fn test_profiles_select_by_name() {
+        let mut profiles = Profiles::new("Code only", &[profile("shell", "One shell command")]);
+
+        assert_eq!(profiles.select("shell").unwrap().system_prompt, "One shell command");
+        assert_eq!(profiles.select("poetry").unwrap_err(), "Unknown profile \"poetry\", available: default, shell");
+        assert_eq!(profiles.active().name, "shell");
+    }
+}
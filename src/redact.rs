@@ -0,0 +1,74 @@
+use regex::Regex;
+
+/// Prefixed keys (`sk-…`, `ghp_…`, `AKIA…`, Slack tokens) and long unbroken runs of key characters
+pub const API_KEY_PATTERN: &str =
+    r"\b(?:sk|pk|rk|ghp|gho|ghs|xox[abpr]|AKIA)[-_A-Za-z0-9]{12,}\b|\b[A-Za-z0-9]*[0-9][A-Za-z0-9_\-]{31,}\b";
+
+pub const EMAIL_PATTERN: &str = r"\b[A-Za-z0-9._%+\-]+@[A-Za-z0-9.\-]+\.[A-Za-z]{2,}\b";
+
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Replaces anything matching one of its patterns with `[REDACTED]`
+#[derive(Debug, Clone, Default)]
+pub struct Redactor {
+    patterns: Vec<Regex>,
+}
+
+impl Redactor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_pattern(mut self, pattern: &str) -> Result<Self, regex::Error> {
+        self.patterns.push(Regex::new(pattern)?);
+        Ok(self)
+    }
+
+    /// The redacted text and how many matches were replaced
+    pub fn redact(&self, text: &str) -> (String, usize) {
+        let mut redacted = text.to_string();
+        let mut count = 0;
+
+        for pattern in &self.patterns {
+            count += pattern.find_iter(&redacted).count();
+            redacted = pattern.replace_all(&redacted, REDACTED).into_owned();
+        }
+
+        (redacted, count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_api_keys_and_emails() {
+        let redactor = Redactor::new()
+            .with_pattern(API_KEY_PATTERN)
+            .unwrap()
+            .with_pattern(EMAIL_PATTERN)
+            .unwrap();
+
+        let (text, count) = redactor.redact("use key sk-proj-abc123DEF456ghi789 and mail jane.doe@example.com");
+
+        assert_eq!(text, "use key [REDACTED] and mail [REDACTED]");
+        assert_eq!(count, 2);
+    }
+
+    #[test]
+    fn test_redact_leaves_ordinary_words() {
+        let redactor = Redactor::new().with_pattern(API_KEY_PATTERN).unwrap();
+        let text = "list the internationalization files in the skeleton directory";
+
+        assert_eq!(redactor.redact(text), (text.to_string(), 0));
+    }
+
+    #[test]
+    fn test_redact_custom_pattern() {
+        let redactor = Redactor::new().with_pattern(r"(?i)password is \S+").unwrap();
+
+        assert_eq!(redactor.redact("my Password is hunter2 ok").0, "my [REDACTED] ok");
+        assert!(Redactor::new().with_pattern("(unclosed").is_err());
+    }
+}
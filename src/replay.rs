@@ -0,0 +1,57 @@
+use std::error::Error;
+use std::sync::Arc;
+
+use clipster::assistant::get_ai_response;
+use clipster::audio_processing::load_wav;
+use clipster::postprocess::Chain;
+
+use crate::config::Config;
+use crate::worker::prepare_audio;
+use crate::{build_ai, build_redactor, describe_error, load_whisper, AIProvider};
+
+/// Runs the recording kept by `save_last_recording` through the same steps as a hotkey press,
+/// printing the transcript and response instead of copying them
+pub async fn run(name: &str, config: &Config, system_prompt: &str) -> Result<(), Box<dyn Error>> {
+    let provider = AIProvider::from_name(name).ok_or_else(|| format!("Unknown provider \"{}\"", name))?;
+    let model = config.models.get(provider.key()).map(String::as_str);
+    let ai = Arc::new(
+        build_ai(provider, model, config)
+            .map_err(|e| format!("{} is not configured: {}", provider.name(), describe_error(e.as_ref())))?,
+    );
+
+    let path = Config::last_recording_path().ok_or("Can't find the config directory")?;
+    if !path.exists() {
+        return Err(format!(
+            "No saved recording at {}, set save_last_recording = true under [audio] and record something first",
+            path.display()
+        )
+        .into());
+    }
+
+    let (samples, sample_rate) = load_wav(&path)?;
+    println!("Replaying {} ({:.1}s)", path.display(), samples.len() as f32 / sample_rate as f32);
+
+    let Some(normalized) = prepare_audio(config, &samples, sample_rate)? else {
+        println!("Only silence recorded");
+        return Ok(());
+    };
+
+    let mut whisper = load_whisper(config);
+    let text = whisper.transcribe(&normalized)?;
+    match whisper.last_confidence() {
+        Some(confidence) => println!("You said: {} (confidence {:.2})", text, confidence),
+        None => println!("You said: {}", text),
+    }
+
+    let (prompt, redacted) = build_redactor(&config.redaction)?.redact(&text);
+    if redacted > 0 {
+        println!("Redacted {} match(es) before sending", redacted);
+    }
+
+    println!("Processing with {} ({})...", provider.name(), ai.name());
+    let post_processor = Chain::from_steps(&config.output.post_process);
+    let (response, _) = get_ai_response(&ai, system_prompt, &[], &prompt, None, None, &post_processor).await?;
+    println!("Response:\n{}", response);
+
+    Ok(())
+}
@@ -0,0 +1,214 @@
+use std::error::Error;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+
+use clipster::ai::local::whisper::resolve_model_path;
+
+use crate::config::{self, parse_env, Config, WhisperConfig};
+use crate::AIProvider;
+
+/// Offered when the chosen Whisper model can't be found, about 75 MB
+const TINY_MODEL_URL: &str = "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-tiny.en.bin";
+
+/// Asks which providers to use and for their keys, writing the keys to [`Config::env_path`],
+/// then finds or downloads a Whisper model and writes the choices to [`Config::path`]
+pub async fn run() -> Result<(), Box<dyn Error>> {
+    let dir = Config::dir().ok_or("Can't find the config directory, set HOME or XDG_CONFIG_HOME")?;
+    println!("Clipster setup, press Enter to accept the [default]\n");
+
+    let mut providers = Vec::new();
+    let mut keys = Vec::new();
+
+    for provider in AIProvider::ALL {
+        let has_key = provider.is_configured(false);
+        if !confirm(&format!("Use {}?", provider.name()), has_key)? {
+            continue;
+        }
+        providers.push(provider);
+
+        // Ollama has no key, being in `providers` is what enables it
+        let Some(env_var) = provider.env_var() else {
+            continue;
+        };
+        if has_key {
+            println!("  Using {} from the environment", env_var);
+            continue;
+        }
+
+        let key = ask(&format!("  {} (empty to set it yourself later)", env_var), "")?;
+        if !key.is_empty() {
+            keys.push((env_var.to_string(), key));
+        }
+    }
+
+    std::fs::create_dir_all(&dir)?;
+
+    // Saved straight away, so a failed download doesn't mean typing them again
+    if !keys.is_empty() {
+        let env_path = Config::env_path().ok_or("Can't find the config directory")?;
+        let existing = std::fs::read_to_string(&env_path).unwrap_or_default();
+        write_private(&env_path, &render_env(&existing, &keys))?;
+        println!("Saved {} key(s) to {}", keys.len(), env_path.display());
+    }
+
+    let mut model_path = ask("Whisper model path or name", &WhisperConfig::default().model_path)?;
+    if resolve_model_path(&model_path, Some(&dir), config::home_dir().as_deref()).is_err() {
+        println!("  {} wasn't found", model_path);
+        if confirm("  Download the tiny English model (about 75 MB)?", true)? {
+            let relative = "models/ggml-tiny.en.bin";
+            download(TINY_MODEL_URL, &dir.join(relative)).await?;
+            model_path = relative.to_string();
+        }
+    }
+
+    let config_path = Config::path().ok_or("Can't find the config directory")?;
+    if config_path.exists() && !confirm(&format!("Replace {}?", config_path.display()), false)? {
+        println!("Kept the existing config");
+    } else {
+        std::fs::write(&config_path, render_config(&providers, &model_path))?;
+        println!("Wrote {}", config_path.display());
+    }
+
+    println!("\nDone, run clipster to start");
+    Ok(())
+}
+
+/// Prints `question [default]: ` and returns the trimmed answer, or the default for an empty one
+fn ask(question: &str, default: &str) -> io::Result<String> {
+    if default.is_empty() {
+        print!("{}: ", question);
+    } else {
+        print!("{} [{}]: ", question, default);
+    }
+    io::stdout().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    let answer = answer.trim();
+
+    Ok(if answer.is_empty() { default.to_string() } else { answer.to_string() })
+}
+
+fn confirm(question: &str, default: bool) -> io::Result<bool> {
+    let answer = ask(question, if default { "Y/n" } else { "y/N" })?;
+    Ok(match answer.to_lowercase().as_str() {
+        "y" | "yes" => true,
+        "n" | "no" => false,
+        _ => default,
+    })
+}
+
+/// Streams `url` into a `.part` file next to `path`, renamed once complete so an interrupted
+/// download never leaves a truncated model behind
+async fn download(url: &str, path: &Path) -> Result<(), Box<dyn Error>> {
+    println!("  Downloading {}...", url);
+    let mut response = reqwest::get(url).await?.error_for_status()?;
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let partial = partial_path(path);
+    let mut file = tokio::fs::File::create(&partial).await?;
+
+    let written = async {
+        while let Some(chunk) = response.chunk().await? {
+            file.write_all(&chunk).await?;
+        }
+        file.flush().await?;
+        Ok::<(), Box<dyn Error>>(())
+    }
+    .await;
+    if let Err(e) = written {
+        let _ = std::fs::remove_file(&partial);
+        return Err(e);
+    }
+
+    std::fs::rename(&partial, path)?;
+    println!("  Saved to {}", path.display());
+
+    Ok(())
+}
+
+/// `ggml-tiny.en.bin` downloads to `ggml-tiny.en.bin.part`
+fn partial_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".part");
+    path.with_file_name(name)
+}
+
+/// Keys are only readable by the current user where that can be enforced
+fn write_private(path: &Path, contents: &str) -> io::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    options.open(path)?.write_all(contents.as_bytes())
+}
+
+/// `.env` contents with `keys` replacing any existing entries of the same name
+fn render_env(existing: &str, keys: &[(String, String)]) -> String {
+    let mut rendered: String = existing
+        .lines()
+        .filter(|line| {
+            parse_env(line)
+                .first()
+                .is_none_or(|(name, _)| !keys.iter().any(|(key, _)| key == name))
+        })
+        .map(|line| format!("{}\n", line))
+        .collect();
+
+    for (key, value) in keys {
+        rendered.push_str(&format!("{}={}\n", key, value));
+    }
+
+    rendered
+}
+
+fn render_config(providers: &[AIProvider], model_path: &str) -> String {
+    let providers: Vec<String> = providers.iter().map(|provider| toml_string(provider.key())).collect();
+
+    format!(
+        "# Written by clipster --setup, see the README for every option\n\
+         providers = [{}]\n\n\
+         [whisper]\n\
+         model_path = {}\n",
+        providers.join(", "),
+        toml_string(model_path)
+    )
+}
+
+fn toml_string(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_env_replaces_existing_keys() {
+        let existing = "# clipster\nOPENAI_API_KEY=old\nOTHER=kept\n";
+        let keys = vec![("OPENAI_API_KEY".to_string(), "new".to_string())];
+
+        assert_eq!(render_env(existing, &keys), "# clipster\nOTHER=kept\nOPENAI_API_KEY=new\n");
+    }
+
+    #[test]
+    fn test_partial_path_keeps_extension() {
+        assert_eq!(
+            partial_path(Path::new("/home/me/.config/clipster/models/ggml-tiny.en.bin")),
+            PathBuf::from("/home/me/.config/clipster/models/ggml-tiny.en.bin.part")
+        );
+    }
+
+    #[test]
+    fn test_render_config_parses() {
+        let rendered = render_config(&[AIProvider::OpenAI, AIProvider::Mistral], r"C:\models\ggml-base.bin");
+        let config = Config::from_toml(&rendered).unwrap();
+
+        assert_eq!(config.providers, Some(vec!["openai".to_string(), "mistral".to_string()]));
+        assert_eq!(config.whisper.model_path, r"C:\models\ggml-base.bin");
+    }
+}
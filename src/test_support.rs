@@ -0,0 +1,176 @@
+use async_trait::async_trait;
+use std::error::Error;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use crate::ai::remote::traits::{Message, AI, USER};
+use crate::error::ClipsterError;
+
+/// What [`MockAI`] answers with
+#[derive(Debug, Clone, PartialEq)]
+pub enum MockReply {
+    /// The same text for every request
+    Canned(String),
+    /// The last user message, as sent
+    Echo,
+}
+
+/// An [`AI`] that never leaves the machine, for tests and for checking a setup offline.
+/// Replies are deterministic, optionally after a delay or as an API error.
+#[derive(Debug)]
+pub struct MockAI {
+    reply: MockReply,
+    /// Wraps replies in a code fence with this language, like a chatty model would
+    fence: Option<String>,
+    delay: Duration,
+    /// Status and body of the [`ClipsterError::ApiError`] every request fails with
+    error: Option<(u16, String)>,
+    /// Reported by [`AI::returns_json`], like an OpenAI client in JSON mode
+    json: bool,
+    calls: AtomicUsize,
+}
+
+impl MockAI {
+    pub fn new(reply: MockReply) -> Self {
+        Self {
+            reply,
+            fence: None,
+            delay: Duration::ZERO,
+            error: None,
+            json: false,
+            calls: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn canned(response: &str) -> Self {
+        Self::new(MockReply::Canned(response.to_string()))
+    }
+
+    pub fn echo() -> Self {
+        Self::new(MockReply::Echo)
+    }
+
+    pub fn with_fence(mut self, language: &str) -> Self {
+        self.fence = Some(language.to_string());
+        self
+    }
+
+    /// Waits this long before every reply (or error), to stand in for a slow provider
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Fails every request with `status` and `body`, as if the provider had answered that
+    pub fn with_error(mut self, status: u16, body: &str) -> Self {
+        self.error = Some((status, body.to_string()));
+        self
+    }
+
+    pub fn with_json(mut self, json: bool) -> Self {
+        self.json = json;
+        self
+    }
+
+    /// Requests (and warm-ups) made so far, failed ones included
+    pub fn calls(&self) -> usize {
+        self.calls.load(Ordering::SeqCst)
+    }
+
+    /// Counts the request, waits out the delay and fails with the configured error, if any
+    async fn respond(&self) -> Result<(), ClipsterError> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        if !self.delay.is_zero() {
+            tokio::time::sleep(self.delay).await;
+        }
+
+        match &self.error {
+            Some((status, body)) => Err(ClipsterError::ApiError {
+                provider: "mock".to_string(),
+                status: *status,
+                body: body.clone(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    async fn reply(&self, messages: &[Message]) -> Result<String, ClipsterError> {
+        self.respond().await?;
+
+        let text = match &self.reply {
+            MockReply::Canned(response) => response.clone(),
+            MockReply::Echo => messages
+                .iter()
+                .rev()
+                .find(|message| message.role == USER)
+                .map(|message| message.content.clone())
+                .ok_or_else(|| ClipsterError::InvalidResponse("No user message to echo".to_string()))?,
+        };
+
+        Ok(match &self.fence {
+            Some(language) => format!("```{}\n{}\n```", language, text),
+            None => text,
+        })
+    }
+}
+
+#[async_trait]
+impl AI for MockAI {
+    fn name(&self) -> String {
+        match self.reply {
+            MockReply::Canned(_) => "mock:canned".to_string(),
+            MockReply::Echo => "mock:echo".to_string(),
+        }
+    }
+
+    fn returns_json(&self) -> bool {
+        self.json
+    }
+
+    async fn chat(&self, messages: Vec<Message>) -> Result<String, Box<dyn Error>> {
+        Ok(self.reply(&messages).await?)
+    }
+
+    /// Counted and failed like any other request
+    async fn warm_up(&self) -> Result<(), Box<dyn Error>> {
+        Ok(self.respond().await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_mock_echoes_last_user_message() {
+        let ai = MockAI::echo().with_fence("bash");
+        let messages = vec![Message::system("Code only"), Message::user("ls"), Message::assistant("ls"), Message::user("ls -la")];
+
+        assert_eq!(ai.chat(messages).await.unwrap(), "```bash\nls -la\n```");
+        assert_eq!(ai.name(), "mock:echo");
+    }
+
+    #[tokio::test]
+    async fn test_mock_canned_counts_calls() {
+        let ai = MockAI::canned("git status");
+
+        assert_eq!(ai.generate("anything").await.unwrap(), "git status");
+        assert_eq!(ai.generate("else").await.unwrap(), "git status");
+        assert_eq!(ai.calls(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_mock_error_after_delay() {
+        let ai = MockAI::canned("ls").with_delay(Duration::from_millis(20)).with_error(503, "overloaded");
+
+        let started = std::time::Instant::now();
+        let err = ai.generate("Hi").await.unwrap_err();
+
+        assert!(started.elapsed() >= Duration::from_millis(20));
+        assert!(matches!(
+            err.downcast_ref::<ClipsterError>(),
+            Some(ClipsterError::ApiError { status: 503, body, .. }) if body == "overloaded"
+        ));
+        assert_eq!(ai.calls(), 1);
+    }
+}
@@ -0,0 +1,548 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc;
+use tokio::sync::Mutex;
+
+use clipster::ai::local::whisper::{SegmentCallback, WhisperModel};
+use clipster::assistant::{
+    build_user_prompt, format_clipboard, get_ai_response, get_ai_response_streaming, post_process, preview,
+    with_trailing_newline, Conversation,
+};
+use clipster::audio_processing::{self, WHISPER_SAMPLE_RATE};
+use clipster::clipboard::ClipboardBackend;
+use clipster::error::ClipsterError;
+use clipster::history;
+use clipster::metrics::SessionStats;
+use clipster::postprocess::Chain;
+use clipster::redact::Redactor;
+
+use crate::config::Config;
+use crate::profiles::ActiveProfile;
+use crate::{auto_paste, copy_to_clipboard, describe_error, is_low_confidence, notify, save_transcript, AIConfig};
+
+/// Response held back for low transcription confidence: (provider name, clipboard text)
+pub type PendingCopy = Arc<Mutex<Option<(String, String)>>>;
+
+/// Transcription language picked with the language hotkey, `None` to auto-detect. Kept apart from
+/// the model so switching never waits for a transcription to finish.
+pub type SharedLanguage = Arc<std::sync::Mutex<Option<String>>>;
+
+/// Follow-up context for `[history]`, kept apart per hotkey target so providers never see each other's turns
+pub type Conversations = Arc<std::sync::Mutex<HashMap<String, Conversation>>>;
+
+/// A finished recording waiting to be transcribed and sent
+pub struct Job {
+    pub samples: Vec<f32>,
+    pub sample_rate: u32,
+    pub ai_config: AIConfig,
+}
+
+/// Everything needed to turn a recording into a clipboard entry, shared with the hotkey loop
+pub struct Worker {
+    pub config: Arc<Config>,
+    /// Only locked on a blocking thread, while decoding
+    pub whisper: Arc<std::sync::Mutex<WhisperModel>>,
+    /// Applied to the model before each transcription
+    pub language: SharedLanguage,
+    /// Set by the cancel hotkey to stop the transcription in progress, cleared as each job starts
+    pub cancel: Arc<AtomicBool>,
+    pub clipboard: Arc<Mutex<Box<dyn ClipboardBackend>>>,
+    /// Supplies the system prompt, switchable while jobs are queued
+    pub profiles: ActiveProfile,
+    pub post_processor: Chain,
+    pub redactor: Redactor,
+    pub pending_copy: PendingCopy,
+    pub conversations: Conversations,
+    pub stats: Arc<std::sync::Mutex<SessionStats>>,
+    /// Where `--save-audio` keeps what Whisper receives
+    pub save_audio_dir: Option<PathBuf>,
+    /// `--dry-run`: print responses instead of copying them, the clipboard is never touched
+    pub dry_run: bool,
+}
+
+impl Worker {
+    /// Processes jobs one at a time in the order they were sent.
+    /// The queue holds at most `config.queue.capacity` jobs, see [`enqueue`].
+    pub fn spawn(self) -> mpsc::Sender<Job> {
+        let (sender, mut receiver) = mpsc::channel::<Job>(self.config.queue.capacity.max(1));
+
+        tokio::spawn(async move {
+            while let Some(job) = receiver.recv().await {
+                self.process(job).await;
+            }
+        });
+
+        sender
+    }
+
+    async fn process(&self, job: Job) {
+        let config = &self.config;
+        let AIConfig { name, ai, user_template, persona } = job.ai_config;
+
+        println!("Processing with {} ({})...", name, ai.name());
+
+        if job.samples.is_empty() {
+            return;
+        }
+
+        if config.audio.save_last_recording {
+            save_last_recording(&job.samples, job.sample_rate);
+        }
+
+        let normalized = match prepare_audio(config, &job.samples, job.sample_rate) {
+            Ok(Some(normalized)) => normalized,
+            Ok(None) => {
+                println!("Only silence recorded, skipping\n");
+                return;
+            }
+            Err(e) => {
+                eprintln!("Resampling Error: {}\n", e);
+                return;
+            }
+        };
+
+        if let Some(dir) = &self.save_audio_dir {
+            save_prepared_audio(dir, &normalized);
+        }
+
+        // Transcribe on a blocking thread, decoding takes seconds of CPU that would stall the runtime
+        let whisper = Arc::clone(&self.whisper);
+        let language = self.language.lock().unwrap().clone();
+        let partial_results = config.whisper.partial_results;
+        let cancel = Arc::clone(&self.cancel);
+        cancel.store(false, Ordering::Relaxed);
+        let transcription = tokio::task::spawn_blocking(move || {
+            let mut whisper = whisper.lock().unwrap();
+            whisper.set_language(language.as_deref());
+            let on_segment: Option<SegmentCallback> =
+                partial_results.then(|| Box::new(|text: &str| println!("  ... {}", text)) as SegmentCallback);
+
+            let transcription = whisper
+                .transcribe_with_segments(&normalized, on_segment, Some(cancel))
+                .map(|text| (text, whisper.last_confidence()))
+                .map_err(|e| e.to_string());
+            if let Some(language) = whisper.detected_language() {
+                println!("Detected language: {}", language);
+            }
+            transcription
+        })
+        .await
+        .unwrap_or_else(|e| Err(format!("Transcription task failed: {}", e)));
+
+        let (text, confidence) = match transcription {
+            Ok(transcription) => transcription,
+            Err(e) => {
+                eprintln!("Transcription Error: {}", e);
+                return;
+            }
+        };
+
+        println!("You said: {}", text);
+
+        let low_confidence = is_low_confidence(config.whisper.min_confidence, confidence);
+        if low_confidence {
+            eprintln!(
+                "Warning: low transcription confidence ({:.2}), this may be misheard",
+                confidence.unwrap_or_default()
+            );
+        }
+
+        // Two-stage mode: the raw transcript is pasteable while the AI works
+        if config.output.transcript_first && !low_confidence && !self.dry_run {
+            match copy_to_clipboard(&self.clipboard, &text).await {
+                Ok(()) => println!("Transcript copied, refining with {}...", name),
+                Err(e) => eprintln!("Clipboard Error: {}", e),
+            }
+        }
+
+        // Secrets stay local, the clipboard still gets the real transcript
+        let (prompt, redacted) = self.redactor.redact(&text);
+        if redacted > 0 {
+            println!("Redacted {} match(es) before sending", redacted);
+        }
+
+        let system_prompt = self.profiles.lock().unwrap().active().system_prompt.clone();
+        let history = self
+            .conversations
+            .lock()
+            .unwrap()
+            .get(&name)
+            .map(Conversation::messages)
+            .unwrap_or_default();
+
+        // Get AI response, waiting out short outages so queued recordings aren't lost.
+        // Once part of a streamed response is printed, a retry would print it again.
+        let retry_delay = Duration::from_secs(config.queue.retry_delay_secs);
+        let streamed = std::sync::Mutex::new(String::new());
+        let retryable = |e: &ClipsterError| e.is_retryable() && streamed.lock().unwrap().is_empty();
+        let result = with_retries(config.queue.retry_attempts, retry_delay, retryable, || async {
+            if !config.output.stream_response {
+                return get_ai_response(&ai, &system_prompt, &history, &prompt, user_template.as_deref(), persona.as_deref(), &self.post_processor)
+                    .await
+                    .map_err(ClipsterError::from_boxed);
+            }
+
+            let mut print_chunk = |chunk: &str| {
+                streamed.lock().unwrap().push_str(chunk);
+                print!("{}", chunk);
+                let _ = std::io::stdout().flush();
+            };
+            let response = get_ai_response_streaming(
+                &ai,
+                &system_prompt,
+                &history,
+                &prompt,
+                user_template.as_deref(),
+                persona.as_deref(),
+                &self.post_processor,
+                &mut print_chunk,
+            )
+            .await
+            .map_err(ClipsterError::from_boxed);
+            println!();
+            response
+        })
+        .await;
+
+        // A stream that broke off still leaves everything printed so far, which beats nothing
+        let partial = streamed.into_inner().unwrap();
+        let result = match result {
+            Err(e) if !partial.is_empty() => {
+                eprintln!("Warning: the response broke off ({}), keeping what arrived", describe_error(&e));
+                Ok((post_process(&ai, &self.post_processor, partial), None))
+            }
+            result => result,
+        };
+
+        let response = match result {
+            Ok((response, usage)) => {
+                // Under the provider that answered, a fallback chain has no price of its own
+                let responder = ai.responder();
+                let mut stats = self.stats.lock().unwrap();
+                stats.record(&responder, usage);
+                if let Some(usage) = usage {
+                    println!("{}", stats.request_line(&responder, usage, &config.prices));
+                }
+                response
+            }
+            Err(e) => {
+                eprintln!("AI Error: {}", describe_error(&e));
+                if self.dry_run {
+                    println!("{}", dry_run_report(&name, &text, "(no response)"));
+                } else {
+                    save_transcript(&self.clipboard, &text, config.on_ai_error).await;
+                }
+                return;
+            }
+        };
+
+        if config.history.save {
+            save_history(config, Config::history_path(), &name, &prompt, &response);
+        }
+
+        if config.history.max_turns > 0 {
+            self.conversations
+                .lock()
+                .unwrap()
+                .entry(name.clone())
+                .or_insert_with(|| Conversation::new(config.history.max_turns))
+                .record(&build_user_prompt(&prompt, user_template.as_deref(), persona.as_deref()), &response);
+        }
+
+        // Copy to clipboard
+        let summary = format!("AI Assistant ({})", name);
+        let content = match &config.output.clipboard_template {
+            Some(template) => format_clipboard(template, &text, &response),
+            None => response.clone(),
+        };
+        let clipboard_text = with_trailing_newline(&content, config.output.trailing_newline);
+
+        if self.dry_run {
+            println!("{}", dry_run_report(&name, &text, &clipboard_text));
+            return;
+        }
+
+        if low_confidence {
+            println!("Response:\n{}\n", response);
+            println!("Not copied, press Ctrl+Shift+Y to copy it anyway\n");
+            notify(&summary, "Low confidence, press Ctrl+Shift+Y to copy the response.");
+            *self.pending_copy.lock().await = Some((name, clipboard_text));
+            return;
+        }
+
+        if let Err(e) = copy_to_clipboard(&self.clipboard, &clipboard_text).await {
+            eprintln!("Clipboard Error: {}", e);
+            println!("Response:\n{}\n", response);
+            save_failed_copy(config, Config::history_path(), &name, &prompt, &response);
+            notify(&summary, "Copy failed, response printed to the terminal.");
+            return;
+        }
+        println!("Copied to clipboard via {}!", name);
+        auto_paste(config).await;
+        if config.output.notify_transcript {
+            notify(&summary, &format!("You said: {}", text));
+        } else {
+            notify(&summary, "Response copied! Ready to paste.");
+        }
+
+        println!("Preview: {}\n", preview(&response, config.output.preview_chars));
+    }
+}
+
+/// Resamples and cleans up a recording for Whisper as configured under `[audio]`.
+/// `None` means only silence was left after trimming.
+pub fn prepare_audio(config: &Config, samples: &[f32], sample_rate: u32) -> Result<Option<Vec<f32>>, String> {
+    let resampled = audio_processing::resample_to_16khz_with_quality(samples, sample_rate, config.audio.resample_quality)
+        .map_err(|e| e.to_string())?;
+    let mut normalized = config.audio.normalization.apply(&resampled, config.audio.rms_target);
+
+    if config.audio.noise_reduction {
+        normalized = audio_processing::reduce_noise(
+            &normalized,
+            config.audio.noise_window_ms,
+            config.audio.noise_floor_gain,
+        );
+    }
+
+    if config.audio.trim_edges {
+        normalized = audio_processing::trim_silence_with_margin(
+            &normalized,
+            config.audio.trim_threshold,
+            WHISPER_SAMPLE_RATE,
+            config.audio.trim_margin_ms,
+        );
+        if normalized.is_empty() {
+            return Ok(None);
+        }
+    }
+
+    Ok(Some(normalized))
+}
+
+/// Overwrites the previous recording, a failure only costs `--replay-last` so it's just a warning
+fn save_last_recording(samples: &[f32], sample_rate: u32) {
+    let Some(path) = Config::last_recording_path() else {
+        eprintln!("Warning: can't find the config directory, recording not saved");
+        return;
+    };
+
+    let saved = path
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .map_err(|e| e.to_string())
+        .and_then(|()| audio_processing::save_wav(samples, sample_rate, &path).map_err(|e| e.to_string()));
+
+    if let Err(e) = saved {
+        eprintln!("Warning: failed to save recording to {}: {}", path.display(), e);
+    }
+}
+
+/// Keeps the resampled and cleaned up buffer in `dir`, for checking what Whisper actually heard
+fn save_prepared_audio(dir: &Path, samples: &[f32]) {
+    let millis = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_millis());
+
+    let saved = std::fs::create_dir_all(dir)
+        .and_then(|()| reserve_wav_path(dir, &format!("clipster-{}", millis)))
+        .map_err(|e| e.to_string())
+        .and_then(|path| {
+            audio_processing::save_wav(samples, WHISPER_SAMPLE_RATE, &path)
+                .map(|()| path)
+                .map_err(|e| e.to_string())
+        });
+
+    match saved {
+        Ok(path) => println!("Saved audio to {}", path.display()),
+        Err(e) => eprintln!("Warning: failed to save audio to {}: {}", dir.display(), e),
+    }
+}
+
+/// Creates `stem.wav` in `dir`, or `stem-1.wav` and so on when another recording (or another
+/// clipster) got there first. Creating the file claims the name, so two writers never share one.
+fn reserve_wav_path(dir: &Path, stem: &str) -> std::io::Result<PathBuf> {
+    for attempt in 0u32.. {
+        let name = match attempt {
+            0 => format!("{}.wav", stem),
+            n => format!("{}-{}.wav", stem, n),
+        };
+        let path = dir.join(name);
+
+        match std::fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(_) => return Ok(path),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    unreachable!("ran out of suffixes for {}", stem)
+}
+
+/// Keeps a response the clipboard never got in the `--history` log, even with `[history] save` off.
+/// With it on, the response was logged before copying already.
+fn save_failed_copy(config: &Config, path: Option<PathBuf>, provider: &str, transcript: &str, response: &str) {
+    if !config.history.save {
+        save_history(config, path, provider, transcript, response);
+    }
+}
+
+/// Appends the exchange to the `--history` log at `path`, a failure is only worth a warning
+fn save_history(config: &Config, path: Option<PathBuf>, provider: &str, transcript: &str, response: &str) {
+    let Some(path) = path else {
+        eprintln!("Warning: can't find the data directory, response not added to the history");
+        return;
+    };
+
+    let entry = history::HistoryEntry::now(provider, transcript, response);
+    if let Err(e) = history::append(&path, &entry, config.history.max_saved) {
+        eprintln!("Warning: failed to add the response to {}: {}", path.display(), e);
+    }
+}
+
+/// What would have been copied, between markers so it stands out from the streamed output
+fn dry_run_report(provider: &str, transcript: &str, response: &str) -> String {
+    format!(
+        "---- Dry run, clipboard untouched ----\nProvider: {}\nYou said: {}\nResponse:\n{}\n--------------------------------------\n",
+        provider,
+        transcript,
+        response.trim_end_matches('\n')
+    )
+}
+
+/// Queues a job without blocking the hotkey loop, dropping it with a warning when the queue is full
+pub fn enqueue(jobs: &mpsc::Sender<Job>, job: Job) {
+    let waiting = jobs.max_capacity() - jobs.capacity();
+
+    match jobs.try_send(job) {
+        Ok(()) if waiting > 0 => println!("Queued behind {} recording(s)", waiting),
+        Ok(()) => {}
+        Err(mpsc::error::TrySendError::Full(_)) => {
+            eprintln!("Queue full ({} recordings waiting), dropping this one\n", waiting);
+            notify("Recording dropped", "Too many recordings are waiting to be processed.");
+        }
+        Err(mpsc::error::TrySendError::Closed(_)) => eprintln!("Processing stopped, dropping recording\n"),
+    }
+}
+
+/// Runs `attempt` until it succeeds, trying again up to `retries` more times `delay` apart
+/// as long as the error is one `retryable` expects to clear up
+async fn with_retries<T, E, F, Fut>(
+    retries: u32,
+    delay: Duration,
+    retryable: impl Fn(&E) -> bool,
+    mut attempt: F,
+) -> Result<T, E>
+where
+    E: std::fmt::Display,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let mut failures = 0;
+
+    loop {
+        match attempt().await {
+            Err(e) if failures < retries && retryable(&e) => {
+                failures += 1;
+                eprintln!("{}, retrying in {}s ({}/{})", e, delay.as_secs(), failures, retries);
+                tokio::time::sleep(delay).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[test]
+    fn test_dry_run_report() {
+        assert_eq!(
+            dry_run_report("openai", "list files", "ls -la\n"),
+            "---- Dry run, clipboard untouched ----\nProvider: openai\nYou said: list files\nResponse:\nls -la\n--------------------------------------\n"
+        );
+    }
+
+    #[test]
+    fn test_save_failed_copy_logs_even_with_history_save_off() {
+        let dir = std::env::temp_dir().join(format!("clipster-failed-copy-{}", std::process::id()));
+        let path = dir.join("history.jsonl");
+        let mut config = Config::default();
+        assert!(!config.history.save);
+
+        save_failed_copy(&config, Some(path.clone()), "openai", "list files", "ls -la");
+        let entries = history::read_last(&path, 10).unwrap();
+
+        // With save on, the response is in the log already and isn't added twice
+        config.history.save = true;
+        save_failed_copy(&config, Some(path.clone()), "openai", "list files", "ls -la");
+        let after = history::read_last(&path, 10).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(entries.len(), 1);
+        assert_eq!((entries[0].provider.as_str(), entries[0].response.as_str()), ("openai", "ls -la"));
+        assert_eq!(after, entries);
+    }
+
+    #[test]
+    fn test_reserve_wav_path_never_reuses_a_name() {
+        let dir = std::env::temp_dir().join(format!("clipster-save-audio-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let first = reserve_wav_path(&dir, "clipster-1").unwrap();
+        let second = reserve_wav_path(&dir, "clipster-1").unwrap();
+        let third = reserve_wav_path(&dir, "clipster-1").unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(first, dir.join("clipster-1.wav"));
+        assert_eq!(second, dir.join("clipster-1-1.wav"));
+        assert_eq!(third, dir.join("clipster-1-2.wav"));
+    }
+
+    #[tokio::test]
+    async fn test_with_retries_until_success() {
+        let calls = AtomicU32::new(0);
+
+        let result = with_retries(3, Duration::ZERO, |_: &String| true, || async {
+            match calls.fetch_add(1, Ordering::Relaxed) {
+                0 | 1 => Err("429 Too Many Requests".to_string()),
+                _ => Ok("done"),
+            }
+        })
+        .await;
+
+        assert_eq!(result, Ok("done"));
+        assert_eq!(calls.load(Ordering::Relaxed), 3);
+    }
+
+    #[tokio::test]
+    async fn test_with_retries_gives_up() {
+        let calls = AtomicU32::new(0);
+
+        let result: Result<(), String> = with_retries(2, Duration::ZERO, |_| true, || async {
+            calls.fetch_add(1, Ordering::Relaxed);
+            Err("offline".to_string())
+        })
+        .await;
+
+        assert_eq!(result, Err("offline".to_string()));
+        assert_eq!(calls.load(Ordering::Relaxed), 3);
+    }
+
+    #[tokio::test]
+    async fn test_with_retries_skips_errors_that_would_repeat() {
+        let calls = AtomicU32::new(0);
+
+        let result: Result<(), ClipsterError> = with_retries(2, Duration::ZERO, ClipsterError::is_retryable, || async {
+            calls.fetch_add(1, Ordering::Relaxed);
+            Err(ClipsterError::ApiError { provider: "openai".to_string(), status: 401, body: "bad key".to_string() })
+        })
+        .await;
+
+        assert!(matches!(result, Err(ClipsterError::ApiError { status: 401, .. })));
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+    }
+}
@@ -0,0 +1,145 @@
+use std::cell::RefCell;
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+use clipster::ai::local::whisper::WhisperModel;
+use clipster::ai::remote::traits::AI;
+use clipster::assistant::{get_ai_response, get_ai_response_streaming, with_trailing_newline, SYSTEM_PROMPT};
+use clipster::audio_processing::{normalize_audio, resample_to_16khz};
+use clipster::postprocess::{Chain, MaxLength, StripFences};
+use clipster::test_support::MockAI;
+
+/// 0.6s mono 16-bit recording at 44.1kHz: 0.1s silence, 0.4s tone, 0.1s silence
+const FIXTURE: &str = "tests/fixtures/tone_44k.wav";
+const MODEL: &str = "models/ggml-tiny.en.bin";
+
+fn load_fixture() -> (Vec<f32>, u32) {
+    let mut reader = hound::WavReader::open(FIXTURE).unwrap();
+    let sample_rate = reader.spec().sample_rate;
+    let samples = reader
+        .samples::<i16>()
+        .map(|sample| sample.unwrap() as f32 / i16::MAX as f32)
+        .collect();
+
+    (samples, sample_rate)
+}
+
+fn preprocess(samples: &[f32], sample_rate: u32) -> Vec<f32> {
+    let resampled = resample_to_16khz(samples, sample_rate).unwrap();
+    normalize_audio(&resampled)
+}
+
+#[test]
+fn test_pipeline_preprocesses_fixture() {
+    let (samples, sample_rate) = load_fixture();
+    assert_eq!(sample_rate, 44100);
+
+    let audio = preprocess(&samples, sample_rate);
+
+    // 0.6s at 16kHz, with some slack for the resampler's edges
+    let expected = (0.6 * 16000.0) as usize;
+    assert!(audio.len().abs_diff(expected) < 500, "got {} samples", audio.len());
+
+    let peak = audio.iter().map(|s| s.abs()).fold(0.0f32, f32::max);
+    assert!((peak - 1.0).abs() < 1e-6);
+}
+
+#[tokio::test]
+async fn test_pipeline_response_reaches_output_with_mock_ai() {
+    let ai: Arc<Box<dyn AI>> = Arc::new(Box::new(MockAI::echo().with_fence("text")));
+    let mut output = Vec::new();
+
+    let post_processor = Chain::new().then(StripFences);
+
+    let (response, _) = get_ai_response(&ai, SYSTEM_PROMPT, &[], "list files by size", Some("Shell command for: {transcript}"), None, &post_processor)
+        .await
+        .unwrap();
+    output.push(with_trailing_newline(&response, false));
+
+    assert_eq!(output, vec!["Shell command for: list files by size"]);
+}
+
+#[tokio::test]
+async fn test_pipeline_streams_chunks_before_post_processing() {
+    let ai: Arc<Box<dyn AI>> = Arc::new(Box::new(MockAI::echo().with_fence("text")));
+    let mut chunks = Vec::new();
+
+    let (response, _) = get_ai_response_streaming(&ai, SYSTEM_PROMPT, &[], "ls", None, None, &StripFences, &mut |chunk: &str| {
+        chunks.push(chunk.to_string())
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(chunks, vec!["```text\nls\n```"]);
+    assert_eq!(response, "ls");
+}
+
+#[tokio::test]
+async fn test_pipeline_leaves_json_responses_alone() {
+    let json = "{\"command\": \"ls -la\"}\n";
+    let ai: Arc<Box<dyn AI>> = Arc::new(Box::new(MockAI::canned(json).with_json(true)));
+    let post_processor = Chain::new().then(StripFences).then(MaxLength(5));
+
+    let (response, _) = get_ai_response(&ai, SYSTEM_PROMPT, &[], "list files", None, None, &post_processor).await.unwrap();
+
+    assert_eq!(response, json);
+}
+
+#[tokio::test]
+async fn test_pipeline_surfaces_provider_errors() {
+    let ai: Arc<Box<dyn AI>> = Arc::new(Box::new(MockAI::canned("ls").with_error(503, "overloaded")));
+
+    let err = get_ai_response(&ai, SYSTEM_PROMPT, &[], "list files", None, None, &StripFences).await.unwrap_err();
+
+    assert_eq!(err.to_string(), "mock API error 503 Service Unavailable: overloaded");
+}
+
+#[tokio::test]
+async fn test_pipeline_end_to_end_with_whisper() {
+    if !Path::new(MODEL).exists() {
+        eprintln!("Skipping: Whisper model not found at {}", MODEL);
+        return;
+    }
+
+    let (samples, sample_rate) = load_fixture();
+    let audio = preprocess(&samples, sample_rate);
+
+    let mut whisper = WhisperModel::new(MODEL).unwrap();
+    let segments = Rc::new(RefCell::new(Vec::new()));
+    let collected = segments.clone();
+    let transcript = whisper
+        .transcribe_with_segments(&audio, Some(Box::new(move |text: &str| collected.borrow_mut().push(text.to_string()))), None)
+        .unwrap();
+    let streamed = segments.borrow().join(" ");
+    assert_eq!(
+        streamed.split_whitespace().collect::<Vec<_>>(),
+        transcript.split_whitespace().collect::<Vec<_>>()
+    );
+
+    let ai: Arc<Box<dyn AI>> = Arc::new(Box::new(MockAI::echo().with_fence("text")));
+    let mut output = Vec::new();
+
+    let (response, _) = get_ai_response(&ai, SYSTEM_PROMPT, &[], &transcript, None, None, &StripFences).await.unwrap();
+    output.push(with_trailing_newline(&response, false));
+
+    assert_eq!(output, vec![transcript.trim().to_string()]);
+}
+
+#[test]
+fn test_transcription_cancelled_with_whisper() {
+    if !Path::new(MODEL).exists() {
+        eprintln!("Skipping: Whisper model not found at {}", MODEL);
+        return;
+    }
+
+    let (samples, sample_rate) = load_fixture();
+    let audio = preprocess(&samples, sample_rate);
+
+    let mut whisper = WhisperModel::new(MODEL).unwrap();
+    let cancel = Arc::new(AtomicBool::new(true));
+    let err = whisper.transcribe_with_segments(&audio, None, Some(cancel)).unwrap_err();
+
+    assert_eq!(err.to_string(), "Transcription cancelled");
+}